@@ -2,477 +2,11889 @@
 
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
-#[ink::contract]
+/// Chain extension that mirrors registered DIDs into a runtime identity pallet
+#[cfg(feature = "did-anchor")]
+#[ink::chain_extension]
+pub trait DidAnchor {
+    type ErrorCode = DidAnchorError;
+
+    /// Anchors a DID in runtime storage
+    #[ink(extension = 0x5344_0001)]
+    fn anchor_did(did: ink::prelude::vec::Vec<u8>);
+
+    /// Removes a DID from runtime storage
+    #[ink(extension = 0x5344_0002)]
+    fn remove_did(did: ink::prelude::vec::Vec<u8>);
+}
+
+/// Failure reported by the DID-anchoring chain extension
+#[cfg(feature = "did-anchor")]
+#[derive(scale::Encode, scale::Decode, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum DidAnchorError {
+    /// The runtime does not support anchoring (e.g. no identity pallet is configured)
+    Unsupported,
+    /// The runtime rejected the anchoring request
+    Failed,
+}
+
+#[cfg(feature = "did-anchor")]
+impl ink::env::chain_extension::FromStatusCode for DidAnchorError {
+    fn from_status_code(status_code: u32) -> Result<(), Self> {
+        match status_code {
+            0 => Ok(()),
+            1 => Err(Self::Unsupported),
+            _ => Err(Self::Failed),
+        }
+    }
+}
+
+/// Default environment extended with the DID-anchoring chain extension
+#[cfg(feature = "did-anchor")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum DidAnchorEnvironment {}
+
+#[cfg(feature = "did-anchor")]
+impl ink::env::Environment for DidAnchorEnvironment {
+    const MAX_EVENT_TOPICS: usize =
+        <ink::env::DefaultEnvironment as ink::env::Environment>::MAX_EVENT_TOPICS;
+
+    type AccountId = <ink::env::DefaultEnvironment as ink::env::Environment>::AccountId;
+    type Balance = <ink::env::DefaultEnvironment as ink::env::Environment>::Balance;
+    type Hash = <ink::env::DefaultEnvironment as ink::env::Environment>::Hash;
+    type BlockNumber = <ink::env::DefaultEnvironment as ink::env::Environment>::BlockNumber;
+    type Timestamp = <ink::env::DefaultEnvironment as ink::env::Environment>::Timestamp;
+
+    type ChainExtension = DidAnchor;
+}
+
+#[cfg_attr(
+    feature = "did-anchor",
+    ink::contract(env = crate::DidAnchorEnvironment)
+)]
+#[cfg_attr(not(feature = "did-anchor"), ink::contract)]
 mod db_contract {
+    use ink::env::call::{build_call, ExecutionInput, Selector};
+    use ink::env::hash::Blake2x256;
+    use ink::prelude::vec;
     use ink::storage::Mapping;
     use scale_info::prelude::vec::Vec;
 
     /// Node multiaddress type
     type Multiaddr = Vec<u8>;
     /// Decentralized Identifier type
+    #[allow(clippy::upper_case_acronyms)]
     type DID = Vec<u8>;
     /// IPFS content identifier type
+    #[allow(clippy::upper_case_acronyms)]
     type CID = Vec<u8>;
+    /// Identifier of the network (mainnet, testnet...) a deployment serves
+    type NetworkId = u32;
+    /// Identifier of an on-chain access token
+    type TokenId = u64;
+    /// A (user, application) DID pair
+    type AccessPair = (DID, DID);
+    /// A DID method segment and a position among the DIDs registered under it
+    type PrefixSlot = (Vec<u8>, u32);
 
-    #[derive(scale::Decode, scale::Encode, Default, Clone)]
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
     #[cfg_attr(
         feature = "std",
         derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
     )]
-    struct AccountInfo {
-        did_document_uri: Vec<u8>, // DID document IPFS CID
-        hashtable_cid: Vec<u8>,    // Application/User Hashtable CID
-        auth_material: Vec<u8>, // This helps authenticate applications during node initialization
+    pub struct AccountInfo {
+        did_document_uri: Vec<u8>,              // DID document IPFS CID
+        hashtable_cid: Vec<u8>,                 // Application/User Hashtable CID
+        auth_hash: Hash, // Blake2 hash of the material that authenticates applications during node initialization
+        auth_policy: Option<AuthPolicy>, // Application-defined policy nodes enforce at connection time
+        created_at: BlockNumber,         // Block at which the account was registered
+        key_agreement: Option<KeyAgreementKey>, // Current X25519 key for encrypted channels
+        controllers: Vec<AccountId>,     // Chain accounts allowed to change the account
+        auth_key: Option<Vec<u8>>,       // Compressed ECDSA key nodes can sign challenges with
+        status: AccountStatus,           // Whether the account is currently present on the network
     }
 
-    #[ink(storage)]
-    pub struct DbContract {
-        /// Stores the possible bootnodes of the network
-        nodes: Vec<Multiaddr>,
-        /// Stores data about an application/user
-        accounts: Mapping<DID, AccountInfo>,
-        /// Stores nodes that run an applications operations (Gossipsub)
-        subscribers: Mapping<DID, Vec<Multiaddr>>,
-        /// Data access mapping application to users
-        restricted: Mapping<DID, Vec<DID>>,
+    /// Whether an account takes part in the network, has been paused by its controller or has
+    /// been suspended by an admin
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum AccountStatus {
+        Active,
+        Deactivated,
+        Suspended,
     }
 
-    /// Contract events
-    #[ink(event)]
-    pub struct AccountCreated {
-        #[ink(topic)]
-        did: DID,
+    /// An admin's suspension of an account and the appeal against it
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Suspension {
+        reason: Vec<u8>,           // Reason the admin gave for the suspension
+        suspended_at: BlockNumber, // Block at which the account was suspended
+        appeal: Option<CID>,       // CID of the controller's statement while an appeal is pending
+        ruling: Option<Vec<u8>>,   // Reason recorded when the latest appeal was rejected
     }
 
-    #[ink(event)]
-    pub struct BootNodeAdded {
-        #[ink(topic)]
-        address: Multiaddr,
+    /// Pointer to an application's authentication policy document
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct AuthPolicy {
+        policy_cid: CID,   // IPFS CID of the policy document
+        policy_hash: Hash, // Hash nodes use to verify the fetched document
     }
 
-    #[ink(event)]
-    pub struct BootNodeRemoved {
-        #[ink(topic)]
-        address: Multiaddr,
+    /// Network id used when a deployment does not specify one
+    const DEFAULT_NETWORK_ID: NetworkId = 0;
+
+    /// Maximum number of changes kept in an application's subscriber journal
+    const MAX_JOURNAL_ENTRIES: usize = 64;
+
+    /// Maximum number of changes kept in the restriction journal
+    const MAX_RESTRICTION_LOG: u64 = 1024;
+
+    /// Maximum number of subscription requests an account can have awaiting one
+    /// application's approval
+    const MAX_PENDING_PER_REQUESTER: usize = 4;
+
+    /// Maximum number of suspension appeals awaiting a ruling
+    const MAX_PENDING_APPEALS: usize = 64;
+
+    /// Maximum number of contracts observing a single DID
+    const MAX_OBSERVERS: usize = 4;
+
+    /// Gas each observer callback may use before it is abandoned
+    const OBSERVER_GAS_LIMIT: u64 = 5_000_000_000;
+
+    /// Maximum length in bytes of a bootnode's region label
+    const MAX_REGION_LEN: usize = 32;
+
+    /// Maximum length in bytes of the issuer signature over an enclave attestation report
+    const MAX_ATTESTATION_SIGNATURE_LEN: usize = 512;
+
+    /// Maximum number of regions an application's replication policy can allow
+    const MAX_ALLOWED_REGIONS: usize = 16;
+
+    /// Maximum length in bytes of a node's multiaddress
+    const MAX_MULTIADDR_LEN: usize = 256;
+
+    /// Maximum number of addresses `add_addresses` and `remove_addresses` accept at once
+    const MAX_NODE_BATCH: usize = 20;
+
+    /// Maximum number of bootnodes returned by `get_random_bootnodes`
+    const MAX_RANDOM_BOOTNODES: u32 = 16;
+
+    /// Maximum number of council members
+    const MAX_COUNCIL_MEMBERS: usize = 16;
+
+    /// Maximum number of reserved DID prefixes
+    const MAX_RESERVED_PREFIXES: usize = 32;
+
+    /// Maximum number of nodes an application can ban
+    const MAX_BANNED_SUBSCRIBERS: usize = 64;
+
+    /// Number of votes that admit a suggested bootnode until the owner changes it
+    const DEFAULT_NODE_VOTES_REQUIRED: u32 = 3;
+
+    /// Blocks a bootnode suggestion stays open for voting until the owner changes it
+    const DEFAULT_NODE_VOTING_PERIOD: BlockNumber = 14_400;
+
+    /// Blocks an upgrade or parameter change waits before it can be executed, until the
+    /// owner changes it
+    const DEFAULT_TIMELOCK_DELAY: BlockNumber = 14_400;
+
+    /// Blocks a bootnode stays listed without a heartbeat until the owner changes it
+    /// (~7 days at 6 seconds per block)
+    const DEFAULT_NODE_TTL: BlockNumber = 100_800;
+
+    /// Value locked when a bootnode is registered through `add_address` until the owner
+    /// changes it
+    const DEFAULT_NODE_STAKE: Balance = 0;
+
+    /// Value charged to register a DID until the owner changes it
+    const DEFAULT_REGISTRATION_FEE: Balance = 0;
+
+    /// Whether listing bootnodes directly takes the `NodeRegistrar` role until the owner
+    /// changes it
+    const DEFAULT_PERMISSIONED: bool = true;
+
+    /// Share of each collected fee, in percent, burned until the owner changes it
+    const DEFAULT_FEE_BURN_PERCENT: u32 = 0;
+
+    /// Share of a slashed stake, in percent, paid to the reporter of an upheld bonded report
+    const REPORTER_SHARE_PERCENT: Balance = 50;
+
+    /// Maximum number of retired key-agreement keys kept per account
+    const MAX_KEY_HISTORY: usize = 8;
+
+    /// Maximum number of credentials an account can register
+    const MAX_CREDENTIALS: usize = 8;
+
+    /// Maximum number of bootnodes kept in the list when a deployment does not specify one
+    const DEFAULT_MAX_BOOTNODES: u32 = 10;
+
+    /// Maximum number of nodes one account can subscribe to a single application until the
+    /// owner changes it
+    const DEFAULT_MAX_SUBSCRIPTIONS_PER_ACCOUNT: u32 = 64;
+
+    /// Maximum length in bytes of a registered DID until the owner changes it
+    const DEFAULT_MAX_DID_LEN: u32 = 256;
+
+    /// Number of failed authentication attempts reported against a DID in one epoch
+    /// before `AuthFailureSpike` is emitted
+    const AUTH_FAILURE_THRESHOLD: u32 = 5;
+
+    /// Blocks a signature over an auth challenge stays valid after the block it names
+    /// (~1 minute at 6 seconds per block)
+    const AUTH_CHALLENGE_TTL: BlockNumber = 10;
+
+    /// Maximum number of chain accounts that can control a DID
+    const MAX_CONTROLLERS: usize = 4;
+
+    /// Maximum number of guardians an account can appoint
+    const MAX_GUARDIANS: usize = 8;
+
+    /// Blocks a recovery must wait after being initiated before it can be finalized
+    const RECOVERY_DELAY: BlockNumber = 14_400;
+
+    /// Epochs without a controller action after which anyone can mark an account inactive
+    /// (~6 months of 14,400-block epochs)
+    const INACTIVITY_EPOCHS: u32 = 180;
+
+    /// Epochs an account marked inactive has to return before anyone can reap it
+    const REAP_GRACE_EPOCHS: u32 = 30;
+
+    /// Maximum number of indexers that can publish checkpoints
+    const MAX_INDEXERS: usize = 16;
+
+    /// Maximum number of checkpoints kept per indexer
+    const MAX_CHECKPOINTS: usize = 16;
+
+    /// Number of recent epochs a bootnode's uptime score covers, one bit of heartbeat history
+    /// each
+    const UPTIME_WINDOW: u32 = 32;
+
+    /// Number of operators that must fail to dial a bootnode within an epoch to delist it
+    const DIAL_FAILURE_THRESHOLD: usize = 3;
+
+    /// Blocks a delisted bootnode stays off the list after its next heartbeat
+    /// (~2 hours at 6 seconds per block)
+    const PROBATION_PERIOD: BlockNumber = 1_200;
+
+    /// Subscriptions the bootnodes of a new operator can serve together
+    const ONBOARDING_QUOTA: u32 = 4;
+
+    /// Subscriptions added to an operator's quota by every clean epoch
+    const ONBOARDING_QUOTA_STEP: u32 = 2;
+
+    /// Clean epochs after which an operator's bootnodes serve without a quota
+    const TRUSTED_OPERATOR_EPOCHS: u32 = 30;
+
+    /// Version of the message set a fresh deployment starts at
+    const INITIAL_VERSION: ContractVersion = ContractVersion {
+        major: 0,
+        minor: 1,
+        patch: 0,
+    };
+
+    /// Default number of blocks in an epoch (~24 hours at 6 seconds per block)
+    const DEFAULT_EPOCH_LENGTH: BlockNumber = 14_400;
+
+    /// Snapshot of the contract's view of the current epoch
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct EpochInfo {
+        index: u32,                    // Number of epochs elapsed since deployment
+        start_block: BlockNumber,      // Block at which the current epoch started
+        remaining_blocks: BlockNumber, // Blocks left before the next epoch begins
+        epoch_length: BlockNumber,     // Configured number of blocks per epoch
     }
 
-    #[ink(event)]
-    pub struct HashTableAddressUpdated {
-        #[ink(topic)]
-        did: DID,
-        ipfs_address: CID,
+    /// Transport a multiaddress can be dialed over
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum Transport {
+        Tcp,
+        Quic,
+        WebSocket,
+        WebRtc,
     }
 
-    #[ink(event)]
-    pub struct EntryNotFound {
-        #[ink(topic)]
-        entry_value: Vec<u8>,
+    /// Bitmask of the functions a node serves, combined with `|`
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct NodeRole(u8);
+
+    impl NodeRole {
+        /// Serves as an entry point for joining nodes
+        pub const BOOTNODE: Self = Self(1);
+        /// Stores application data
+        pub const STORAGE: Self = Self(1 << 1);
+        /// Relays gossip between applications' subscribers
+        pub const RELAY: Self = Self(1 << 2);
+
+        /// Checks if every role in `other` is set
+        pub fn contains(self, other: Self) -> bool {
+            self.0 & other.0 == other.0
+        }
     }
 
-    #[ink(event)]
-    pub struct TopicSubscriptionComplete {
-        #[ink(topic)]
-        did: DID,
-        node: Multiaddr,
+    impl core::ops::BitOr for NodeRole {
+        type Output = Self;
+
+        fn bitor(self, other: Self) -> Self {
+            Self(self.0 | other.0)
+        }
     }
 
-    #[ink(event)]
-    pub struct TopicUnsubscriptionComplete {
-        #[ink(topic)]
-        did: DID,
-        node: Multiaddr,
+    /// Reason a multiaddress was rejected
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum ValidationError {
+        Length,               // Empty or longer than 256 bytes
+        Malformed,            // Missing the leading `/`, or has an empty or missing component
+        UnknownProtocol,      // Uses an unknown protocol or doesn't start with an address
+        InvalidValue,         // Has an IP address, port, host name or peer id that doesn't parse
+        UnsupportedTransport, // Can't be dialed over TCP, QUIC, WebSocket or WebRTC
     }
 
-    #[ink(event)]
-    pub struct RestrictApplicationAccess {
-        #[ink(topic)]
-        user_did: DID,
-        #[ink(topic)]
-        application_did: DID,
+    /// Checks that a multiaddress in its text form, such as `/ip4/1.2.3.4/tcp/1509`, starts
+    /// with an address, only uses known protocols with well-formed values and can be dialed
+    /// over a supported transport
+    fn validate_multiaddr(addr: &[u8]) -> core::result::Result<(), ValidationError> {
+        if addr.is_empty() || addr.len() > MAX_MULTIADDR_LEN {
+            return Err(ValidationError::Length);
+        }
+        let mut components = match addr.strip_prefix(b"/") {
+            Some(rest) => rest.split(|byte| *byte == b'/'),
+            None => return Err(ValidationError::Malformed),
+        };
+
+        let mut first = true;
+        while let Some(protocol) = components.next() {
+            if protocol.is_empty() {
+                return Err(ValidationError::Malformed);
+            }
+            let is_address = matches!(
+                protocol,
+                b"ip4" | b"ip6" | b"dns" | b"dns4" | b"dns6" | b"dnsaddr"
+            );
+            if first && !is_address {
+                return Err(ValidationError::UnknownProtocol);
+            }
+            first = false;
+
+            // protocols without a value
+            if matches!(
+                protocol,
+                b"quic"
+                    | b"quic-v1"
+                    | b"ws"
+                    | b"wss"
+                    | b"tls"
+                    | b"noise"
+                    | b"webrtc"
+                    | b"webrtc-direct"
+                    | b"p2p-circuit"
+            ) {
+                continue;
+            }
+
+            let value = match components.next() {
+                Some(value) if !value.is_empty() => value,
+                _ => return Err(ValidationError::Malformed),
+            };
+            let valid = match protocol {
+                b"ip4" => is_ip4(value),
+                b"ip6" => value
+                    .iter()
+                    .all(|byte| byte.is_ascii_hexdigit() || matches!(byte, b':' | b'.')),
+                b"dns" | b"dns4" | b"dns6" | b"dnsaddr" | b"sni" => value
+                    .iter()
+                    .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.')),
+                b"tcp" | b"udp" => parse_decimal(value).is_some_and(|port| port <= u16::MAX as u32),
+                b"p2p" | b"ipfs" | b"certhash" => value
+                    .iter()
+                    .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_')),
+                _ => return Err(ValidationError::UnknownProtocol),
+            };
+            if !valid {
+                return Err(ValidationError::InvalidValue);
+            }
+        }
+
+        if parse_transport(addr).is_none() {
+            return Err(ValidationError::UnsupportedTransport);
+        }
+        Ok(())
     }
 
-    #[ink(event)]
-    pub struct UnrestrictApplicationAccess {
-        #[ink(topic)]
-        user_did: DID,
-        #[ink(topic)]
-        application_did: DID,
+    /// Checks for a dotted-quad IPv4 address such as `192.168.0.1`
+    fn is_ip4(value: &[u8]) -> bool {
+        let octets = value.split(|byte| *byte == b'.').collect::<Vec<_>>();
+        octets.len() == 4
+            && octets
+                .iter()
+                .all(|octet| octet.len() <= 3 && parse_decimal(octet).is_some_and(|n| n <= 255))
     }
 
-    impl DbContract {
-        /// Constructor that initializes the contract storage
-        #[ink(constructor)]
-        pub fn new() -> Self {
-            Self {
-                nodes: Vec::with_capacity(10),
-                accounts: Default::default(),
-                subscribers: Default::default(),
-                restricted: Default::default(),
+    /// Parses a short unsigned decimal number
+    fn parse_decimal(value: &[u8]) -> Option<u32> {
+        if value.is_empty() || value.len() > 5 {
+            return None;
+        }
+        value.iter().try_fold(0u32, |number, byte| {
+            byte.is_ascii_digit()
+                .then(|| number * 10 + (byte - b'0') as u32)
+        })
+    }
+
+    /// Determines the transport of a multiaddress such as `/ip4/1.2.3.4/tcp/1509/ws`.
+    /// Browser transports take precedence over the TCP/UDP layer they run on.
+    fn parse_transport(addr: &[u8]) -> Option<Transport> {
+        let mut transport = None;
+        for protocol in addr.split(|byte| *byte == b'/') {
+            match protocol {
+                b"ws" | b"wss" if transport == Some(Transport::Tcp) => {
+                    transport = Some(Transport::WebSocket)
+                }
+                b"webrtc" | b"webrtc-direct" => return Some(Transport::WebRtc),
+                b"quic" | b"quic-v1" if transport.is_none() => transport = Some(Transport::Quic),
+                b"tcp" if transport.is_none() => transport = Some(Transport::Tcp),
+                _ => {}
             }
         }
+        transport
+    }
 
-        /// Checks if a DID exists
-        #[ink(message, payable)]
-        pub fn check_did_existence(&self, did: DID) -> bool {
-            self.accounts.contains(&did)
+    /// Returns the peer id carried by a multiaddress's `/p2p/` component, or an empty id
+    fn parse_peer_id(addr: &[u8]) -> Vec<u8> {
+        let mut protocols = addr.split(|byte| *byte == b'/');
+        while let Some(protocol) = protocols.next() {
+            if protocol == b"p2p" || protocol == b"ipfs" {
+                return protocols.next().unwrap_or_default().to_vec();
+            }
         }
+        Vec::new()
+    }
 
-        /// Creates an account on the network
-        #[ink(message, payable)]
-        pub fn new_account(&mut self, did: DID, hashtable_cid: CID, auth_material: Vec<u8>) {
-            // Get the account Id of the
-            // The document would be created on demand
-            let account = AccountInfo {
-                did_document_uri: Default::default(),
-                hashtable_cid,
-                auth_material,
+    /// Short-lived bearer token granting an application scoped access to a user's data
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct AccessToken {
+        app_did: DID,            // Application the token was minted for
+        user_did: DID,           // User whose data the token grants access to
+        scope: u32,              // Bitmask of the operations the token allows
+        expires_at: BlockNumber, // Last block at which the token is valid
+        issuer: AccountId,       // Account that minted the token and may revoke it
+        commitment: Hash,        // Commitment nodes check presented tokens against
+        revoked: bool,
+    }
+
+    /// Returns the blake2_256 hash under which auth material is stored
+    pub fn hash_auth_material(auth_material: &[u8]) -> Hash {
+        let mut output = [0u8; 32];
+        ink::env::hash_bytes::<Blake2x256>(auth_material, &mut output);
+        Hash::from(output)
+    }
+
+    /// Returns the challenge a node signs to prove it holds an account's auth key.
+    /// `issued_at` is a recent block number, so signatures expire instead of being replayable
+    pub fn auth_challenge(did: &DID, issued_at: BlockNumber) -> [u8; 32] {
+        let mut output = [0u8; 32];
+        ink::env::hash_encoded::<Blake2x256, _>(&(did, issued_at), &mut output);
+        output
+    }
+
+    impl AccountInfo {
+        /// Checks supplied auth material against the stored hash
+        fn verify_auth_material(&self, auth_material: &[u8]) -> bool {
+            hash_auth_material(auth_material) == self.auth_hash
+        }
+
+        /// Checks whether a chain account is one of the account's controllers
+        fn is_controller(&self, account: &AccountId) -> bool {
+            self.controllers.contains(account)
+        }
+
+        /// Checks whether the account hasn't been deactivated by its controller
+        fn is_active(&self) -> bool {
+            self.status == AccountStatus::Active
+        }
+
+        /// Checks a signature over the account's challenge for `issued_at` against its auth key
+        fn verify_auth_signature(
+            &self,
+            did: &DID,
+            issued_at: BlockNumber,
+            signature: &[u8; 65],
+        ) -> bool {
+            let Some(auth_key) = &self.auth_key else {
+                return false;
             };
+            let mut key = [0u8; 33];
+            ink::env::ecdsa_recover(signature, &auth_challenge(did, issued_at), &mut key)
+                .is_ok_and(|_| key[..] == auth_key[..])
+        }
+    }
 
-            self.accounts.insert(&did, &account);
+    /// Returns the method segment of a DID, e.g. `did:sam:apps` for `did:sam:apps:xyz`
+    fn did_prefix(did: &[u8]) -> &[u8] {
+        match did.iter().rposition(|byte| *byte == b':') {
+            Some(index) => &did[..index],
+            None => &[],
+        }
+    }
+
+    /// Propagation status of a (user, application) restriction change across subscribed nodes
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct AckStatus {
+        key_version: u32,  // Restriction change the acknowledgements refer to
+        acknowledged: u32, // Subscribed nodes that applied the change
+        replicas: u32,     // Nodes currently subscribed to the application
+    }
+
+    /// Everything a joining node needs to initialize, fetched in a single call
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct BootstrapBundle {
+        network_id: NetworkId,               // Network this deployment serves
+        bootnodes: Vec<Multiaddr>,           // Bootnodes registered for the network
+        max_bootnodes: u32,                  // Capacity of the bootnode list
+        epoch: EpochInfo,                    // Current epoch
+        registration_fee: Balance,           // Value charged to register a DID
+        node_stake: Balance,                 // Value locked to list a bootnode
+        min_client_version: ContractVersion, // Live message set; older clients must upgrade
+    }
+
+    /// A node joining or leaving an application's subscribers
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct SubscriberChange {
+        block: BlockNumber, // Block at which the change happened
+        node: Multiaddr,    // Node that joined or left
+        joined: bool,       // `true` for a subscription, `false` for an unsubscription
+    }
+
+    /// Bounded log of recent changes to an application's subscribers
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Default, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct SubscriberJournal {
+        changes: Vec<SubscriberChange>,    // Retained changes, oldest first
+        truncated_at: Option<BlockNumber>, // Block of the newest change dropped from the log
+    }
+
+    /// A user restricting or unrestricting an application
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct RestrictionChange {
+        seq: u64,           // Sequence number of the change, starting at 1
+        block: BlockNumber, // Block at which the change happened
+        app_did: DID,       // Application whose access changed
+        user_did: DID,      // User who changed it
+        restricted: bool,   // `true` for a restriction, `false` for its removal
+    }
+
+    /// Key type of a credential controlling a DID
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum CredentialType {
+        Sr25519,
+        Ed25519,
+        Ecdsa,
+        WebAuthn,
+    }
+
+    impl CredentialType {
+        /// Length in bytes of a public key of this type
+        fn key_len(&self) -> usize {
+            match self {
+                CredentialType::Sr25519 | CredentialType::Ed25519 => 32,
+                // compressed secp256k1 and P-256 points
+                CredentialType::Ecdsa | CredentialType::WebAuthn => 33,
+            }
+        }
+    }
+
+    /// A public key registered to control a DID
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Credential {
+        credential_type: CredentialType,
+        public_key: Vec<u8>,
+        added_at: BlockNumber, // Block at which the credential was registered
+        revoked: bool,
+    }
+
+    /// A node's request to join an application that vets its subscribers
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct PendingSubscription {
+        node: Multiaddr,
+        expires_at: BlockNumber, // Last block at which the request can be approved
+        requester: AccountId,    // Account that asked for the node to join
+    }
+
+    /// Where an application's subscribers may run, set through `set_replication_policy`
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Default, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct ReplicationPolicy {
+        pub allowed_regions: Vec<Vec<u8>>, // Regions a joining node must be listed in; empty allows any
+        pub min_regions: u32,              // Distinct regions the subscribers must span
+        pub max_replicas: u32,             // Subscribers the application accepts; 0 for no cap
+        pub require_attestation: bool,     // Whether joining nodes need a verified attestation
+    }
+
+    /// Initial configuration of a deployment, passed to `new_with_config`
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct InitConfig {
+        pub owner: AccountId,          // Account operating the deployment
+        pub network_id: NetworkId,     // Network the deployment serves
+        pub max_bootnodes: u32,        // Capacity of the bootnode list
+        pub epoch_length: BlockNumber, // Number of blocks per epoch
+        pub registration_fee: Balance, // Value that must be sent to create an account
+        pub node_stake: Balance,       // Value locked to register a bootnode
+        pub permissioned: bool,        // Whether only node registrars list bootnodes directly
+    }
+
+    /// Network parameters the owner can change after deployment through `update_config`
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Config {
+        pub max_bootnodes: u32,                 // Capacity of the bootnode list
+        pub max_subscriptions_per_account: u32, // Nodes one account can subscribe to an application
+        pub registration_fee: Balance,          // Value that must be sent to create an account
+        pub max_did_len: u32,                   // Maximum length in bytes of a registered DID
+        pub node_votes_required: u32,           // Votes that admit a suggested bootnode
+        pub node_voting_period: BlockNumber, // Blocks a bootnode suggestion stays open for voting
+        pub timelock_delay: BlockNumber,     // Blocks an upgrade or parameter change waits
+        pub node_ttl: BlockNumber,           // Blocks a bootnode stays listed without a heartbeat
+        pub node_stake: Balance,             // Value locked to register a bootnode
+        pub permissioned: bool,              // Whether only node registrars list bootnodes directly
+        pub fee_burn_percent: u32,           // Share of each collected fee burned, in percent
+    }
+
+    /// Settings replaced when cloning an application through `clone_app`; `None` keeps the source's
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Default, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct CloneOverrides {
+        pub hashtable_cid: Option<CID>,      // Hashtable the clone starts from
+        pub auth_hash: Option<Hash>,         // Hash of the clone's own auth material
+        pub auth_policy: Option<AuthPolicy>, // Policy nodes enforce for the clone
+    }
+
+    /// A node carried over by `import_subscribers` with the details of its subscription
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct ImportedSubscriber {
+        pub node: Multiaddr,            // Address of the subscribed node
+        pub owner: AccountId,           // Account that subscribed the node and can remove it
+        pub subscribed_at: BlockNumber, // Block the node joined at on the previous instance
+    }
+
+    /// Registry-wide counters for indexers and dashboards
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct NetworkStats {
+        accounts: u32,  // Accounts registered
+        bootnodes: u32, // Bootnodes currently listed
+        dids: u32,      // DIDs known to the contract, including subscription-only ones
+    }
+
+    /// Counters kept for a single DID
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct DidStats {
+        subscribers: u32,  // Nodes subscribed to the application
+        restrictions: u32, // Users that restricted the application
+    }
+
+    /// An X25519 public key nodes use to open encrypted channels with an account
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct KeyAgreementKey {
+        key: [u8; 32],
+        set_at: BlockNumber, // Block at which the key was set
+    }
+
+    /// Activity counted during an epoch, reported through `EpochReport` once it closes
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Default, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct EpochActivity {
+        epoch: u32,              // Epoch the counters belong to
+        accounts_created: u32,   // New DIDs registered
+        cid_updates: u32,        // Hashtable CIDs switched, directly or via an announcement
+        subscriptions: u32,      // Nodes that joined an application
+        unsubscriptions: u32,    // Nodes that left an application
+        fees_collected: Balance, // Registration and cloning fees kept by the contract
+    }
+
+    /// Guardian DIDs able to recover an account, and how many of them must approve
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct GuardianSet {
+        guardians: Vec<DID>, // Accounts whose controllers can approve a recovery
+        threshold: u32,      // Approvals required to finalize a recovery
+    }
+
+    /// A recovery of an account to a new controller, awaiting guardian approvals
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Recovery {
+        new_controller: AccountId, // Chain account that takes control once finalized
+        executable_at: BlockNumber, // First block at which the recovery can be finalized
+        approvals: Vec<DID>,       // Guardians that approved the recovery
+    }
+
+    /// State an off-chain indexer claims to have indexed
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Checkpoint {
+        block: BlockNumber,        // Block the indexer has indexed through
+        state_hash: Hash,          // Hash of the indexed state at that block
+        published_at: BlockNumber, // Block at which the checkpoint was published
+    }
+
+    /// Change made to an account, reported to its controllers through `ControllerActivity`
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum AccountAction {
+        Created,
+        Reregistered,
+        AuthMaterialRotated,
+        HashtableCidUpdated,
+        StatusChanged,
+        ControllersUpdated,
+        Transferred,
+        Recovered,
+        Deleted,
+    }
+
+    /// Privileges the owner can delegate to other accounts
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum Role {
+        Admin,         // Grants and revokes roles and runs maintenance such as migrations
+        NodeRegistrar, // Adds and removes bootnodes
+        Pauser,        // Freezes and resumes registrations
+        Verifier,      // Validates enclave attestations submitted for bootnodes
+    }
+
+    /// Group of messages that can be paused on its own
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum Subsystem {
+        Accounts,      // Account registration, updates, credentials, recovery and balances
+        Nodes,         // Bootnode list, suggestions and indexer checkpoints
+        Subscriptions, // Node subscriptions and their approval
+        Restrictions,  // Restriction lists and acknowledgements
+    }
+
+    /// Semantic version of the message set live on a deployment, compared field by field
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct ContractVersion {
+        pub major: u16, // Incompatible changes to existing messages
+        pub minor: u16, // Messages added without breaking existing ones
+        pub patch: u16, // Fixes that leave the message set unchanged
+    }
+
+    /// Accounts sharing control of privileged operations, and how many must approve a proposal
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Default, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Council {
+        members: Vec<AccountId>, // Accounts that can propose, approve and execute
+        threshold: u32,          // Approvals required to execute a proposal
+    }
+
+    /// Privileged operation the council can execute once a proposal is approved
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum CouncilAction {
+        /// Pauses a subsystem, or every subsystem with `None`
+        Pause(Option<Subsystem>),
+        /// Resumes a subsystem, or every subsystem with `None`
+        Unpause(Option<Subsystem>),
+        /// Replaces the contract code and records the new version
+        Upgrade(Hash, ContractVersion),
+        /// Stops or resumes new account and bootnode registrations
+        FreezeRegistrations(bool),
+        /// Replaces the network parameters
+        UpdateConfig(Config),
+        /// Removes a misbehaving bootnode and moves its stake to the treasury
+        Slash(Multiaddr, Vec<u8>),
+        /// Removes a bootnode and keeps it from being listed or subscribing again
+        Blacklist(Multiaddr),
+        /// Lifts a blacklisting
+        Unblacklist(Multiaddr),
+        /// Replaces the council members and the approvals a proposal needs
+        SetCouncil(Vec<AccountId>, u32),
+        /// Declares the keys of an account compromised, with evidence
+        DeclareCompromise(DID, Vec<u8>),
+        /// Lifts a compromise declaration without recovering the account
+        ClearCompromise(DID),
+        /// Drops a scheduled operation before it is executed
+        CancelScheduled(u32),
+        /// Reserves a DID prefix, or releases it with `false`
+        SetReservedPrefix(Vec<u8>, bool),
+        /// Lets an account register a DID under a reserved prefix
+        AllocateDid(DID, AccountId),
+        /// Upholds a bonded report against a bootnode, slashing it, or rejects it
+        AdjudicateReport(u32, bool),
+    }
+
+    /// A privileged operation awaiting council approvals
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Proposal {
+        action: CouncilAction,     // Operation executed once approved
+        proposer: AccountId,       // Member that submitted the proposal
+        approvals: Vec<AccountId>, // Members that approved, the proposer included
+    }
+
+    /// A contract notified through a cross-contract call when a DID it tracks changes
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Observer {
+        contract: AccountId, // Contract receiving the callback
+        selector: [u8; 4],   // Message called with `(DID, RegistryChange)`
+    }
+
+    /// Change to a DID passed to its observers
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum RegistryChange {
+        /// The account's hashtable CID changed
+        CidUpdated(CID),
+        /// A node stopped supporting the application, leaving this many subscribers
+        SubscriberLost(Multiaddr, u32),
+        /// Control of the DID was offered to an account, which must accept it with `claim_did`
+        TransferOffered(AccountId),
+        /// The chain accounts controlling the DID changed
+        ControllersChanged(Vec<AccountId>),
+    }
+
+    /// A bootnode awaiting votes from node operators and council members
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct NodeSuggestion {
+        operator: AccountId,       // Account that suggested the node and will operate it
+        endorsers: Vec<AccountId>, // Voters that endorsed the node, in voting order
+        expires_at: BlockNumber,   // Last block at which votes are accepted
+        stake: Balance,            // Value locked with the suggestion, becoming the node's stake
+    }
+
+    /// Accusation against a bootnode, backed by a bond the reporter loses if it's rejected
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct NodeReport {
+        reporter: AccountId,   // Account that filed the report and locked the bond
+        target: Multiaddr,     // Bootnode accused of misbehaving
+        evidence_cid: CID,     // CID of the evidence, such as equivocating signed records
+        bond: Balance,         // Value locked with the report
+        filed_at: BlockNumber, // Block at which the report was filed
+    }
+
+    /// A DID offered for sale, with the payment a buyer escrowed until settlement
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct DidListing {
+        seller: AccountId,        // Controller that listed the DID
+        price: Balance,           // Value a buyer must escrow
+        buyer: Option<AccountId>, // Account that escrowed the price, if any
+    }
+
+    /// Enclave attestation submitted for a listed bootnode
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Attestation {
+        report_hash: Hash,                // Hash of the enclave's attestation report
+        issuer_signature: Vec<u8>,        // Attestation service's signature over the report
+        submitted_at: BlockNumber,        // Block at which the attestation was submitted
+        verified_at: Option<BlockNumber>, // Block at which a verifier validated it, if any
+    }
+
+    /// A privileged operation waiting out the timelock delay
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct TimelockedAction {
+        action: CouncilAction,      // Operation applied on execution
+        executable_at: BlockNumber, // First block at which it can be executed
+    }
+
+    /// Track record a node operator builds up to lift its onboarding quota
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, Default, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct OperatorTrust {
+        clean_epochs: u32, // Epochs with a heartbeat since the operator's last failed check
+        last_epoch: Option<u32>, // Epoch of the latest heartbeat counted
+    }
+
+    /// Neighbours of a listed bootnode's slot in registration order
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, Default, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    struct NodeLink {
+        prev: Option<u32>, // Slot of the node listed just before, if any
+        next: Option<u32>, // Slot of the node listed just after, if any
+    }
+
+    /// A listed bootnode with the details peers use to decide whether to dial it
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct NodeRecord {
+        multiaddr: Multiaddr,                // Address peers dial
+        peer_id: Vec<u8>,                    // Peer id taken from the address's `/p2p/` component
+        region: Vec<u8>,                     // Region label set by the operator, such as `eu-west`
+        capacity: u32,                       // Connections the operator says the node can serve
+        registered_at_block: BlockNumber,    // Block at which the node was listed
+        operator: AccountId,                 // Account operating the node
+        last_seen: BlockNumber,              // Block of the node's latest heartbeat
+        stake: Balance,                      // Value locked at registration, refunded on removal
+        payer: AccountId,                    // Account that locked the stake and gets it back
+        reputation: i32,                     // Vouches minus reports from other operators
+        roles: NodeRole,                     // Functions the node serves
+        heartbeats: u32,                     // Epochs with a heartbeat, `heartbeat_epoch` in bit 0
+        heartbeat_epoch: u32,                // Epoch of the latest heartbeat
+        dial_failures: Vec<AccountId>,       // Operators that failed to dial the node this epoch
+        dial_failure_epoch: u32,             // Epoch the dial failures were reported in
+        probation_ends: Option<BlockNumber>, // Delisted until this block; MAX until a heartbeat
+    }
+
+    /// Operation a payment covered, reported through `PaymentReceipt`
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum PaymentOperation {
+        NewAccount,
+        CloneApp,
+    }
+
+    /// Errors returned by the contract's messages
+    #[derive(scale::Decode, scale::Encode, Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Error {
+        /// No account is registered under the DID
+        AccountNotFound,
+        /// The address is not in the bootnode list
+        NodeNotFound,
+        /// The supplied auth material or caller does not control the entry
+        Unauthorized,
+        /// The entry is already registered
+        AlreadyExists,
+        /// A bounded list is full
+        CapacityExceeded,
+        /// The address was tagged with a network other than the contract's
+        NetworkMismatch,
+        /// The account has been declared compromised and is frozen
+        AccountCompromised,
+        /// No hashtable CID has been announced for the account
+        PendingUpdateNotFound,
+        /// The user has not restricted the application
+        NotRestricted,
+        /// No access token exists with the id
+        TokenNotFound,
+        /// The node is not subscribed to the application
+        NotSubscribed,
+        /// The public key does not match the length of its declared type
+        InvalidCredential,
+        /// The account has no credential at the index
+        CredentialNotFound,
+        /// Transferring funds out of the contract failed
+        TransferFailed,
+        /// No unexpired subscription request exists for the node
+        RequestNotFound,
+        /// New registrations are frozen by the owner
+        RegistrationsFrozen,
+        /// The caller already suggested a bootnode, or rated this one, this epoch
+        RateLimited,
+        /// No suggestion is pending for the address
+        SuggestionNotFound,
+        /// The user has already restricted the application
+        AlreadyRestricted,
+        /// No ownership transfer to the caller is pending for the DID
+        TransferNotFound,
+        /// The account has been deactivated by its controller
+        AccountDeactivated,
+        /// The threshold is zero or exceeds the number of guardians
+        InvalidThreshold,
+        /// No recovery is in progress for the account
+        RecoveryNotFound,
+        /// The recovery lacks approvals or its timelock hasn't passed
+        RecoveryNotReady,
+        /// The chain account does not control the DID
+        ControllerNotFound,
+        /// Removing the controller would leave the account without one
+        LastController,
+        /// The checkpoint is not newer than the indexer's latest one, or lies in the future
+        StaleCheckpoint,
+        /// The message is paused by a pauser
+        Paused,
+        /// The version is not newer than the one currently live
+        VersionNotNewer,
+        /// The runtime rejected the new code hash
+        UpgradeFailed,
+        /// The treasury holds less than the requested amount
+        InsufficientFunds,
+        /// The contract must be paused globally first
+        NotPaused,
+        /// Accounts still have balances to claim through `withdraw_balance` or stakes locked
+        ClaimsOutstanding,
+        /// The application has banned the node
+        NodeBanned,
+        /// The application has already banned the node
+        AlreadyBanned,
+        /// The application has not banned the node
+        NotBanned,
+        /// No proposal is pending under the id
+        ProposalNotFound,
+        /// The proposal lacks approvals from current council members
+        ProposalNotReady,
+        /// The value sent doesn't cover the registration fee
+        InsufficientFee,
+        /// The DID is longer than the configured maximum
+        DidTooLong,
+        /// A configuration parameter is out of range
+        InvalidConfig,
+        /// The contract is not observing the DID
+        ObserverNotFound,
+        /// The migration has been finalized and imports are disabled
+        MigrationFinalized,
+        /// The voting period of the suggestion has ended
+        VotingClosed,
+        /// No operation is scheduled under the id
+        OperationNotFound,
+        /// The timelock delay of the operation hasn't passed
+        OperationNotReady,
+        /// The value sent doesn't cover the bootnode stake
+        InsufficientStake,
+        /// The node address has been blacklisted
+        NodeBlacklisted,
+        /// A node must serve at least one role
+        InvalidNodeRole,
+        /// The node address isn't a well-formed multiaddress
+        InvalidMultiaddr(ValidationError),
+        /// The node isn't listed in a region the application allows
+        RegionNotAllowed,
+        /// The node's region is already covered and the remaining subscriber slots are
+        /// needed to reach the application's region diversity
+        InsufficientDiversity,
+        /// The account hasn't been declared compromised
+        NotCompromised,
+        /// The DID falls under a reserved prefix and hasn't been allocated to the caller
+        DidReserved,
+        /// No bonded report is pending under the id
+        ReportNotFound,
+        /// The node's operator is still onboarding and its nodes serve as many subscriptions as
+        /// its quota allows
+        OnboardingQuotaExceeded,
+        /// The account has seen a controller action too recently to be marked inactive or
+        /// reaped
+        AccountActive,
+        /// The DID isn't listed for sale, or the caller has no part in its sale
+        ListingNotFound,
+        /// The account is suspended, so its status can only change through an appeal
+        AccountSuspended,
+        /// The account isn't suspended
+        NotSuspended,
+        /// No appeal against the account's suspension is pending
+        AppealNotFound,
+        /// The application requires attested nodes and the node has no verified attestation
+        NodeNotAttested,
+        /// A council is set, so the operation needs a council proposal
+        CouncilRequired,
+    }
+
+    /// Result type returned by the contract's messages
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    #[ink(storage)]
+    pub struct DbContract {
+        /// Stores the possible bootnodes of the network with their metadata. Slots
+        /// `0..node_count` are always filled: removing a node moves the last slot into the
+        /// freed one, and `node_links` keeps the registration order across moves
+        nodes: Mapping<u32, NodeRecord>,
+        /// Neighbours of each slot in registration order
+        node_links: Mapping<u32, NodeLink>,
+        /// Slot of each listed bootnode
+        node_slots: Mapping<Multiaddr, u32>,
+        /// Slot of the oldest listed bootnode
+        nodes_head: Option<u32>,
+        /// Slot of the newest listed bootnode
+        nodes_tail: Option<u32>,
+        /// Number of listed bootnodes, which is also the number of slots in use
+        node_count: u32,
+        /// Stores data about an application/user
+        accounts: Mapping<DID, AccountInfo>,
+        /// Stores nodes that run an applications operations (Gossipsub)
+        subscribers: Mapping<DID, Vec<Multiaddr>>,
+        /// Data access mapping application to users
+        restricted: Mapping<DID, Vec<DID>>,
+        /// Block at which the contract was deployed, marking the start of epoch 0
+        genesis_block: BlockNumber,
+        /// Number of blocks in an epoch
+        epoch_length: BlockNumber,
+        /// Funds owed to accounts, claimable through `withdraw_balance`
+        claimable: Mapping<AccountId, Balance>,
+        /// Network this deployment serves
+        network_id: NetworkId,
+        /// Network each registered bootnode address belongs to
+        node_networks: Mapping<Multiaddr, NetworkId>,
+        /// Transport of each registered bootnode address
+        node_transports: Mapping<Multiaddr, Transport>,
+        /// Version of the wrapped data key an application must use for a user
+        key_versions: Mapping<AccessPair, u32>,
+        /// Accounts each account has a wrapped data-key version with, on either side of the pair
+        key_version_peers: Mapping<DID, Vec<DID>>,
+        /// Accounts whose keys were declared compromised, with the block of the declaration
+        compromised: Mapping<DID, BlockNumber>,
+        /// Access tokens minted on-chain
+        access_tokens: Mapping<TokenId, AccessToken>,
+        /// Access tokens minted by or for each account
+        account_tokens: Mapping<DID, Vec<TokenId>>,
+        /// Identifier assigned to the next minted access token
+        next_token_id: TokenId,
+        /// DIDs registered under each DID method segment, by position. Deleting a DID moves
+        /// the last DID of its segment into the freed position
+        dids_by_prefix: Mapping<PrefixSlot, DID>,
+        /// Number of DIDs registered under each DID method segment
+        prefix_counts: Mapping<Vec<u8>, u32>,
+        /// Position of each registered DID in `dids_by_prefix`
+        prefix_positions: Mapping<DID, u32>,
+        /// Every DID known to the contract (registered or subscribed to), by insertion order
+        did_index: Mapping<u32, DID>,
+        /// Number of entries in `did_index`
+        did_count: u32,
+        /// DIDs already in `did_index`, so a deleted DID registered again isn't indexed twice
+        indexed_dids: Mapping<DID, ()>,
+        /// Whether DIDs are mirrored into runtime storage through the chain extension
+        #[cfg(feature = "did-anchor")]
+        did_anchoring: bool,
+        /// Block at which each node joined an application's subscribers
+        subscribed_at: Mapping<(DID, Multiaddr), BlockNumber>,
+        /// Account that subscribed each node to an application and acts for it
+        subscription_owners: Mapping<(DID, Multiaddr), AccountId>,
+        /// Number of nodes each account has subscribed to each application
+        account_subscriptions: Mapping<(DID, AccountId), u32>,
+        /// Subscribed nodes that applied the latest restriction change of a (user, application) pair
+        restriction_acks: Mapping<AccessPair, Vec<Multiaddr>>,
+        /// Hashtable CIDs announced but not yet confirmed
+        pending_cids: Mapping<DID, CID>,
+        /// Recent subscriber changes of each application, for incremental resync
+        subscriber_journals: Mapping<DID, SubscriberJournal>,
+        /// Recent restriction changes across all applications, keyed by sequence number
+        restriction_log: Mapping<u64, RestrictionChange>,
+        /// Sequence number of the latest restriction change
+        restriction_seq: u64,
+        /// Credentials registered to control each DID
+        credentials: Mapping<DID, Vec<Credential>>,
+        /// Stores how long subscription requests stay open, for applications that vet their subscribers
+        subscription_approval: Mapping<DID, BlockNumber>,
+        /// Stores the subscription requests awaiting an application's approval
+        pending_subscriptions: Mapping<DID, Vec<PendingSubscription>>,
+        /// Stores where each application's subscribers may run
+        replication_policies: Mapping<DID, ReplicationPolicy>,
+        /// Account operating this deployment
+        owner: AccountId,
+        /// Network parameters the owner can change after deployment
+        config: Config,
+        /// Whether new accounts and bootnodes are currently turned away
+        registrations_frozen: bool,
+        /// Number of registered accounts
+        account_count: u32,
+        /// Key-agreement keys an account has rotated away from, oldest first
+        key_agreement_history: Mapping<DID, Vec<KeyAgreementKey>>,
+        /// Applications each node is subscribed to, the reverse of `subscribers`
+        node_subscriptions: Mapping<Multiaddr, Vec<DID>>,
+        /// Bootnodes awaiting votes, with their endorsers
+        node_suggestions: Mapping<Multiaddr, NodeSuggestion>,
+        /// Epoch in which each account last suggested a bootnode
+        last_suggestion: Mapping<AccountId, u32>,
+        /// Controller each DID has been offered to, until they claim it
+        pending_transfers: Mapping<DID, AccountId>,
+        /// DIDs listed for sale, with any escrowed payment
+        did_listings: Mapping<DID, DidListing>,
+        /// Suspensions in force, with any appeal
+        suspensions: Mapping<DID, Suspension>,
+        /// Suspended accounts with an appeal awaiting a ruling, oldest first
+        pending_appeals: Vec<DID>,
+        /// Applications each user has restricted, the reverse of `restricted`
+        user_restrictions: Mapping<DID, Vec<DID>>,
+        /// Activity of the current epoch, reported once the next epoch starts
+        epoch_activity: EpochActivity,
+        /// Failed authentication attempts reported against each DID, with the epoch they were counted in
+        auth_failures: Mapping<DID, (u32, u32)>,
+        /// Node operators that reported a failure against each DID in the epoch counted in
+        /// `auth_failures`
+        auth_failure_reporters: Mapping<DID, Vec<AccountId>>,
+        /// Blocks an account's auth material stays locked after a failure spike, for accounts that opted in
+        auth_cooldowns: Mapping<DID, BlockNumber>,
+        /// Block until which reads gated on an account's auth material are refused
+        auth_locked_until: Mapping<DID, BlockNumber>,
+        /// Guardians appointed by each account for social recovery
+        guardians: Mapping<DID, GuardianSet>,
+        /// Recoveries in progress
+        recoveries: Mapping<DID, Recovery>,
+        /// Guardians that reported an account compromised, until enough of them agree
+        compromise_reports: Mapping<DID, Vec<DID>>,
+        /// Epoch of each account's latest controller action
+        last_active: Mapping<DID, u32>,
+        /// Epoch in which each idle account was marked inactive
+        inactive_since: Mapping<DID, u32>,
+        /// Accounts allowed to publish indexer checkpoints
+        indexers: Vec<AccountId>,
+        /// Recent checkpoints of each indexer, oldest first
+        checkpoints: Mapping<AccountId, Vec<Checkpoint>>,
+        /// Roles granted to accounts other than the owner
+        roles: Mapping<(AccountId, Role), ()>,
+        /// DID prefixes that only allocated accounts can register under
+        reserved_prefixes: Vec<Vec<u8>>,
+        /// Account each reserved DID has been allocated to, until it is registered
+        did_allocations: Mapping<DID, AccountId>,
+        /// Whether every mutating message is paused
+        paused: bool,
+        /// Subsystems paused on their own
+        paused_subsystems: Vec<Subsystem>,
+        /// Version of the message set currently live
+        version: ContractVersion,
+        /// Sum of all claimable balances, which the treasury never pays out
+        owed: Balance,
+        /// Sum of the fees burned, which stay locked in the contract for good
+        burned: Balance,
+        /// Nodes each application refuses as subscribers
+        banned_subscribers: Mapping<DID, Vec<Multiaddr>>,
+        /// Council sharing control of privileged operations
+        council: Council,
+        /// Council proposals awaiting approval or execution
+        proposals: Mapping<u32, Proposal>,
+        /// Id assigned to the next council proposal
+        next_proposal: u32,
+        /// Contracts notified when a DID changes
+        observers: Mapping<DID, Vec<Observer>>,
+        /// Whether state imports from a previous instance have been disabled for good
+        migration_finalized: bool,
+        /// Enclave attestation submitted for each listed bootnode
+        attestations: Mapping<Multiaddr, Attestation>,
+        /// Number of listed bootnodes each account operates
+        operated_nodes: Mapping<AccountId, u32>,
+        /// Track record of each node operator, growing its onboarding quota
+        operator_trust: Mapping<AccountId, OperatorTrust>,
+        /// Epoch in which each operator last vouched for or reported each bootnode
+        node_ratings: Mapping<(AccountId, Multiaddr), u32>,
+        /// Node addresses kept off the bootnode list and out of subscriptions
+        blacklisted: Mapping<Multiaddr, ()>,
+        /// Sum of the stakes locked by listed bootnodes, the bonds locked by pending reports
+        /// and the payments escrowed for DID sales, which the treasury never pays out
+        staked: Balance,
+        /// Bonded reports against bootnodes awaiting adjudication
+        node_reports: Mapping<u32, NodeReport>,
+        /// Id assigned to the next bonded report
+        next_report_id: u32,
+        /// Upgrades and parameter changes waiting out the timelock delay
+        timelock: Mapping<u32, TimelockedAction>,
+        /// Id assigned to the next scheduled operation
+        next_timelock_id: u32,
+        /// Fees each account has paid over the contract's lifetime
+        paid_totals: Mapping<AccountId, Balance>,
+    }
+
+    /// Contract events
+    #[ink(event)]
+    pub struct AccountCreated {
+        #[ink(topic)]
+        did: DID,
+    }
+
+    #[ink(event)]
+    pub struct BootNodeAdded {
+        #[ink(topic)]
+        address: Multiaddr,
+    }
+
+    #[ink(event)]
+    pub struct BootNodeRemoved {
+        #[ink(topic)]
+        address: Multiaddr,
+    }
+
+    #[ink(event)]
+    pub struct AttestationSubmitted {
+        #[ink(topic)]
+        address: Multiaddr,
+        report_hash: Hash,
+    }
+
+    #[ink(event)]
+    pub struct AttestationVerified {
+        #[ink(topic)]
+        address: Multiaddr,
+        valid: bool,
+    }
+
+    #[ink(event)]
+    pub struct DialFailureReported {
+        #[ink(topic)]
+        address: Multiaddr,
+        #[ink(topic)]
+        reporter: AccountId,
+        reports: u32,
+    }
+
+    #[ink(event)]
+    pub struct BootNodeDelisted {
+        #[ink(topic)]
+        address: Multiaddr,
+    }
+
+    #[ink(event)]
+    pub struct BootNodeProbationStarted {
+        #[ink(topic)]
+        address: Multiaddr,
+        ends_at: BlockNumber,
+    }
+
+    #[ink(event)]
+    pub struct BootNodeUpdated {
+        #[ink(topic)]
+        old: Multiaddr,
+        #[ink(topic)]
+        new: Multiaddr,
+    }
+
+    #[ink(event)]
+    pub struct HashTableAddressUpdated {
+        #[ink(topic)]
+        did: DID,
+        ipfs_address: CID,
+    }
+
+    #[ink(event)]
+    pub struct TopicSubscriptionComplete {
+        #[ink(topic)]
+        did: DID,
+        node: Multiaddr,
+    }
+
+    #[ink(event)]
+    pub struct TopicUnsubscriptionComplete {
+        #[ink(topic)]
+        did: DID,
+        node: Multiaddr,
+    }
+
+    #[ink(event)]
+    pub struct RestrictApplicationAccess {
+        #[ink(topic)]
+        user_did: DID,
+        #[ink(topic)]
+        application_did: DID,
+    }
+
+    #[ink(event)]
+    pub struct UnrestrictApplicationAccess {
+        #[ink(topic)]
+        user_did: DID,
+        #[ink(topic)]
+        application_did: DID,
+    }
+
+    #[ink(event)]
+    pub struct BalanceWithdrawn {
+        #[ink(topic)]
+        account: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct AuthPolicyUpdated {
+        #[ink(topic)]
+        did: DID,
+        policy_cid: CID,
+        policy_hash: Hash,
+    }
+
+    #[ink(event)]
+    pub struct CompromiseDeclared {
+        #[ink(topic)]
+        did: DID,
+        evidence: Vec<u8>,
+        block: BlockNumber,
+    }
+
+    #[ink(event)]
+    pub struct CompromiseReported {
+        #[ink(topic)]
+        did: DID,
+        guardian: DID,
+        reports: u32,
+    }
+
+    #[ink(event)]
+    pub struct CompromiseCleared {
+        #[ink(topic)]
+        did: DID,
+    }
+
+    #[ink(event)]
+    pub struct AccessTokenMinted {
+        #[ink(topic)]
+        token_id: TokenId,
+        #[ink(topic)]
+        app_did: DID,
+        #[ink(topic)]
+        user_did: DID,
+        commitment: Hash,
+        expires_at: BlockNumber,
+    }
+
+    #[ink(event)]
+    pub struct AccessTokenRevoked {
+        #[ink(topic)]
+        token_id: TokenId,
+    }
+
+    #[ink(event)]
+    pub struct DidAnchorFailed {
+        #[ink(topic)]
+        did: DID,
+    }
+
+    #[ink(event)]
+    pub struct RestrictionAcknowledged {
+        #[ink(topic)]
+        application_did: DID,
+        #[ink(topic)]
+        user_did: DID,
+        node: Multiaddr,
+    }
+
+    #[ink(event)]
+    pub struct HashTableUpdateAnnounced {
+        #[ink(topic)]
+        did: DID,
+        ipfs_address: CID,
+    }
+
+    #[ink(event)]
+    pub struct CredentialAdded {
+        #[ink(topic)]
+        did: DID,
+        index: u32,
+        credential_type: CredentialType,
+        public_key: Vec<u8>,
+    }
+
+    #[ink(event)]
+    pub struct CredentialRevoked {
+        #[ink(topic)]
+        did: DID,
+        index: u32,
+    }
+
+    #[ink(event)]
+    pub struct SubscriptionApprovalUpdated {
+        #[ink(topic)]
+        did: DID,
+        request_ttl: Option<BlockNumber>,
+    }
+
+    #[ink(event)]
+    pub struct ReplicationPolicyUpdated {
+        #[ink(topic)]
+        did: DID,
+        policy: ReplicationPolicy,
+    }
+
+    #[ink(event)]
+    pub struct SubscriptionRequested {
+        #[ink(topic)]
+        did: DID,
+        node: Multiaddr,
+        expires_at: BlockNumber,
+    }
+
+    #[ink(event)]
+    pub struct SubscriptionRejected {
+        #[ink(topic)]
+        did: DID,
+        node: Multiaddr,
+    }
+
+    #[ink(event)]
+    pub struct RegistrationsFrozen {
+        frozen: bool,
+    }
+
+    #[ink(event)]
+    pub struct KeyAgreementKeyRotated {
+        #[ink(topic)]
+        did: DID,
+        key: [u8; 32],
+    }
+
+    #[ink(event)]
+    pub struct NodeSlashed {
+        #[ink(topic)]
+        address: Multiaddr,
+        #[ink(topic)]
+        operator: AccountId,
+        amount: Balance,
+        reason: Vec<u8>,
+    }
+
+    #[ink(event)]
+    pub struct NodeReportFiled {
+        id: u32,
+        #[ink(topic)]
+        target: Multiaddr,
+        #[ink(topic)]
+        reporter: AccountId,
+        evidence_cid: CID,
+        bond: Balance,
+    }
+
+    #[ink(event)]
+    pub struct NodeReportResolved {
+        id: u32,
+        upheld: bool,
+        reward: Balance,
+    }
+
+    #[ink(event)]
+    pub struct NodeRated {
+        #[ink(topic)]
+        address: Multiaddr,
+        #[ink(topic)]
+        rater: AccountId,
+        vouched: bool,
+        reputation: i32,
+    }
+
+    #[ink(event)]
+    pub struct PrefixReservationUpdated {
+        prefix: Vec<u8>,
+        reserved: bool,
+    }
+
+    #[ink(event)]
+    pub struct DidAllocated {
+        #[ink(topic)]
+        did: DID,
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct NodeBlacklisted {
+        #[ink(topic)]
+        address: Multiaddr,
+    }
+
+    #[ink(event)]
+    pub struct NodeUnblacklisted {
+        #[ink(topic)]
+        address: Multiaddr,
+    }
+
+    #[ink(event)]
+    pub struct NodeMetadataUpdated {
+        #[ink(topic)]
+        address: Multiaddr,
+        region: Vec<u8>,
+        capacity: u32,
+    }
+
+    #[ink(event)]
+    pub struct NodeSuggested {
+        #[ink(topic)]
+        address: Multiaddr,
+        operator: AccountId,
+        expires_at: BlockNumber,
+    }
+
+    #[ink(event)]
+    pub struct NodeEndorsed {
+        #[ink(topic)]
+        address: Multiaddr,
+        endorser: AccountId,
+        endorsements: u32,
+    }
+
+    #[ink(event)]
+    pub struct NodeSuggestionWithdrawn {
+        #[ink(topic)]
+        address: Multiaddr,
+    }
+
+    #[ink(event)]
+    pub struct AuthMaterialRotated {
+        #[ink(topic)]
+        did: DID,
+    }
+
+    #[ink(event)]
+    pub struct AuthKeyUpdated {
+        #[ink(topic)]
+        did: DID,
+        auth_key: Option<Vec<u8>>,
+    }
+
+    #[ink(event)]
+    pub struct DidTransferProposed {
+        #[ink(topic)]
+        did: DID,
+        new_controller: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct DidListed {
+        #[ink(topic)]
+        did: DID,
+        seller: AccountId,
+        price: Balance,
+    }
+
+    #[ink(event)]
+    pub struct DidPaymentEscrowed {
+        #[ink(topic)]
+        did: DID,
+        buyer: AccountId,
+        price: Balance,
+    }
+
+    #[ink(event)]
+    pub struct DidSaleCancelled {
+        #[ink(topic)]
+        did: DID,
+        by: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct DidSold {
+        #[ink(topic)]
+        did: DID,
+        seller: AccountId,
+        buyer: AccountId,
+        price: Balance,
+    }
+
+    #[ink(event)]
+    pub struct DidOwnershipTransferred {
+        #[ink(topic)]
+        did: DID,
+        previous_controllers: Vec<AccountId>,
+        new_controller: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct AccountDeleted {
+        #[ink(topic)]
+        did: DID,
+    }
+
+    #[ink(event)]
+    pub struct AccountMarkedInactive {
+        #[ink(topic)]
+        did: DID,
+        reapable_from: u32,
+    }
+
+    #[ink(event)]
+    pub struct AccountStatusChanged {
+        #[ink(topic)]
+        did: DID,
+        status: AccountStatus,
+    }
+
+    #[ink(event)]
+    pub struct AccountSuspended {
+        #[ink(topic)]
+        did: DID,
+        reason: Vec<u8>,
+    }
+
+    #[ink(event)]
+    pub struct SuspensionAppealed {
+        #[ink(topic)]
+        did: DID,
+        statement_cid: CID,
+    }
+
+    #[ink(event)]
+    pub struct AppealRuled {
+        #[ink(topic)]
+        did: DID,
+        lifted: bool,
+        reason: Vec<u8>,
+    }
+
+    #[ink(event)]
+    pub struct EpochReport {
+        #[ink(topic)]
+        epoch: u32,
+        accounts_created: u32,
+        cid_updates: u32,
+        subscriptions: u32,
+        unsubscriptions: u32,
+        fees_collected: Balance,
+    }
+
+    #[ink(event)]
+    pub struct AuthFailureSpike {
+        #[ink(topic)]
+        did: DID,
+        epoch: u32,
+        failures: u32,
+        locked_until: Option<BlockNumber>,
+    }
+
+    #[ink(event)]
+    pub struct GuardiansUpdated {
+        #[ink(topic)]
+        did: DID,
+        guardians: Vec<DID>,
+        threshold: u32,
+    }
+
+    #[ink(event)]
+    pub struct RecoveryInitiated {
+        #[ink(topic)]
+        did: DID,
+        new_controller: AccountId,
+        executable_at: BlockNumber,
+    }
+
+    #[ink(event)]
+    pub struct RecoveryApproved {
+        #[ink(topic)]
+        did: DID,
+        guardian: DID,
+        approvals: u32,
+    }
+
+    #[ink(event)]
+    pub struct RecoveryFinalized {
+        #[ink(topic)]
+        did: DID,
+        new_controller: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RecoveryCancelled {
+        #[ink(topic)]
+        did: DID,
+    }
+
+    #[ink(event)]
+    pub struct ControllersUpdated {
+        #[ink(topic)]
+        did: DID,
+        controllers: Vec<AccountId>,
+    }
+
+    #[ink(event)]
+    pub struct IndexerUpdated {
+        #[ink(topic)]
+        indexer: AccountId,
+        registered: bool,
+    }
+
+    #[ink(event)]
+    pub struct CheckpointPublished {
+        #[ink(topic)]
+        indexer: AccountId,
+        block: BlockNumber,
+        state_hash: Hash,
+    }
+
+    #[ink(event)]
+    pub struct AccountReregistered {
+        #[ink(topic)]
+        did: DID,
+    }
+
+    /// Secondary event emitted once per affected controller alongside account events, so
+    /// wallets can follow every DID they control by filtering on their own account
+    #[ink(event)]
+    pub struct ControllerActivity {
+        #[ink(topic)]
+        controller: AccountId,
+        did: DID,
+        action: AccountAction,
+    }
+
+    #[ink(event)]
+    pub struct RoleGranted {
+        #[ink(topic)]
+        account: AccountId,
+        role: Role,
+        granted_by: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RoleRevoked {
+        #[ink(topic)]
+        account: AccountId,
+        role: Role,
+        revoked_by: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct PauseUpdated {
+        subsystem: Option<Subsystem>,
+        paused: bool,
+    }
+
+    #[ink(event)]
+    pub struct AppCloned {
+        #[ink(topic)]
+        source_did: DID,
+        #[ink(topic)]
+        did: DID,
+    }
+
+    #[ink(event)]
+    pub struct SubscriberBanned {
+        #[ink(topic)]
+        did: DID,
+        node: Multiaddr,
+    }
+
+    #[ink(event)]
+    pub struct SubscriberUnbanned {
+        #[ink(topic)]
+        did: DID,
+        node: Multiaddr,
+    }
+
+    #[ink(event)]
+    pub struct ObserverAdded {
+        #[ink(topic)]
+        did: DID,
+        contract: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct ObserverRemoved {
+        #[ink(topic)]
+        did: DID,
+        contract: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct ObserverCallFailed {
+        #[ink(topic)]
+        did: DID,
+        #[ink(topic)]
+        contract: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct MigrationFinalized {}
+
+    #[ink(event)]
+    pub struct Scheduled {
+        #[ink(topic)]
+        id: u32,
+        action: CouncilAction,
+        executable_at: BlockNumber,
+    }
+
+    #[ink(event)]
+    pub struct ScheduledExecuted {
+        #[ink(topic)]
+        id: u32,
+    }
+
+    #[ink(event)]
+    pub struct ScheduledCancelled {
+        #[ink(topic)]
+        id: u32,
+    }
+
+    #[ink(event)]
+    pub struct ConfigUpdated {
+        config: Config,
+    }
+
+    #[ink(event)]
+    pub struct CouncilUpdated {
+        members: Vec<AccountId>,
+        threshold: u32,
+    }
+
+    #[ink(event)]
+    pub struct ProposalSubmitted {
+        #[ink(topic)]
+        id: u32,
+        proposer: AccountId,
+        action: CouncilAction,
+    }
+
+    #[ink(event)]
+    pub struct ProposalApproved {
+        #[ink(topic)]
+        id: u32,
+        member: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct ProposalExecuted {
+        #[ink(topic)]
+        id: u32,
+    }
+
+    #[ink(event)]
+    pub struct FeesBurned {
+        amount: Balance,
+        total: Balance,
+    }
+
+    #[ink(event)]
+    pub struct TreasuryWithdrawn {
+        #[ink(topic)]
+        to: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ContractUpgraded {
+        code_hash: Hash,
+        version: ContractVersion,
+    }
+
+    /// Emitted for every call that carries value towards a fee, so accounting systems can
+    /// reconcile what each payment covered
+    #[ink(event)]
+    pub struct PaymentReceipt {
+        #[ink(topic)]
+        payer: AccountId,
+        operation: PaymentOperation,
+        fee: Balance,
+        refund: Balance,
+    }
+
+    impl DbContract {
+        /// Constructor that initializes the contract storage
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self::with_network_id(DEFAULT_NETWORK_ID)
+        }
+
+        /// Constructor that initializes the contract storage for a specific network
+        #[ink(constructor)]
+        pub fn with_network_id(network_id: NetworkId) -> Self {
+            Self::new_with_config(InitConfig {
+                owner: Self::env().caller(),
+                network_id,
+                max_bootnodes: DEFAULT_MAX_BOOTNODES,
+                epoch_length: DEFAULT_EPOCH_LENGTH,
+                registration_fee: DEFAULT_REGISTRATION_FEE,
+                node_stake: DEFAULT_NODE_STAKE,
+                permissioned: DEFAULT_PERMISSIONED,
+            })
+        }
+
+        /// Constructor that initializes the contract storage from a full configuration
+        #[ink(constructor)]
+        pub fn new_with_config(config: InitConfig) -> Self {
+            Self {
+                nodes: Default::default(),
+                node_slots: Default::default(),
+                node_links: Default::default(),
+                nodes_head: None,
+                nodes_tail: None,
+                node_count: 0,
+                accounts: Default::default(),
+                subscribers: Default::default(),
+                restricted: Default::default(),
+                genesis_block: Self::env().block_number(),
+                // epochs must be at least one block long
+                epoch_length: config.epoch_length.max(1),
+                claimable: Default::default(),
+                network_id: config.network_id,
+                node_networks: Default::default(),
+                node_transports: Default::default(),
+                key_versions: Default::default(),
+                key_version_peers: Default::default(),
+                compromised: Default::default(),
+                access_tokens: Default::default(),
+                account_tokens: Default::default(),
+                next_token_id: 0,
+                dids_by_prefix: Default::default(),
+                prefix_counts: Default::default(),
+                prefix_positions: Default::default(),
+                did_index: Default::default(),
+                did_count: 0,
+                indexed_dids: Default::default(),
+                subscribed_at: Default::default(),
+                restriction_acks: Default::default(),
+                pending_cids: Default::default(),
+                subscriber_journals: Default::default(),
+                restriction_log: Default::default(),
+                restriction_seq: 0,
+                credentials: Default::default(),
+                subscription_approval: Default::default(),
+                pending_subscriptions: Default::default(),
+                replication_policies: Default::default(),
+                owner: config.owner,
+                config: Config {
+                    // the list must hold at least one bootnode
+                    max_bootnodes: config.max_bootnodes.max(1),
+                    max_subscriptions_per_account: DEFAULT_MAX_SUBSCRIPTIONS_PER_ACCOUNT,
+                    registration_fee: config.registration_fee,
+                    max_did_len: DEFAULT_MAX_DID_LEN,
+                    node_votes_required: DEFAULT_NODE_VOTES_REQUIRED,
+                    node_voting_period: DEFAULT_NODE_VOTING_PERIOD,
+                    timelock_delay: DEFAULT_TIMELOCK_DELAY,
+                    node_ttl: DEFAULT_NODE_TTL,
+                    node_stake: config.node_stake,
+                    permissioned: config.permissioned,
+                    fee_burn_percent: DEFAULT_FEE_BURN_PERCENT,
+                },
+                registrations_frozen: false,
+                account_count: 0,
+                key_agreement_history: Default::default(),
+                node_subscriptions: Default::default(),
+                node_suggestions: Default::default(),
+                last_suggestion: Default::default(),
+                pending_transfers: Default::default(),
+                did_listings: Default::default(),
+                suspensions: Default::default(),
+                pending_appeals: Vec::new(),
+                user_restrictions: Default::default(),
+                epoch_activity: Default::default(),
+                auth_failures: Default::default(),
+                auth_failure_reporters: Default::default(),
+                auth_cooldowns: Default::default(),
+                auth_locked_until: Default::default(),
+                guardians: Default::default(),
+                recoveries: Default::default(),
+                compromise_reports: Default::default(),
+                last_active: Default::default(),
+                inactive_since: Default::default(),
+                indexers: Vec::new(),
+                checkpoints: Default::default(),
+                roles: Default::default(),
+                reserved_prefixes: Vec::new(),
+                did_allocations: Default::default(),
+                paused: false,
+                paused_subsystems: Vec::new(),
+                version: INITIAL_VERSION,
+                owed: 0,
+                burned: 0,
+                banned_subscribers: Default::default(),
+                council: Default::default(),
+                proposals: Default::default(),
+                next_proposal: 0,
+                observers: Default::default(),
+                migration_finalized: false,
+                operated_nodes: Default::default(),
+                attestations: Default::default(),
+                operator_trust: Default::default(),
+                timelock: Default::default(),
+                next_timelock_id: 0,
+                staked: 0,
+                node_reports: Default::default(),
+                next_report_id: 0,
+                node_ratings: Default::default(),
+                blacklisted: Default::default(),
+                subscription_owners: Default::default(),
+                account_subscriptions: Default::default(),
+                paid_totals: Default::default(),
+                #[cfg(feature = "did-anchor")]
+                did_anchoring: false,
+            }
+        }
+
+        /// Constructor that seeds the bootnode list, so a genesis deployment doesn't need a
+        /// transaction per node. Duplicates are skipped and the list cap still applies, but
+        /// an address `add_address` would reject fails the deployment.
+        /// The deployer is recorded as the operator of the seeded nodes. Seeds lock no stake,
+        /// which the default configuration allows
+        #[ink(constructor)]
+        pub fn with_bootnodes(network_id: NetworkId, bootnodes: Vec<Multiaddr>) -> Result<Self> {
+            let mut contract = Self::with_network_id(network_id);
+            let owner = contract.owner;
+            for addr in bootnodes {
+                match contract.list_node(addr, network_id, NodeRole::BOOTNODE, owner, owner, 0) {
+                    Ok(()) | Err(Error::AlreadyExists) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+            Ok(contract)
+        }
+
+        /// Constructor for runtimes that provide the DID-anchoring chain extension.
+        /// DIDs registered on this deployment are mirrored into runtime storage
+        #[cfg(feature = "did-anchor")]
+        #[ink(constructor)]
+        pub fn with_did_anchoring(network_id: NetworkId) -> Self {
+            let mut contract = Self::with_network_id(network_id);
+            contract.did_anchoring = true;
+            contract
+        }
+
+        /// Checks if a DID exists
+        #[ink(message, payable)]
+        pub fn check_did_existence(&self, did: DID) -> bool {
+            self.accounts.contains(&did)
+        }
+
+        /// Creates an account on the network. Only the blake2_256 hash of the auth material
+        /// is submitted and stored; the material itself is presented when reading the account
+        #[ink(message, payable)]
+        pub fn new_account(&mut self, did: DID, hashtable_cid: CID, auth_hash: Hash) -> Result<()> {
+            self.check_new_account(&did)?;
+
+            // Get the account Id of the
+            // The document would be created on demand
+            let account = AccountInfo {
+                did_document_uri: Default::default(),
+                hashtable_cid,
+                auth_hash,
+                auth_policy: None,
+                created_at: self.env().block_number(),
+                key_agreement: None,
+                auth_key: None,
+                controllers: vec![self.env().caller()],
+                status: AccountStatus::Active,
+            };
+            self.register_account(did, account);
+            self.charge_fee(PaymentOperation::NewAccount, self.config.registration_fee);
+            Ok(())
+        }
+
+        /// Stores a newly registered account and indexes its DID
+        fn register_account(&mut self, did: DID, account: AccountInfo) {
+            // index the DID under its method segment
+            self.index_did(&did);
+            let prefix = did_prefix(&did).to_vec();
+            let count = self.prefix_counts.get(&prefix).unwrap_or(0);
+            self.dids_by_prefix.insert((&prefix, count), &did);
+            self.prefix_positions.insert(&did, &count);
+            self.prefix_counts.insert(&prefix, &(count + 1));
+            self.account_count += 1;
+            self.track_activity().accounts_created += 1;
+            self.did_allocations.remove(&did);
+            self.last_active.insert(&did, &self.current_epoch());
+
+            self.accounts.insert(&did, &account);
+
+            #[cfg(feature = "did-anchor")]
+            self.anchor_did(&did);
+
+            // emit events
+            self.emit_controller_activity(&did, &account.controllers, AccountAction::Created);
+            self.env().emit_event(AccountCreated { did });
+        }
+
+        /// Resets the hashtable CID and auth material of an existing account, for controllers
+        /// starting over with fresh data. Controllers, guardians and restrictions are kept,
+        /// while the signature auth key and any announced CID are dropped
+        #[ink(message, payable)]
+        pub fn reregister_account(
+            &mut self,
+            did: DID,
+            hashtable_cid: CID,
+            auth_hash: Hash,
+        ) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let mut account = self.authorize(&did)?;
+            account.hashtable_cid = hashtable_cid.clone();
+            account.auth_hash = auth_hash;
+            account.auth_key = None;
+            self.accounts.insert(&did, &account);
+            self.pending_cids.remove(&did);
+
+            // emit events
+            self.emit_controller_activity(&did, &account.controllers, AccountAction::Reregistered);
+            self.env()
+                .emit_event(AccountReregistered { did: did.clone() });
+            self.notify_observers(&did, RegistryChange::CidUpdated(hashtable_cid));
+            Ok(())
+        }
+
+        /// Adds a network address serving the given roles to the list of nodes using FIFO.
+        /// On permissioned deployments node registrars only, and other nodes get listed as
+        /// bootnodes through suggestions and endorsements; otherwise any operator locking the
+        /// stake can list its node
+        #[ink(message, payable)]
+        pub fn add_address(
+            &mut self,
+            addr: Multiaddr,
+            network_id: NetworkId,
+            roles: NodeRole,
+        ) -> Result<()> {
+            self.ensure_can_list()?;
+            let stake = self.check_stake()?;
+            let caller = self.env().caller();
+            self.list_node(addr, network_id, roles, caller, caller, stake)
+        }
+
+        /// Checks that the caller can list bootnodes directly: anyone on open deployments, node
+        /// registrars on permissioned ones
+        fn ensure_can_list(&self) -> Result<()> {
+            if self.config.permissioned {
+                self.ensure_role(Role::NodeRegistrar)?;
+            }
+            Ok(())
+        }
+
+        /// Adds a network address on behalf of the operator running the node, who can then
+        /// remove it without the registrar. The stake stays the registrar's and is refunded to
+        /// it on removal. Node registrars only
+        #[ink(message, payable)]
+        pub fn add_address_for(
+            &mut self,
+            addr: Multiaddr,
+            network_id: NetworkId,
+            roles: NodeRole,
+            operator: AccountId,
+        ) -> Result<()> {
+            self.ensure_role(Role::NodeRegistrar)?;
+            let stake = self.check_stake()?;
+            let payer = self.env().caller();
+            self.list_node(addr, network_id, roles, operator, payer, stake)
+        }
+
+        /// Adds an address to the list of nodes, evicting the oldest one when the list is full.
+        /// This helps to eventually remove nodes that may exit without the proper bookkeeping.
+        /// Every path into the list has to lock at least the configured bootnode stake
+        fn list_node(
+            &mut self,
+            addr: Multiaddr,
+            network_id: NetworkId,
+            roles: NodeRole,
+            operator: AccountId,
+            payer: AccountId,
+            stake: Balance,
+        ) -> Result<()> {
+            self.check_new_address(&addr, network_id)?;
+            if roles.0 == 0 {
+                return Err(Error::InvalidNodeRole);
+            }
+            if stake < self.config.node_stake {
+                return Err(Error::InsufficientStake);
+            }
+
+            // If the list has reached its capacity, remove the oldest items before adding a new one
+            while self.node_count >= self.config.max_bootnodes {
+                match self.nodes_head.and_then(|head| self.take_node(head)) {
+                    Some(oldest) => self.unlist_node(&oldest),
+                    None => break,
+                }
+            }
+            // Add the address to the end of the list
+            self.push_node(NodeRecord {
+                multiaddr: addr.clone(),
+                peer_id: parse_peer_id(&addr),
+                region: Vec::new(),
+                capacity: 0,
+                registered_at_block: self.env().block_number(),
+                operator,
+                last_seen: self.env().block_number(),
+                stake,
+                payer,
+                reputation: 0,
+                roles,
+                heartbeats: 1,
+                heartbeat_epoch: self.current_epoch(),
+                dial_failures: Vec::new(),
+                dial_failure_epoch: 0,
+                probation_ends: None,
+            });
+            self.staked = self.staked.saturating_add(stake);
+            self.node_networks.insert(&addr, &network_id);
+            let operated = self.operated_nodes.get(operator).unwrap_or_default();
+            self.operated_nodes.insert(operator, &(operated + 1));
+            if let Some(transport) = parse_transport(&addr) {
+                self.node_transports.insert(&addr, &transport);
+            }
+
+            // emit event
+            self.env().emit_event(BootNodeAdded { address: addr });
+            Ok(())
+        }
+
+        /// Remove node address from bootnodes. Node registrars can remove any address,
+        /// operators only the addresses listed for them
+        #[ink(message, payable)]
+        pub fn remove_address(&mut self, addr: Multiaddr) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Nodes)?;
+            let slot = self.manage_node(&addr)?;
+
+            // remove address
+            if let Some(record) = self.take_node(slot) {
+                self.unlist_node(&record);
+            }
+
+            // emit event
+            self.env().emit_event(BootNodeRemoved { address: addr });
+            Ok(())
+        }
+
+        /// Replaces the address of a listed node in place, keeping its position in the list,
+        /// registration block, stake, reputation and other metadata. The new address goes
+        /// through the same checks as `add_address`. Subscriptions made under the old address
+        /// aren't carried over. Node registrars and the node's operator only
+        #[ink(message, payable)]
+        pub fn update_address(&mut self, old: Multiaddr, new: Multiaddr) -> Result<()> {
+            let slot = self.manage_node(&old)?;
+            let network_id = self.node_networks.get(&old).unwrap_or(self.network_id);
+            self.check_new_address(&new, network_id)?;
+            let mut record = self.nodes.get(slot).ok_or(Error::NodeNotFound)?;
+
+            // swap the address in the same slot
+            record.multiaddr = new.clone();
+            record.peer_id = parse_peer_id(&new);
+            self.nodes.insert(slot, &record);
+            self.node_slots.remove(&old);
+            self.node_slots.insert(&new, &slot);
+            self.node_networks.remove(&old);
+            self.node_networks.insert(&new, &network_id);
+            self.node_transports.remove(&old);
+            if let Some(transport) = parse_transport(&new) {
+                self.node_transports.insert(&new, &transport);
+            }
+            // the new address may be served by another host, so it needs a fresh attestation
+            self.attestations.remove(&old);
+
+            // emit event
+            self.env().emit_event(BootNodeUpdated { old, new });
+            Ok(())
+        }
+
+        /// Adds up to 20 network addresses serving the same roles in one call, as with
+        /// `add_address`, and never more than the bootnode capacity so a batch can't evict its
+        /// own entries. The value sent must cover the stake of every node and is split evenly
+        /// between them. Fails without listing any address if one is rejected.
+        /// Node registrars only on permissioned deployments
+        #[ink(message, payable)]
+        pub fn add_addresses(
+            &mut self,
+            addrs: Vec<Multiaddr>,
+            network_id: NetworkId,
+            roles: NodeRole,
+        ) -> Result<()> {
+            self.ensure_can_list()?;
+            if addrs.len() > MAX_NODE_BATCH || addrs.len() > self.config.max_bootnodes as usize {
+                return Err(Error::CapacityExceeded);
+            }
+            if addrs.is_empty() {
+                return Ok(());
+            }
+            let count = addrs.len() as Balance;
+            let value = self.env().transferred_value();
+            if value < self.config.node_stake.saturating_mul(count) {
+                return Err(Error::InsufficientStake);
+            }
+
+            let operator = self.env().caller();
+            for (i, addr) in addrs.into_iter().enumerate() {
+                // the first node also locks what doesn't divide evenly
+                let stake = value / count + if i == 0 { value % count } else { 0 };
+                self.list_node(addr, network_id, roles, operator, operator, stake)?;
+            }
+            Ok(())
+        }
+
+        /// Removes up to 20 addresses in one call, as with `remove_address`. Fails without
+        /// removing any address if one is missing or the caller can't manage it
+        #[ink(message, payable)]
+        pub fn remove_addresses(&mut self, addrs: Vec<Multiaddr>) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Nodes)?;
+            if addrs.len() > MAX_NODE_BATCH {
+                return Err(Error::CapacityExceeded);
+            }
+            for addr in addrs {
+                self.remove_address(addr)?;
+            }
+            Ok(())
+        }
+
+        /// Returns the value sent with the call after checking it covers the bootnode stake
+        fn check_stake(&self) -> Result<Balance> {
+            let stake = self.env().transferred_value();
+            if stake < self.config.node_stake {
+                return Err(Error::InsufficientStake);
+            }
+            Ok(stake)
+        }
+
+        /// Drops the records kept for a bootnode taken off the list, crediting its stake back
+        /// to the account that paid it
+        fn unlist_node(&mut self, record: &NodeRecord) {
+            if record.stake > 0 {
+                self.staked = self.staked.saturating_sub(record.stake);
+                self.credit(record.payer, record.stake);
+            }
+            self.node_networks.remove(&record.multiaddr);
+            self.node_transports.remove(&record.multiaddr);
+            self.attestations.remove(&record.multiaddr);
+            let operated = self.operated_nodes.get(record.operator).unwrap_or_default();
+            self.operated_nodes
+                .insert(record.operator, &operated.saturating_sub(1));
+        }
+
+        /// Returns the slot of a listed bootnode after checking that the caller is a node
+        /// registrar or the node's operator
+        fn manage_node(&self, addr: &Multiaddr) -> Result<u32> {
+            let caller = self.env().caller();
+            let slot = self.node_slots.get(addr);
+            let operator = slot
+                .and_then(|slot| self.nodes.get(slot))
+                .map(|record| record.operator);
+            if !self.has_role(caller, Role::NodeRegistrar) && operator != Some(caller) {
+                return Err(Error::Unauthorized);
+            }
+            slot.ok_or(Error::NodeNotFound)
+        }
+
+        /// Retrieves a listed bootnode by its address
+        fn node_record(&self, addr: &Multiaddr) -> Option<NodeRecord> {
+            self.node_slots
+                .get(addr)
+                .and_then(|slot| self.nodes.get(slot))
+        }
+
+        /// Iterates over the listed bootnodes from oldest to newest
+        fn iter_nodes(&self) -> impl Iterator<Item = NodeRecord> + '_ {
+            core::iter::successors(self.nodes_head, |slot| {
+                self.node_links.get(slot).and_then(|link| link.next)
+            })
+            .filter_map(|slot| self.nodes.get(slot))
+        }
+
+        /// Iterates over the live nodes of a network that serve as bootnodes, oldest first
+        fn live_bootnodes(&self, network_id: NetworkId) -> impl Iterator<Item = NodeRecord> + '_ {
+            self.iter_nodes().filter(move |record| {
+                self.on_network(record, network_id)
+                    && self.is_live(record)
+                    && record.roles.contains(NodeRole::BOOTNODE)
+            })
+        }
+
+        /// Writes a bootnode to the first free slot and links it at the end of the list
+        fn push_node(&mut self, record: NodeRecord) {
+            let slot = self.node_count;
+            self.nodes.insert(slot, &record);
+            self.node_slots.insert(&record.multiaddr, &slot);
+            self.node_links.insert(
+                slot,
+                &NodeLink {
+                    prev: self.nodes_tail,
+                    next: None,
+                },
+            );
+            match self.nodes_tail {
+                Some(tail) => self.set_next(tail, Some(slot)),
+                None => self.nodes_head = Some(slot),
+            }
+            self.nodes_tail = Some(slot);
+            self.node_count += 1;
+        }
+
+        /// Takes the bootnode out of a slot and unlinks it, then moves the node in the last
+        /// slot into the freed one so the slots in use stay contiguous
+        fn take_node(&mut self, slot: u32) -> Option<NodeRecord> {
+            let record = self.nodes.take(slot)?;
+            let link = self.node_links.take(slot).unwrap_or_default();
+            self.node_slots.remove(&record.multiaddr);
+            self.relink(link, None);
+            self.node_count -= 1;
+
+            let last = self.node_count;
+            if slot != last {
+                if let Some(moved) = self.nodes.take(last) {
+                    let moved_link = self.node_links.take(last).unwrap_or_default();
+                    self.nodes.insert(slot, &moved);
+                    self.node_links.insert(slot, &moved_link);
+                    self.node_slots.insert(&moved.multiaddr, &slot);
+                    self.relink(moved_link, Some(slot));
+                }
+            }
+            Some(record)
+        }
+
+        /// Points the neighbours of a link, or the head and tail, at `slot` instead of the
+        /// node the link belonged to. With `None` the node is unlinked
+        fn relink(&mut self, link: NodeLink, slot: Option<u32>) {
+            let (prev, next) = match slot {
+                Some(_) => (slot, slot),
+                None => (link.next, link.prev),
+            };
+            match link.prev {
+                Some(before) => self.set_next(before, prev),
+                None => self.nodes_head = prev,
+            }
+            match link.next {
+                Some(after) => self.set_prev(after, next),
+                None => self.nodes_tail = next,
+            }
+        }
+
+        /// Sets the slot following a node's slot
+        fn set_next(&mut self, slot: u32, next: Option<u32>) {
+            let mut link = self.node_links.get(slot).unwrap_or_default();
+            link.next = next;
+            self.node_links.insert(slot, &link);
+        }
+
+        /// Sets the slot preceding a node's slot
+        fn set_prev(&mut self, slot: u32, prev: Option<u32>) {
+            let mut link = self.node_links.get(slot).unwrap_or_default();
+            link.prev = prev;
+            self.node_links.insert(slot, &link);
+        }
+
+        /// Checks if a bootnode was registered for the given network
+        fn on_network(&self, record: &NodeRecord, network_id: NetworkId) -> bool {
+            self.node_networks.get(&record.multiaddr) == Some(network_id)
+        }
+
+        /// Checks if a bootnode has sent a heartbeat within the configured TTL and isn't
+        /// delisted for dial failures
+        fn is_live(&self, record: &NodeRecord) -> bool {
+            self.is_fresh(record)
+                && record
+                    .probation_ends
+                    .is_none_or(|ends| self.env().block_number() >= ends)
+        }
+
+        /// Checks if a bootnode has sent a heartbeat within the configured TTL
+        fn is_fresh(&self, record: &NodeRecord) -> bool {
+            self.env().block_number().saturating_sub(record.last_seen) <= self.config.node_ttl
+        }
+
+        /// Records that a bootnode is still up, keeping it in the list for another TTL.
+        /// A node delisted for dial failures starts its probation period and returns once it
+        /// has passed. Node registrars and the node's operator only
+        #[ink(message, payable)]
+        pub fn heartbeat(&mut self, addr: Multiaddr) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Nodes)?;
+            let slot = self.manage_node(&addr)?;
+            if let Some(mut record) = self.nodes.get(slot) {
+                let block = self.env().block_number();
+                let epoch = self.current_epoch();
+                record.last_seen = block;
+                record.heartbeats = self.heartbeat_history(&record) | 1;
+                record.heartbeat_epoch = epoch;
+                // heartbeats of delisted nodes don't build trust
+                if record.probation_ends.is_none_or(|ends| block >= ends) {
+                    let mut trust = self.operator_trust.get(record.operator).unwrap_or_default();
+                    if trust.last_epoch != Some(epoch) {
+                        trust.clean_epochs = trust.clean_epochs.saturating_add(1);
+                        trust.last_epoch = Some(epoch);
+                        self.operator_trust.insert(record.operator, &trust);
+                    }
+                }
+                if record.probation_ends == Some(BlockNumber::MAX) {
+                    let ends_at = block.saturating_add(PROBATION_PERIOD);
+                    record.probation_ends = Some(ends_at);
+
+                    // emit event
+                    self.env().emit_event(BootNodeProbationStarted {
+                        address: addr,
+                        ends_at,
+                    });
+                }
+                self.nodes.insert(slot, &record);
+            }
+            Ok(())
+        }
+
+        /// Reports that the caller failed to dial a listed bootnode. Operators of other listed
+        /// bootnodes only, once per node every epoch. When enough operators report the same
+        /// node within an epoch, it's delisted until its next heartbeat and a probation period
+        #[ink(message, payable)]
+        pub fn report_dial_failure(&mut self, addr: Multiaddr) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Nodes)?;
+            let caller = self.env().caller();
+            if self.operated_nodes.get(caller).unwrap_or_default() == 0 {
+                return Err(Error::Unauthorized);
+            }
+            let slot = self.node_slots.get(&addr).ok_or(Error::NodeNotFound)?;
+            let mut record = self.nodes.get(slot).ok_or(Error::NodeNotFound)?;
+            // operators can't report their own nodes
+            if record.operator == caller {
+                return Err(Error::Unauthorized);
+            }
+            let epoch = self.current_epoch();
+            if record.dial_failure_epoch != epoch {
+                record.dial_failures.clear();
+                record.dial_failure_epoch = epoch;
+            }
+            if record.dial_failures.contains(&caller) {
+                return Err(Error::AlreadyExists);
+            }
+            record.dial_failures.push(caller);
+            let reports = record.dial_failures.len() as u32;
+            let delisted = record.dial_failures.len() >= DIAL_FAILURE_THRESHOLD;
+            if delisted {
+                record.dial_failures.clear();
+                record.probation_ends = Some(BlockNumber::MAX);
+                self.operator_trust.remove(record.operator);
+            }
+            self.nodes.insert(slot, &record);
+
+            // emit events
+            self.env().emit_event(DialFailureReported {
+                address: addr.clone(),
+                reporter: caller,
+                reports,
+            });
+            if delisted {
+                self.env().emit_event(BootNodeDelisted { address: addr });
+            }
+            Ok(())
+        }
+
+        /// Returns a bootnode's heartbeat history shifted so that bit 0 stands for the current
+        /// epoch. Epochs older than the uptime window drop off
+        fn heartbeat_history(&self, record: &NodeRecord) -> u32 {
+            let elapsed = self.current_epoch().saturating_sub(record.heartbeat_epoch);
+            if elapsed >= UPTIME_WINDOW {
+                0
+            } else {
+                record.heartbeats << elapsed
+            }
+        }
+
+        /// Returns the percentage of the epochs within the uptime window in which a bootnode
+        /// sent a heartbeat. Only the epochs since the node was listed count
+        fn uptime_score(&self, record: &NodeRecord) -> u32 {
+            let listed_epoch = record
+                .registered_at_block
+                .saturating_sub(self.genesis_block)
+                / self.epoch_length;
+            let epochs = self
+                .current_epoch()
+                .saturating_sub(listed_epoch)
+                .saturating_add(1)
+                .min(UPTIME_WINDOW);
+            self.heartbeat_history(record).count_ones() * 100 / epochs
+        }
+
+        /// Retrieves a listed bootnode's uptime score: the percentage of the last 32 epochs,
+        /// or of the epochs since it was listed, in which it sent a heartbeat
+        #[ink(message, payable)]
+        pub fn get_node_uptime(&self, addr: Multiaddr) -> Option<u32> {
+            self.node_record(&addr)
+                .map(|record| self.uptime_score(&record))
+        }
+
+        /// Removes every bootnode whose latest heartbeat is older than the TTL and returns
+        /// how many were removed. Anyone can call it
+        #[ink(message, payable)]
+        pub fn prune_stale_nodes(&mut self) -> Result<u32> {
+            self.ensure_not_paused(Subsystem::Nodes)?;
+            let stale = self
+                .iter_nodes()
+                .filter(|record| !self.is_fresh(record))
+                .collect::<Vec<_>>();
+            for record in &stale {
+                if let Some(slot) = self.node_slots.get(&record.multiaddr) {
+                    self.take_node(slot);
+                }
+                self.unlist_node(record);
+
+                // emit event
+                self.env().emit_event(BootNodeRemoved {
+                    address: record.multiaddr.clone(),
+                });
+            }
+            Ok(stale.len() as u32)
+        }
+
+        /// Submits an enclave attestation for a listed bootnode: the hash of the report and
+        /// the attestation service's signature over it. It replaces any earlier attestation and
+        /// counts once a verifier validates it. Node registrars and the node's operator only
+        #[ink(message, payable)]
+        pub fn submit_attestation(
+            &mut self,
+            addr: Multiaddr,
+            report_hash: Hash,
+            issuer_signature: Vec<u8>,
+        ) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Nodes)?;
+            self.manage_node(&addr)?;
+            if issuer_signature.len() > MAX_ATTESTATION_SIGNATURE_LEN {
+                return Err(Error::CapacityExceeded);
+            }
+            self.attestations.insert(
+                &addr,
+                &Attestation {
+                    report_hash,
+                    issuer_signature,
+                    submitted_at: self.env().block_number(),
+                    verified_at: None,
+                },
+            );
+
+            // emit event
+            self.env().emit_event(AttestationSubmitted {
+                address: addr,
+                report_hash,
+            });
+            Ok(())
+        }
+
+        /// Validates a bootnode's attestation after checking the report and issuer signature
+        /// off-chain, or drops it with `valid` set to `false`. Verifiers only
+        #[ink(message, payable)]
+        pub fn verify_attestation(&mut self, addr: Multiaddr, valid: bool) -> Result<()> {
+            self.ensure_role(Role::Verifier)?;
+            let mut attestation = self.attestations.get(&addr).ok_or(Error::NodeNotAttested)?;
+            if valid {
+                attestation.verified_at = Some(self.env().block_number());
+                self.attestations.insert(&addr, &attestation);
+            } else {
+                self.attestations.remove(&addr);
+            }
+
+            // emit event
+            self.env().emit_event(AttestationVerified {
+                address: addr,
+                valid,
+            });
+            Ok(())
+        }
+
+        /// Retrieves the attestation submitted for a bootnode, verified or not
+        #[ink(message, payable)]
+        pub fn get_attestation(&self, addr: Multiaddr) -> Option<Attestation> {
+            self.attestations.get(&addr)
+        }
+
+        /// Checks if a bootnode has an attestation validated by a verifier
+        fn is_attested(&self, addr: &Multiaddr) -> bool {
+            self.attestations
+                .get(addr)
+                .is_some_and(|attestation| attestation.verified_at.is_some())
+        }
+
+        /// Sets the region label and capacity peers see for a listed bootnode. Node registrars
+        /// and the node's operator only
+        #[ink(message, payable)]
+        pub fn set_node_metadata(
+            &mut self,
+            addr: Multiaddr,
+            region: Vec<u8>,
+            capacity: u32,
+        ) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Nodes)?;
+            let slot = self.manage_node(&addr)?;
+            if region.len() > MAX_REGION_LEN {
+                return Err(Error::CapacityExceeded);
+            }
+            if let Some(mut record) = self.nodes.get(slot) {
+                record.region = region.clone();
+                record.capacity = capacity;
+                self.nodes.insert(slot, &record);
+            }
+
+            // emit event
+            self.env().emit_event(NodeMetadataUpdated {
+                address: addr,
+                region,
+                capacity,
+            });
+            Ok(())
+        }
+
+        /// Removes a misbehaving bootnode and moves its stake to the treasury instead of
+        /// refunding it. Owner only; the council slashes through a proposal
+        #[ink(message, payable)]
+        pub fn slash(&mut self, addr: Multiaddr, reason: Vec<u8>) -> Result<()> {
+            self.ensure_owner()?;
+            self.slash_node(addr, reason).map(|_| ())
+        }
+
+        /// Removes a bootnode, keeping its stake, and returns the stake confiscated
+        fn slash_node(&mut self, addr: Multiaddr, reason: Vec<u8>) -> Result<Balance> {
+            let slot = self.node_slots.get(&addr).ok_or(Error::NodeNotFound)?;
+            let mut record = self.take_node(slot).ok_or(Error::NodeNotFound)?;
+            let amount = record.stake;
+            self.staked = self.staked.saturating_sub(amount);
+            record.stake = 0;
+            self.unlist_node(&record);
+            self.operator_trust.remove(record.operator);
+
+            // emit events
+            self.env().emit_event(NodeSlashed {
+                address: addr.clone(),
+                operator: record.operator,
+                amount,
+                reason,
+            });
+            self.env().emit_event(BootNodeRemoved { address: addr });
+            Ok(amount)
+        }
+
+        /// Files a report against a listed bootnode, e.g. one caught equivocating, and returns
+        /// its id. The value sent with the call is locked as a bond and must cover the
+        /// bootnode stake. If the report is upheld, the bond is refunded along with a share of
+        /// the slashed stake; if it's rejected, the bond moves to the treasury
+        #[ink(message, payable)]
+        pub fn report_with_bond(&mut self, target: Multiaddr, evidence_cid: CID) -> Result<u32> {
+            self.ensure_not_paused(Subsystem::Nodes)?;
+            let bond = self.check_stake()?;
+            if bond == 0 {
+                return Err(Error::InsufficientStake);
+            }
+            if !self.node_slots.contains(&target) {
+                return Err(Error::NodeNotFound);
+            }
+            let id = self.next_report_id;
+            self.next_report_id = id.checked_add(1).ok_or(Error::CapacityExceeded)?;
+            let reporter = self.env().caller();
+            self.staked = self.staked.saturating_add(bond);
+            self.node_reports.insert(
+                id,
+                &NodeReport {
+                    reporter,
+                    target: target.clone(),
+                    evidence_cid: evidence_cid.clone(),
+                    bond,
+                    filed_at: self.env().block_number(),
+                },
+            );
+
+            // emit event
+            self.env().emit_event(NodeReportFiled {
+                id,
+                target,
+                reporter,
+                evidence_cid,
+                bond,
+            });
+            Ok(id)
+        }
+
+        /// Resolves a bonded report. Upholding it slashes the bootnode, if it's still listed,
+        /// and credits the reporter with the bond and a share of the slashed stake; rejecting
+        /// it moves the bond to the treasury. Owner only; the council adjudicates through a
+        /// proposal
+        #[ink(message, payable)]
+        pub fn adjudicate_report(&mut self, id: u32, upheld: bool) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_no_council()?;
+            self.resolve_report(id, upheld)
+        }
+
+        /// Settles a bonded report and the bond locked with it
+        fn resolve_report(&mut self, id: u32, upheld: bool) -> Result<()> {
+            let report = self.node_reports.take(id).ok_or(Error::ReportNotFound)?;
+            self.staked = self.staked.saturating_sub(report.bond);
+            let mut reward = 0;
+            if upheld {
+                if self.node_slots.contains(&report.target) {
+                    let slashed = self.slash_node(report.target, report.evidence_cid)?;
+                    reward = slashed.saturating_mul(REPORTER_SHARE_PERCENT) / 100;
+                }
+                self.credit(report.reporter, report.bond.saturating_add(reward));
+            }
+
+            // emit event
+            self.env()
+                .emit_event(NodeReportResolved { id, upheld, reward });
+            Ok(())
+        }
+
+        /// Retrieves a bonded report awaiting adjudication
+        #[ink(message, payable)]
+        pub fn get_node_report(&self, id: u32) -> Option<NodeReport> {
+            self.node_reports.get(id)
+        }
+
+        /// Removes a node from the bootnode list, dropping any suggestion for it, and keeps it
+        /// from being listed, suggested or subscribed again. Admins only; the council
+        /// blacklists through a proposal
+        #[ink(message, payable)]
+        pub fn blacklist(&mut self, addr: Multiaddr) -> Result<()> {
+            self.ensure_role(Role::Admin)?;
+            self.set_blacklisted(addr, true);
+            Ok(())
+        }
+
+        /// Lifts a blacklisting. Existing listings and subscriptions aren't restored.
+        /// Admins only; the council unblacklists through a proposal
+        #[ink(message, payable)]
+        pub fn unblacklist(&mut self, addr: Multiaddr) -> Result<()> {
+            self.ensure_role(Role::Admin)?;
+            self.set_blacklisted(addr, false);
+            Ok(())
+        }
+
+        /// Checks if a node address has been blacklisted
+        #[ink(message, payable)]
+        pub fn is_blacklisted(&self, addr: Multiaddr) -> bool {
+            self.blacklisted.contains(&addr)
+        }
+
+        /// Adds an address to, or removes it from, the blacklist
+        fn set_blacklisted(&mut self, addr: Multiaddr, blacklisted: bool) {
+            if !blacklisted {
+                self.blacklisted.remove(&addr);
+
+                // emit event
+                self.env().emit_event(NodeUnblacklisted { address: addr });
+                return;
+            }
+
+            self.blacklisted.insert(&addr, &());
+            if let Some(suggestion) = self.node_suggestions.take(&addr) {
+                self.release_suggestion(&suggestion);
+            }
+            if let Some(record) = self
+                .node_slots
+                .get(&addr)
+                .and_then(|slot| self.take_node(slot))
+            {
+                self.unlist_node(&record);
+
+                // emit event
+                self.env().emit_event(BootNodeRemoved {
+                    address: addr.clone(),
+                });
+            }
+
+            // emit event
+            self.env().emit_event(NodeBlacklisted { address: addr });
+        }
+
+        /// Raises the reputation of a listed bootnode. Operators of other listed bootnodes
+        /// only, once per node every epoch
+        #[ink(message, payable)]
+        pub fn vouch(&mut self, addr: Multiaddr) -> Result<()> {
+            self.rate_node(addr, true)
+        }
+
+        /// Lowers the reputation of a listed bootnode. Operators of other listed bootnodes
+        /// only, once per node every epoch
+        #[ink(message, payable)]
+        pub fn report(&mut self, addr: Multiaddr) -> Result<()> {
+            self.rate_node(addr, false)
+        }
+
+        /// Adds one to, or takes one from, a bootnode's reputation on behalf of the caller
+        fn rate_node(&mut self, addr: Multiaddr, vouched: bool) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Nodes)?;
+            let caller = self.env().caller();
+            if self.operated_nodes.get(caller).unwrap_or_default() == 0 {
+                return Err(Error::Unauthorized);
+            }
+            let slot = self.node_slots.get(&addr).ok_or(Error::NodeNotFound)?;
+            let mut record = self.nodes.get(slot).ok_or(Error::NodeNotFound)?;
+            // operators can't rate their own nodes
+            if record.operator == caller {
+                return Err(Error::Unauthorized);
+            }
+            let epoch = self.current_epoch();
+            let key = (caller, addr.clone());
+            if self.node_ratings.get(&key) == Some(epoch) {
+                return Err(Error::RateLimited);
+            }
+            self.node_ratings.insert(&key, &epoch);
+
+            record.reputation = if vouched {
+                record.reputation.saturating_add(1)
+            } else {
+                record.reputation.saturating_sub(1)
+            };
+            self.nodes.insert(slot, &record);
+
+            // emit event
+            self.env().emit_event(NodeRated {
+                address: addr,
+                rater: caller,
+                vouched,
+                reputation: record.reputation,
+            });
+            Ok(())
+        }
+
+        /// Retrieves a listed bootnode with its metadata
+        #[ink(message, payable)]
+        pub fn get_node_record(&self, addr: Multiaddr) -> Option<NodeRecord> {
+            self.node_record(&addr)
+        }
+
+        /// Retrieves the list of bootnodes available on a network, skipping nodes without a
+        /// heartbeat within the TTL. The list starts at a different node every block, so
+        /// clients don't all dial the same peer first
+        #[ink(message, payable)]
+        pub fn get_node_addresses(&self, network_id: NetworkId) -> Vec<Multiaddr> {
+            let mut nodes = self
+                .live_bootnodes(network_id)
+                .map(|record| record.multiaddr)
+                .collect::<Vec<_>>();
+            if !nodes.is_empty() {
+                let offset = self.env().block_number() as usize % nodes.len();
+                nodes.rotate_left(offset);
+            }
+            nodes
+        }
+
+        /// Retrieves up to `count` live bootnodes, at most 16, picked at random so that joining
+        /// nodes spread their first dials across the list. The pick is derived from the block
+        /// and the caller's seed, so it can be reproduced within the same block
+        #[ink(message, payable)]
+        pub fn get_random_bootnodes(
+            &self,
+            network_id: NetworkId,
+            count: u32,
+            seed: u64,
+        ) -> Vec<Multiaddr> {
+            let mut nodes = self
+                .live_bootnodes(network_id)
+                .map(|record| record.multiaddr)
+                .collect::<Vec<_>>();
+            let count = count.min(MAX_RANDOM_BOOTNODES) as usize;
+            let count = count.min(nodes.len());
+
+            // partial Fisher-Yates shuffle of the first `count` positions
+            let block = self.env().block_number();
+            let timestamp = self.env().block_timestamp();
+            for i in 0..count {
+                let mut output = [0u8; 32];
+                ink::env::hash_encoded::<Blake2x256, _>(
+                    &(block, timestamp, seed, i as u32),
+                    &mut output,
+                );
+                let mut random = [0u8; 8];
+                random.copy_from_slice(&output[..8]);
+                let j = i + (u64::from_le_bytes(random) % (nodes.len() - i) as u64) as usize;
+                nodes.swap(i, j);
+            }
+            nodes.truncate(count);
+            nodes
+        }
+
+        /// Retrieves the bootnodes with a reputation of at least `min_score` that sent a
+        /// heartbeat within the TTL, most reputable first and, among equally reputable nodes,
+        /// those with the highest uptime first
+        #[ink(message, payable)]
+        pub fn get_node_addresses_by_reputation(
+            &self,
+            network_id: NetworkId,
+            min_score: i32,
+        ) -> Vec<Multiaddr> {
+            let mut nodes = self
+                .iter_nodes()
+                .filter(|record| self.on_network(record, network_id) && self.is_live(record))
+                .filter(|record| record.reputation >= min_score)
+                .collect::<Vec<_>>();
+            nodes.sort_by_cached_key(|record| {
+                core::cmp::Reverse((record.reputation, self.uptime_score(record)))
+            });
+            nodes.into_iter().map(|record| record.multiaddr).collect()
+        }
+
+        /// Retrieves the live bootnodes with an uptime score of at least `min_uptime` percent,
+        /// highest uptime first
+        #[ink(message, payable)]
+        pub fn get_node_addresses_by_uptime(
+            &self,
+            network_id: NetworkId,
+            min_uptime: u32,
+        ) -> Vec<Multiaddr> {
+            let mut nodes = self
+                .live_bootnodes(network_id)
+                .map(|record| (self.uptime_score(&record), record.multiaddr))
+                .filter(|(uptime, _)| *uptime >= min_uptime)
+                .collect::<Vec<_>>();
+            nodes.sort_by_key(|(uptime, _)| core::cmp::Reverse(*uptime));
+            nodes.into_iter().map(|(_, addr)| addr).collect()
+        }
+
+        /// Retrieves the live nodes serving every role in `role`
+        #[ink(message, payable)]
+        pub fn get_nodes_by_role(&self, network_id: NetworkId, role: NodeRole) -> Vec<Multiaddr> {
+            self.iter_nodes()
+                .filter(|record| self.on_network(record, network_id) && self.is_live(record))
+                .filter(|record| record.roles.contains(role))
+                .map(|record| record.multiaddr)
+                .collect()
+        }
+
+        /// Retrieves at most `limit` live bootnodes dialable over the given transport
+        #[ink(message, payable)]
+        pub fn get_nodes_by_transport(
+            &self,
+            network_id: NetworkId,
+            transport: Transport,
+            limit: u32,
+        ) -> Vec<Multiaddr> {
+            self.iter_nodes()
+                .filter(|record| self.on_network(record, network_id) && self.is_live(record))
+                .map(|record| record.multiaddr)
+                .filter(|addr| self.node_transports.get(addr) == Some(transport))
+                .take(limit as usize)
+                .collect()
+        }
+
+        /// Retrieves at most `limit` live bootnodes, those in `region` first. When the region
+        /// has too few nodes the rest are filled from the global list in registration order
+        #[ink(message, payable)]
+        pub fn get_nodes_by_region(
+            &self,
+            network_id: NetworkId,
+            region: Vec<u8>,
+            limit: u32,
+        ) -> Vec<Multiaddr> {
+            let (mut nodes, others): (Vec<_>, Vec<_>) = self
+                .iter_nodes()
+                .filter(|record| self.on_network(record, network_id) && self.is_live(record))
+                .partition(|record| record.region == region);
+            nodes.extend(others);
+            nodes
+                .into_iter()
+                .map(|record| record.multiaddr)
+                .take(limit as usize)
+                .collect()
+        }
+
+        /// Retrieves the hashtable CID of an account
+        #[ink(message, payable)]
+        pub fn get_account_ht_cid(&self, did: DID, auth_material: Vec<u8>) -> Vec<u8> {
+            if let Some(account_info) = self.accounts.get(&did) {
+                if account_info.is_active()
+                    && !self.is_auth_locked(&did)
+                    && account_info.verify_auth_material(&auth_material)
+                {
+                    account_info.hashtable_cid.clone()
+                } else {
+                    Vec::new()
+                }
+            } else {
+                Vec::new()
+            }
+        }
+
+        /// Retrieves everything stored for an account in one call. Deactivated accounts are
+        /// withheld, as with `get_account_ht_cid`
+        #[ink(message, payable)]
+        pub fn get_account_info(&self, did: DID, auth_material: Vec<u8>) -> Option<AccountInfo> {
+            if self.is_auth_locked(&did) {
+                return None;
+            }
+            self.accounts.get(&did).filter(|account| {
+                account.is_active() && account.verify_auth_material(&auth_material)
+            })
+        }
+
+        /// Replaces the auth material of an account with the material hashing to `new_hash`.
+        /// Only the account's controller can rotate it; nodes should drop credentials cached
+        /// for the account
+        #[ink(message, payable)]
+        pub fn rotate_auth_material(&mut self, did: DID, new_hash: Hash) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let mut account = self.authorize(&did)?;
+            account.auth_hash = new_hash;
+            self.accounts.insert(&did, &account);
+
+            // emit events
+            self.emit_controller_activity(
+                &did,
+                &account.controllers,
+                AccountAction::AuthMaterialRotated,
+            );
+            self.env().emit_event(AuthMaterialRotated { did });
+            Ok(())
+        }
+
+        /// Updates the hashtable CID of an account
+        #[ink(message, payable)]
+        pub fn update_account_ht_cid(&mut self, did: DID, ht_cid: Vec<u8>) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let mut account = self.authorize(&did)?;
+            account.hashtable_cid = ht_cid.clone();
+            self.accounts.insert(&did, &account);
+            // a direct update supersedes any announced one
+            self.pending_cids.remove(&did);
+            self.track_activity().cid_updates += 1;
+
+            // emit events
+            self.emit_controller_activity(
+                &did,
+                &account.controllers,
+                AccountAction::HashtableCidUpdated,
+            );
+            self.env().emit_event(HashTableAddressUpdated {
+                did: did.clone(),
+                ipfs_address: ht_cid.clone(),
+            });
+            self.notify_observers(&did, RegistryChange::CidUpdated(ht_cid));
+            Ok(())
+        }
+
+        /// Announces a hashtable CID that is being published, before switching to it.
+        /// Nodes can prefetch the pending CID while the confirmed one stays authoritative
+        #[ink(message, payable)]
+        pub fn announce_pending_update(&mut self, did: DID, new_cid: CID) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            self.authorize(&did)?;
+
+            self.pending_cids.insert(&did, &new_cid);
+
+            // emit event
+            self.env().emit_event(HashTableUpdateAnnounced {
+                did,
+                ipfs_address: new_cid,
+            });
+            Ok(())
+        }
+
+        /// Switches an account to its announced hashtable CID once publishing has completed
+        #[ink(message, payable)]
+        pub fn confirm_update(&mut self, did: DID) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            self.authorize(&did)?;
+            let new_cid = self
+                .pending_cids
+                .get(&did)
+                .ok_or(Error::PendingUpdateNotFound)?;
+            self.update_account_ht_cid(did, new_cid)
+        }
+
+        /// Retrieves the hashtable CID announced for an account but not yet confirmed.
+        /// Deactivated accounts are withheld, as with `get_account_ht_cid`
+        #[ink(message, payable)]
+        pub fn get_pending_ht_cid(&self, did: DID, auth_material: Vec<u8>) -> Vec<u8> {
+            match self.accounts.get(&did) {
+                Some(account_info)
+                    if account_info.is_active()
+                        && !self.is_auth_locked(&did)
+                        && account_info.verify_auth_material(&auth_material) =>
+                {
+                    self.pending_cids.get(&did).unwrap_or_default()
+                }
+                _ => Vec::new(),
+            }
+        }
+
+        /// Sets the authentication policy nodes enforce when an application connects
+        #[ink(message, payable)]
+        pub fn set_auth_policy(
+            &mut self,
+            app_did: DID,
+            policy_cid: CID,
+            policy_hash: Hash,
+        ) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let mut account = self.authorize(&app_did)?;
+            account.auth_policy = Some(AuthPolicy {
+                policy_cid: policy_cid.clone(),
+                policy_hash,
+            });
+            self.accounts.insert(&app_did, &account);
+
+            // emit event
+            self.env().emit_event(AuthPolicyUpdated {
+                did: app_did,
+                policy_cid,
+                policy_hash,
+            });
+            Ok(())
+        }
+
+        /// Retrieves the authentication policy of an application, if one is set
+        #[ink(message, payable)]
+        pub fn get_auth_policy(&self, app_did: DID) -> Option<AuthPolicy> {
+            self.accounts
+                .get(&app_did)
+                .and_then(|account| account.auth_policy)
+        }
+
+        /// Subscribe to join nodes supporting application on the network this contract serves.
+        /// If the application vets its subscribers, a request is opened instead and the node
+        /// joins once it is approved
+        #[ink(message, payable)]
+        pub fn subscribe_node(
+            &mut self,
+            did: DID,
+            addr: Multiaddr,
+            network_id: NetworkId,
+        ) -> Result<()> {
+            self.check_subscription(&did, &addr, network_id)?;
+            let subscribed = self
+                .subscribers
+                .get(&did)
+                .unwrap_or_default()
+                .contains(&addr);
+            if let Some(request_ttl) = self.subscription_approval.get(&did).filter(|_| !subscribed)
+            {
+                let now = self.env().block_number();
+                let expires_at = now.saturating_add(request_ttl);
+
+                // drop expired requests along with any earlier request from this node
+                let mut pending = self.pending_subscriptions.get(&did).unwrap_or_default();
+                pending.retain(|request| request.expires_at >= now && request.node != addr);
+                pending.push(PendingSubscription {
+                    node: addr.clone(),
+                    expires_at,
+                    requester: self.env().caller(),
+                });
+                self.pending_subscriptions.insert(&did, &pending);
+
+                // emit event
+                self.env().emit_event(SubscriptionRequested {
+                    did,
+                    node: addr,
+                    expires_at,
+                });
+                return Ok(());
+            }
+
+            self.add_subscriber(did, addr, self.env().caller());
+            Ok(())
+        }
+
+        /// Checks that the caller acts for a subscribed node: the account that subscribed it,
+        /// or the operator of the listed bootnode at that address
+        fn ensure_subscriber(&self, did: &DID, addr: &Multiaddr) -> Result<()> {
+            let caller = self.env().caller();
+            let owner = self.subscription_owners.get((did, addr));
+            let operator = self.node_record(addr).map(|record| record.operator);
+            if owner != Some(caller) && operator != Some(caller) {
+                return Err(Error::Unauthorized);
+            }
+            Ok(())
+        }
+
+        /// Forgets the account acting for a subscription, freeing one of its subscriptions
+        fn release_subscription(&mut self, did: &DID, addr: &Multiaddr) {
+            if let Some(owner) = self.subscription_owners.take((did, addr)) {
+                let count = self.account_subscriptions.get((did, &owner)).unwrap_or(0);
+                self.account_subscriptions
+                    .insert((did, &owner), &count.saturating_sub(1));
+            }
+        }
+
+        /// Checks that a listed bootnode's operator has room left in its onboarding quota
+        fn check_onboarding_quota(&self, addr: &Multiaddr) -> Result<()> {
+            let Some(operator) = self.node_record(addr).map(|record| record.operator) else {
+                return Ok(());
+            };
+            let Some(quota) = self.onboarding_quota(operator) else {
+                return Ok(());
+            };
+            let served = self
+                .iter_nodes()
+                .filter(|record| record.operator == operator)
+                .map(|record| {
+                    self.node_subscriptions
+                        .get(&record.multiaddr)
+                        .unwrap_or_default()
+                        .len() as u32
+                })
+                .sum::<u32>();
+            if served >= quota {
+                return Err(Error::OnboardingQuotaExceeded);
+            }
+            Ok(())
+        }
+
+        /// Returns the subscriptions an operator's bootnodes can serve together, growing with
+        /// every clean epoch, or `None` once the operator is trusted. Node registrars are
+        /// trusted from the start
+        fn onboarding_quota(&self, operator: AccountId) -> Option<u32> {
+            if self.has_role(operator, Role::NodeRegistrar) {
+                return None;
+            }
+            let clean_epochs = self
+                .operator_trust
+                .get(operator)
+                .unwrap_or_default()
+                .clean_epochs;
+            if clean_epochs >= TRUSTED_OPERATOR_EPOCHS {
+                return None;
+            }
+            Some(ONBOARDING_QUOTA + clean_epochs * ONBOARDING_QUOTA_STEP)
+        }
+
+        /// Retrieves the subscriptions an operator's bootnodes can serve together while it is
+        /// onboarding, or `None` once it is trusted
+        #[ink(message, payable)]
+        pub fn get_onboarding_quota(&self, operator: AccountId) -> Option<u32> {
+            self.onboarding_quota(operator)
+        }
+
+        /// Retrieves an operator's track record
+        #[ink(message, payable)]
+        pub fn get_operator_trust(&self, operator: AccountId) -> OperatorTrust {
+            self.operator_trust.get(operator).unwrap_or_default()
+        }
+
+        /// Checks that an account can subscribe another node to an application
+        fn check_subscription_allowance(&self, did: &DID, account: &AccountId) -> Result<()> {
+            let count = self.account_subscriptions.get((did, account)).unwrap_or(0);
+            if count >= self.config.max_subscriptions_per_account {
+                return Err(Error::CapacityExceeded);
+            }
+            Ok(())
+        }
+
+        /// Adds a node to an application's subscribers, recording the account acting for it
+        fn add_subscriber(&mut self, did: DID, addr: Multiaddr, owner: AccountId) {
+            self.index_did(&did);
+            // append to the vector of multiaddresses, or create it for the first node
+            let mut subscribers = self.subscribers.get(&did).unwrap_or_default();
+            if !subscribers.contains(&addr) {
+                subscribers.push(addr.clone());
+                self.subscribers.insert(&did, &subscribers);
+                self.subscribed_at
+                    .insert((&did, &addr), &self.env().block_number());
+                self.record_subscriber_change(&did, &addr, true);
+                self.index_node_subscription(&addr, &did);
+                self.subscription_owners.insert((&did, &addr), &owner);
+                let count = self.account_subscriptions.get((&did, &owner)).unwrap_or(0);
+                self.account_subscriptions
+                    .insert((&did, &owner), &(count + 1));
+                self.track_activity().subscriptions += 1;
+
+                // emit event
+                self.env()
+                    .emit_event(TopicSubscriptionComplete { did, node: addr });
+            }
+        }
+
+        /// Records that a node supports an application in the node's reverse index
+        fn index_node_subscription(&mut self, addr: &Multiaddr, did: &DID) {
+            let mut subscriptions = self.node_subscriptions.get(addr).unwrap_or_default();
+            subscriptions.push(did.clone());
+            self.node_subscriptions.insert(addr, &subscriptions);
+        }
+
+        /// Stop supporting application
+        #[ink(message, payable)]
+        pub fn unsubscribe_node(&mut self, did: DID, address: Multiaddr) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Subscriptions)?;
+            let nodes = self.subscribers.get(&did).unwrap_or_default();
+            if !nodes.contains(&address) {
+                return Err(Error::NotSubscribed);
+            }
+            self.ensure_subscriber(&did, &address)?;
+
+            self.remove_subscriber(did, address, nodes);
+            Ok(())
+        }
+
+        /// Removes a node from an application's subscribers
+        fn remove_subscriber(&mut self, did: DID, address: Multiaddr, nodes: Vec<Multiaddr>) {
+            let filtered_nodes = nodes
+                .iter()
+                .filter(|addr| **addr != address)
+                .cloned()
+                .collect::<Vec<_>>();
+            self.record_subscriber_change(&did, &address, false);
+            self.subscribers.insert(&did, &filtered_nodes);
+            self.subscribed_at.remove((&did, &address));
+            self.release_subscription(&did, &address);
+            let mut subscriptions = self.node_subscriptions.get(&address).unwrap_or_default();
+            subscriptions.retain(|app_did| *app_did != did);
+            self.node_subscriptions.insert(&address, &subscriptions);
+            self.track_activity().unsubscriptions += 1;
+
+            // emit event
+            self.env().emit_event(TopicUnsubscriptionComplete {
+                did: did.clone(),
+                node: address.clone(),
+            });
+            let replicas = filtered_nodes.len() as u32;
+            self.notify_observers(&did, RegistryChange::SubscriberLost(address, replicas));
+        }
+
+        /// Get all nodes supporting an application on a network. Every subscription belongs
+        /// to the network this contract serves, so other networks have none
+        #[ink(message, payable)]
+        pub fn get_subscribers(&self, did: DID, network_id: NetworkId) -> Vec<Multiaddr> {
+            if network_id != self.network_id {
+                return Vec::new();
+            }
+            self.subscribers.get(&did).unwrap_or_default()
+        }
+
+        /// Get all applications a node is subscribed to on a network
+        #[ink(message, payable)]
+        pub fn get_node_subscriptions(&self, addr: Multiaddr, network_id: NetworkId) -> Vec<DID> {
+            if network_id != self.network_id {
+                return Vec::new();
+            }
+            self.node_subscriptions.get(&addr).unwrap_or_default()
+        }
+
+        /// Get up to `limit` nodes supporting an application on a network starting at
+        /// `offset`, along with the total number of subscribed nodes
+        #[ink(message, payable)]
+        pub fn get_subscribers_paged(
+            &self,
+            did: DID,
+            network_id: NetworkId,
+            offset: u32,
+            limit: u32,
+        ) -> (Vec<Multiaddr>, u32) {
+            let nodes = self.get_subscribers(did, network_id);
+            let total = nodes.len() as u32;
+            let page = nodes
+                .into_iter()
+                .skip(offset as usize)
+                .take(limit as usize)
+                .collect();
+            (page, total)
+        }
+
+        /// Get all nodes supporting an application on a network in join order
+        /// (longest-serving first), along with the number of blocks each has been subscribed for
+        #[ink(message, payable)]
+        pub fn get_subscribers_with_tenure(
+            &self,
+            did: DID,
+            network_id: NetworkId,
+        ) -> Vec<(Multiaddr, BlockNumber)> {
+            let now = self.env().block_number();
+            self.get_subscribers(did.clone(), network_id)
+                .into_iter()
+                .map(|addr| {
+                    let joined = self.subscribed_at.get((&did, &addr)).unwrap_or(now);
+                    (addr, now.saturating_sub(joined))
+                })
+                .collect()
+        }
+
+        /// Add an application to the restricted list
+        #[ink(message, payable)]
+        pub fn restrict(&mut self, user_did: DID, app_did: DID) -> Result<()> {
+            self.check_restriction(&user_did, &app_did)?;
+            self.record_account_activity(&user_did);
+            self.add_restriction(user_did, app_did);
+            Ok(())
+        }
+
+        /// Adds a user to an application's restrictions and records the change
+        fn add_restriction(&mut self, user_did: DID, app_did: DID) {
+            let mut users_list = self.restricted.get(&app_did).unwrap_or_default();
+            users_list.push(user_did.clone());
+
+            self.restricted.insert(app_did.clone(), &users_list);
+            let mut apps = self.user_restrictions.get(&user_did).unwrap_or_default();
+            apps.push(app_did.clone());
+            self.user_restrictions.insert(&user_did, &apps);
+            self.bump_key_version(&user_did, &app_did);
+            self.restriction_acks.remove((&user_did, &app_did));
+            self.record_restriction_change(&user_did, &app_did, true);
+
+            // emit event
+            self.env().emit_event(RestrictApplicationAccess {
+                user_did,
+                application_did: app_did,
+            });
+        }
+
+        /// Unrestrict an application's access to user data
+        #[ink(message, payable)]
+        pub fn unrestrict(&mut self, user_did: DID, app_did: DID) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Restrictions)?;
+            self.authorize(&user_did)?;
+            let users = self.restricted.get(&app_did).unwrap_or_default();
+            if !users.contains(&user_did) {
+                return Err(Error::NotRestricted);
+            }
+            let users_list = users
+                .iter()
+                .filter(|did| **did != user_did)
+                .cloned()
+                .collect::<Vec<_>>();
+
+            self.restricted.insert(&app_did, &users_list);
+            let mut apps = self.user_restrictions.get(&user_did).unwrap_or_default();
+            apps.retain(|did| *did != app_did);
+            self.user_restrictions.insert(&user_did, &apps);
+            self.bump_key_version(&user_did, &app_did);
+            self.restriction_acks.remove((&user_did, &app_did));
+            self.record_restriction_change(&user_did, &app_did, false);
+
+            // emit event
+            self.env().emit_event(UnrestrictApplicationAccess {
+                user_did,
+                application_did: app_did,
+            });
+            Ok(())
+        }
+
+        /// Check if an application is restricted
+        #[ink(message, payable)]
+        pub fn is_restricted(&self, did: DID, app_did: DID) -> bool {
+            if let Some(entry) = self.restricted.get(&app_did) {
+                // check if the user is part of the application's restriction list
+                entry.contains(&did)
+            } else {
+                false
+            }
+        }
+
+        /// Check which of the given users have restricted an application, in the order given
+        #[ink(message, payable)]
+        pub fn are_restricted(&self, user_dids: Vec<DID>, app_did: DID) -> Vec<bool> {
+            let entry = self.restricted.get(&app_did).unwrap_or_default();
+            user_dids.iter().map(|did| entry.contains(did)).collect()
+        }
+
+        /// Fetch users that have restricted an application. Passing `user_did` narrows the
+        /// result to that user, so a node can check one user without pulling the whole list
+        #[ink(message, payable)]
+        pub fn get_restriction_list(&self, app_did: DID, user_did: Option<DID>) -> Vec<DID> {
+            let users = self.restricted.get(&app_did).unwrap_or_default();
+            match user_did {
+                // narrow the list down to a single user
+                Some(user_did) => users
+                    .into_iter()
+                    .filter(|did| *did == user_did)
+                    .take(1)
+                    .collect(),
+                None => users,
+            }
+        }
+
+        /// Fetch up to `limit` users that have restricted an application, starting at `cursor`.
+        /// The returned cursor resumes the listing and is `None` once the end is reached
+        #[ink(message, payable)]
+        pub fn get_restriction_list_paged(
+            &self,
+            app_did: DID,
+            cursor: u32,
+            limit: u32,
+        ) -> (Vec<DID>, Option<u32>) {
+            let users = self.restricted.get(&app_did).unwrap_or_default();
+            let page = users
+                .iter()
+                .skip(cursor as usize)
+                .take(limit as usize)
+                .cloned()
+                .collect::<Vec<_>>();
+            let next = cursor.saturating_add(page.len() as u32);
+            (page, (next < users.len() as u32).then_some(next))
+        }
+
+        /// Retrieves the number of users that have restricted an application
+        #[ink(message, payable)]
+        pub fn restriction_count(&self, app_did: DID) -> u32 {
+            self.restricted.get(&app_did).unwrap_or_default().len() as u32
+        }
+
+        /// Retrieves the index of the current epoch
+        #[ink(message, payable)]
+        pub fn current_epoch(&self) -> u32 {
+            self.epoch_info().index
+        }
+
+        /// Retrieves the index, boundaries and length of the current epoch
+        #[ink(message, payable)]
+        pub fn epoch_info(&self) -> EpochInfo {
+            let elapsed = self.env().block_number().saturating_sub(self.genesis_block);
+            let index = elapsed / self.epoch_length;
+            let start_block = self.genesis_block + index * self.epoch_length;
+
+            EpochInfo {
+                index,
+                start_block,
+                remaining_blocks: self.epoch_length - (elapsed % self.epoch_length),
+                epoch_length: self.epoch_length,
+            }
+        }
+
+        /// Returns the activity counters of the current epoch. The first activity of a new
+        /// epoch closes the previous one and emits its `EpochReport`
+        fn track_activity(&mut self) -> &mut EpochActivity {
+            let epoch = self.current_epoch();
+            if self.epoch_activity.epoch != epoch {
+                let closed = core::mem::replace(
+                    &mut self.epoch_activity,
+                    EpochActivity {
+                        epoch,
+                        ..Default::default()
+                    },
+                );
+
+                // emit event
+                self.env().emit_event(EpochReport {
+                    epoch: closed.epoch,
+                    accounts_created: closed.accounts_created,
+                    cid_updates: closed.cid_updates,
+                    subscriptions: closed.subscriptions,
+                    unsubscriptions: closed.unsubscriptions,
+                    fees_collected: closed.fees_collected,
+                });
+            }
+            &mut self.epoch_activity
+        }
+
+        /// Retrieves the activity counted so far in the current epoch
+        #[ink(message, payable)]
+        pub fn epoch_activity(&self) -> EpochActivity {
+            let epoch = self.current_epoch();
+            if self.epoch_activity.epoch == epoch {
+                self.epoch_activity.clone()
+            } else {
+                EpochActivity {
+                    epoch,
+                    ..Default::default()
+                }
+            }
+        }
+
+        /// Retrieves the balance an account can claim through `withdraw_balance`
+        #[ink(message, payable)]
+        pub fn claimable_balance(&self, account: AccountId) -> Balance {
+            self.claimable.get(account).unwrap_or_default()
+        }
+
+        /// Transfers all funds owed to the caller and returns the amount paid out.
+        /// Every payout made by the contract is credited here rather than pushed
+        #[ink(message, payable)]
+        pub fn withdraw_balance(&mut self) -> Result<Balance> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let caller = self.env().caller();
+            let amount = self.claimable.get(caller).unwrap_or_default();
+
+            if amount > 0 {
+                // clear the ledger entry before transferring
+                self.claimable.remove(caller);
+                self.owed = self.owed.saturating_sub(amount);
+                self.env()
+                    .transfer(caller, amount)
+                    .map_err(|_| Error::TransferFailed)?;
+
+                // emit event
+                self.env().emit_event(BalanceWithdrawn {
+                    account: caller,
+                    amount,
+                });
+            }
+
+            Ok(amount)
+        }
+
+        /// Keeps `fee` out of the value sent with the call, burning the configured share of
+        /// it, and credits the rest back to the caller, recording the payment. Calls sent
+        /// without value get no receipt
+        fn charge_fee(&mut self, operation: PaymentOperation, fee: Balance) {
+            let value = self.env().transferred_value();
+            if value == 0 {
+                return;
+            }
+            let payer = self.env().caller();
+            let fee = fee.min(value);
+            let refund = value - fee;
+            if refund > 0 {
+                self.credit(payer, refund);
+            }
+            let paid = self.paid_totals.get(payer).unwrap_or_default();
+            self.paid_totals.insert(payer, &paid.saturating_add(fee));
+            let activity = self.track_activity();
+            activity.fees_collected = activity.fees_collected.saturating_add(fee);
+            let burned = fee.saturating_mul(self.config.fee_burn_percent.into()) / 100;
+            if burned > 0 {
+                self.burned = self.burned.saturating_add(burned);
+
+                // emit event
+                self.env().emit_event(FeesBurned {
+                    amount: burned,
+                    total: self.burned,
+                });
+            }
+
+            // emit event
+            self.env().emit_event(PaymentReceipt {
+                payer,
+                operation,
+                fee,
+                refund,
+            });
+        }
+
+        /// Retrieves the fees burned over the contract's lifetime
+        #[ink(message, payable)]
+        pub fn burned_total(&self) -> Balance {
+            self.burned
+        }
+
+        /// Retrieves the fees an account has paid over the contract's lifetime
+        #[ink(message, payable)]
+        pub fn paid_total(&self, account: AccountId) -> Balance {
+            self.paid_totals.get(account).unwrap_or_default()
+        }
+
+        /// Credits funds to an account's claimable balance
+        fn credit(&mut self, account: AccountId, amount: Balance) {
+            let balance = self.claimable.get(account).unwrap_or_default();
+            self.claimable
+                .insert(account, &balance.saturating_add(amount));
+            self.owed = self.owed.saturating_add(amount);
+        }
+
+        /// Retrieves the funds received through payable messages that aren't owed to accounts,
+        /// locked as bootnode stakes or burned
+        #[ink(message, payable)]
+        pub fn treasury(&self) -> Balance {
+            self.env()
+                .balance()
+                .saturating_sub(self.owed)
+                .saturating_sub(self.staked)
+                .saturating_sub(self.burned)
+        }
+
+        /// Transfers treasury funds to an account. Claimable balances can't be touched. Owner only
+        #[ink(message, payable)]
+        pub fn withdraw(&mut self, amount: Balance, to: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            if amount > self.treasury() {
+                return Err(Error::InsufficientFunds);
+            }
+            self.env()
+                .transfer(to, amount)
+                .map_err(|_| Error::TransferFailed)?;
+
+            // emit event
+            self.env().emit_event(TreasuryWithdrawn { to, amount });
+            Ok(())
+        }
+
+        /// Removes the contract and sweeps its remaining funds to the owner. Only allowed
+        /// while globally paused and once every claimable balance and bootnode stake has been
+        /// paid out. Owner only
+        #[ink(message, payable)]
+        pub fn terminate(&mut self) -> Result<()> {
+            self.ensure_owner()?;
+            if !self.paused {
+                return Err(Error::NotPaused);
+            }
+            if self.owed > 0 || self.staked > 0 {
+                return Err(Error::ClaimsOutstanding);
+            }
+            self.env().terminate_contract(self.owner)
+        }
+
+        /// Retrieves the account operating this deployment
+        #[ink(message, payable)]
+        pub fn owner(&self) -> AccountId {
+            self.owner
+        }
+
+        /// Retrieves the network this deployment serves
+        #[ink(message, payable)]
+        pub fn network_id(&self) -> NetworkId {
+            self.network_id
+        }
+
+        /// Retrieves the current wrapped data-key version for a (user, application) pair
+        #[ink(message, payable)]
+        pub fn key_version(&self, user_did: DID, app_did: DID) -> u32 {
+            self.key_versions
+                .get((user_did, app_did))
+                .unwrap_or_default()
+        }
+
+        /// Invalidates the wrapped data key of a (user, application) pair
+        fn bump_key_version(&mut self, user_did: &DID, app_did: &DID) {
+            let key = (user_did.clone(), app_did.clone());
+            let version = self.key_versions.get(&key).unwrap_or_default();
+            if !self.key_versions.contains(&key) {
+                for (did, peer) in [(user_did, app_did), (app_did, user_did)] {
+                    let mut peers = self.key_version_peers.get(did).unwrap_or_default();
+                    peers.push(peer.clone());
+                    self.key_version_peers.insert(did, &peers);
+                }
+            }
+            self.key_versions.insert(&key, &version.wrapping_add(1));
+        }
+
+        /// Checks that the caller is the account operating this deployment
+        fn ensure_owner(&self) -> Result<()> {
+            if self.env().caller() != self.owner {
+                return Err(Error::Unauthorized);
+            }
+            Ok(())
+        }
+
+        /// Checks that the caller holds a role. The owner holds every role
+        fn ensure_role(&self, role: Role) -> Result<()> {
+            if !self.has_role(self.env().caller(), role) {
+                return Err(Error::Unauthorized);
+            }
+            Ok(())
+        }
+
+        /// Retrieves an account after checking that the caller is its controller, recording the
+        /// action. Compromised accounts are frozen until recovered, so every change made by a
+        /// controller fails
+        fn authorize(&mut self, did: &DID) -> Result<AccountInfo> {
+            let account = self.check_authorized(did)?;
+            self.record_account_activity(did);
+            Ok(account)
+        }
+
+        /// Runs the checks of `authorize` without recording any activity
+        fn check_authorized(&self, did: &DID) -> Result<AccountInfo> {
+            let account = self.ensure_controller(did)?;
+            if self.compromised.contains(did) {
+                return Err(Error::AccountCompromised);
+            }
+            Ok(account)
+        }
+
+        /// Records a controller action on an account, which keeps it from being reaped
+        fn record_account_activity(&mut self, did: &DID) {
+            self.last_active.insert(did, &self.current_epoch());
+            self.inactive_since.remove(did);
+        }
+
+        /// Retrieves an account after checking that the caller is its controller, whether or not
+        /// the account is declared compromised
+        fn ensure_controller(&self, did: &DID) -> Result<AccountInfo> {
+            let account = self.accounts.get(did).ok_or(Error::AccountNotFound)?;
+            if !account.is_controller(&self.env().caller()) {
+                return Err(Error::Unauthorized);
+            }
+            Ok(account)
+        }
+
+        /// Declares the keys of an account compromised, freezing it until recovery.
+        /// Nodes must stop accepting writes signed for the account once this is emitted
+        #[ink(message, payable)]
+        pub fn declare_compromise(&mut self, did: DID, evidence: Vec<u8>) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            self.authorize(&did)?;
+            self.mark_compromised(did, evidence)
+        }
+
+        /// Reports the keys of an account compromised on its behalf. The caller must control
+        /// `guardian_did`, one of the account's guardians. The account is frozen once as many
+        /// guardians as the recovery threshold have reported it
+        #[ink(message, payable)]
+        pub fn declare_compromise_as_guardian(
+            &mut self,
+            did: DID,
+            guardian_did: DID,
+            evidence: Vec<u8>,
+        ) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            self.ensure_guardian(&did, &guardian_did)?;
+            let threshold = self
+                .guardians
+                .get(&did)
+                .map_or(u32::MAX, |set| set.threshold);
+            let mut reports = self.compromise_reports.get(&did).unwrap_or_default();
+            if reports.contains(&guardian_did) {
+                return Err(Error::AlreadyExists);
+            }
+            reports.push(guardian_did.clone());
+            self.compromise_reports.insert(&did, &reports);
+
+            // emit event
+            self.env().emit_event(CompromiseReported {
+                did: did.clone(),
+                guardian: guardian_did,
+                reports: reports.len() as u32,
+            });
+            if reports.len() as u32 >= threshold {
+                self.mark_compromised(did, evidence)?;
+            }
+            Ok(())
+        }
+
+        /// Lifts a compromise declaration, e.g. one made in error on an account without
+        /// guardians to recover it. Admins only; the council clears through a proposal
+        #[ink(message, payable)]
+        pub fn clear_compromise(&mut self, did: DID) -> Result<()> {
+            self.ensure_role(Role::Admin)?;
+            self.ensure_no_council()?;
+            self.unmark_compromised(did)
+        }
+
+        /// Freezes an account until it is recovered
+        fn mark_compromised(&mut self, did: DID, evidence: Vec<u8>) -> Result<()> {
+            if !self.accounts.contains(&did) {
+                return Err(Error::AccountNotFound);
+            }
+            let block = self.env().block_number();
+            self.compromised.insert(&did, &block);
+            self.compromise_reports.remove(&did);
+
+            // emit event
+            self.env().emit_event(CompromiseDeclared {
+                did,
+                evidence,
+                block,
+            });
+            Ok(())
+        }
+
+        /// Unfreezes an account declared compromised
+        fn unmark_compromised(&mut self, did: DID) -> Result<()> {
+            if self.compromised.take(&did).is_none() {
+                return Err(Error::NotCompromised);
+            }
+
+            // emit event
+            self.env().emit_event(CompromiseCleared { did });
+            Ok(())
+        }
+
+        /// Checks if the keys of an account have been declared compromised
+        #[ink(message, payable)]
+        pub fn is_compromised(&self, did: DID) -> bool {
+            self.compromised.contains(&did)
+        }
+
+        /// Mints a token granting an application `scope` access to a user's data for `ttl` blocks.
+        /// Only the application's controller can mint, and both accounts must be active; the
+        /// token id and commitment are returned
+        #[ink(message, payable)]
+        pub fn mint_access_token(
+            &mut self,
+            app_did: DID,
+            user_did: DID,
+            scope: u32,
+            ttl: BlockNumber,
+        ) -> Result<(TokenId, Hash)> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let app = self.authorize(&app_did)?;
+            let user = self.accounts.get(&user_did).ok_or(Error::AccountNotFound)?;
+            if !user.is_active() || !app.is_active() {
+                return Err(Error::AccountDeactivated);
+            }
+
+            let token_id = self.next_token_id;
+            self.next_token_id = token_id.checked_add(1).ok_or(Error::CapacityExceeded)?;
+            let expires_at = self.env().block_number().saturating_add(ttl);
+            let issuer = self.env().caller();
+
+            // bind the commitment to every field of the token
+            let mut commitment = [0u8; 32];
+            ink::env::hash_encoded::<Blake2x256, _>(
+                &(token_id, &app_did, &user_did, scope, expires_at, issuer),
+                &mut commitment,
+            );
+            let commitment = Hash::from(commitment);
+
+            self.access_tokens.insert(
+                token_id,
+                &AccessToken {
+                    app_did: app_did.clone(),
+                    user_did: user_did.clone(),
+                    scope,
+                    expires_at,
+                    issuer,
+                    commitment,
+                    revoked: false,
+                },
+            );
+            let mut tokens = self.account_tokens.get(&app_did).unwrap_or_default();
+            tokens.push(token_id);
+            self.account_tokens.insert(&app_did, &tokens);
+            if user_did != app_did {
+                let mut tokens = self.account_tokens.get(&user_did).unwrap_or_default();
+                tokens.push(token_id);
+                self.account_tokens.insert(&user_did, &tokens);
+            }
+
+            // emit event
+            self.env().emit_event(AccessTokenMinted {
+                token_id,
+                app_did,
+                user_did,
+                commitment,
+                expires_at,
+            });
+
+            Ok((token_id, commitment))
+        }
+
+        /// Revokes an access token. Only the account that minted it may revoke it
+        #[ink(message, payable)]
+        pub fn revoke_token(&mut self, token_id: TokenId) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let mut token = self
+                .access_tokens
+                .get(token_id)
+                .ok_or(Error::TokenNotFound)?;
+            if token.issuer != self.env().caller() {
+                return Err(Error::Unauthorized);
+            }
+
+            token.revoked = true;
+            self.access_tokens.insert(token_id, &token);
+
+            // emit event
+            self.env().emit_event(AccessTokenRevoked { token_id });
+            Ok(())
+        }
+
+        /// Checks that a token is unexpired, unrevoked, covers `scope`, that the application
+        /// it was minted for is still registered and active, and that the user has not since
+        /// restricted the application
+        #[ink(message, payable)]
+        pub fn verify_token(&self, token_id: TokenId, scope: u32) -> bool {
+            if let Some(token) = self.access_tokens.get(token_id) {
+                !token.revoked
+                    && self.env().block_number() <= token.expires_at
+                    && token.scope & scope == scope
+                    && self
+                        .accounts
+                        .get(&token.app_did)
+                        .is_some_and(|app| app.is_active())
+                    && !self.is_restricted(token.user_did, token.app_did)
+            } else {
+                false
+            }
+        }
+
+        /// Retrieves an access token and its commitment
+        #[ink(message, payable)]
+        pub fn get_access_token(&self, token_id: TokenId) -> Option<AccessToken> {
+            self.access_tokens.get(token_id)
+        }
+
+        /// Retrieves up to `limit` DIDs registered under a method segment such as `did:sam:apps`,
+        /// starting at `offset`. DIDs keep their registration order until one is deleted
+        #[ink(message, payable)]
+        pub fn get_dids_by_prefix(&self, prefix: Vec<u8>, offset: u32, limit: u32) -> Vec<DID> {
+            let count = self.prefix_counts.get(&prefix).unwrap_or(0);
+            let end = offset.saturating_add(limit).min(count);
+            (offset..end)
+                .filter_map(|position| self.dids_by_prefix.get((&prefix, position)))
+                .collect()
+        }
+
+        /// Removes a deleted DID from its method segment, moving the segment's last DID into
+        /// the freed position
+        fn unindex_prefix(&mut self, did: &DID) {
+            let Some(position) = self.prefix_positions.take(did) else {
+                return;
+            };
+            let prefix = did_prefix(did).to_vec();
+            let last = self
+                .prefix_counts
+                .get(&prefix)
+                .unwrap_or(1)
+                .saturating_sub(1);
+            if position != last {
+                if let Some(moved) = self.dids_by_prefix.get((&prefix, last)) {
+                    self.dids_by_prefix.insert((&prefix, position), &moved);
+                    self.prefix_positions.insert(&moved, &position);
+                }
+            }
+            self.dids_by_prefix.remove((&prefix, last));
+            self.prefix_counts.insert(&prefix, &last);
+        }
+
+        /// Adds a DID to the enumerable index the first time it appears in the registry
+        fn index_did(&mut self, did: &DID) {
+            if !self.indexed_dids.contains(did) {
+                self.indexed_dids.insert(did, &());
+                self.did_index.insert(self.did_count, did);
+                self.did_count += 1;
+            }
+        }
+
+        /// Retrieves up to `limit` DIDs from the enumerable index, starting at `offset`
+        fn indexed_dids(&self, offset: u32, limit: u32) -> impl Iterator<Item = DID> + '_ {
+            let end = offset.saturating_add(limit).min(self.did_count);
+            (offset..end).filter_map(|index| self.did_index.get(index))
+        }
+
+        /// Exports a chunk of accounts as a SCALE-encoded `Vec<(DID, AccountInfo)>`.
+        /// `offset` and `limit` page through the DID index, so chunks may be smaller than `limit`
+        #[ink(message, payable)]
+        pub fn export_accounts(&self, offset: u32, limit: u32) -> Vec<u8> {
+            let chunk = self
+                .indexed_dids(offset, limit)
+                .filter_map(|did| self.accounts.get(&did).map(|account| (did, account)))
+                .collect::<Vec<_>>();
+            scale::Encode::encode(&chunk)
+        }
+
+        /// Exports a chunk of subscriptions as a SCALE-encoded
+        /// `Vec<(DID, Vec<ImportedSubscriber>)>`, the input of `import_subscribers`. Each node
+        /// carries the account acting for it and the block it joined at
+        #[ink(message, payable)]
+        pub fn export_subscriptions(&self, offset: u32, limit: u32) -> Vec<u8> {
+            let chunk = self
+                .indexed_dids(offset, limit)
+                .filter_map(|did| {
+                    let nodes = self.subscribers.get(&did)?;
+                    let entries = nodes
+                        .into_iter()
+                        .filter_map(|node| {
+                            let key = (&did, &node);
+                            Some(ImportedSubscriber {
+                                owner: self.subscription_owners.get(key)?,
+                                subscribed_at: self.subscribed_at.get(key).unwrap_or_default(),
+                                node,
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    Some((did, entries))
+                })
+                .filter(|(_, entries)| !entries.is_empty())
+                .collect::<Vec<_>>();
+            scale::Encode::encode(&chunk)
+        }
+
+        /// Exports a chunk of restriction lists as a SCALE-encoded `Vec<(DID, Vec<DID>)>`,
+        /// keyed by application DID
+        #[ink(message, payable)]
+        pub fn export_restrictions(&self, offset: u32, limit: u32) -> Vec<u8> {
+            let chunk = self
+                .indexed_dids(offset, limit)
+                .filter_map(|did| self.restricted.get(&did).map(|users| (did, users)))
+                .filter(|(_, users)| !users.is_empty())
+                .collect::<Vec<_>>();
+            scale::Encode::encode(&chunk)
+        }
+
+        /// Retrieves the number of DIDs known to the contract, for paging through exports
+        #[ink(message, payable)]
+        pub fn did_count(&self) -> u32 {
+            self.did_count
+        }
+
+        /// Mirrors a DID into runtime storage. Failures are reported through an event
+        /// rather than reverting, so registration keeps working if the runtime can't anchor
+        #[cfg(feature = "did-anchor")]
+        fn anchor_did(&self, did: &DID) {
+            if self.did_anchoring && self.env().extension().anchor_did(did.clone()).is_err() {
+                self.env().emit_event(DidAnchorFailed { did: did.clone() });
+            }
+        }
+
+        /// Removes a DID from runtime storage, reporting failures like `anchor_did`
+        #[cfg(feature = "did-anchor")]
+        fn unanchor_did(&self, did: &DID) {
+            if self.did_anchoring && self.env().extension().remove_did(did.clone()).is_err() {
+                self.env().emit_event(DidAnchorFailed { did: did.clone() });
+            }
+        }
+
+        /// Records that a node subscribed to an application has applied the latest
+        /// restriction change for a user
+        #[ink(message, payable)]
+        pub fn ack_restriction(
+            &mut self,
+            app_did: DID,
+            user_did: DID,
+            node: Multiaddr,
+        ) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Restrictions)?;
+            let subscribers = self.subscribers.get(&app_did).unwrap_or_default();
+            if !subscribers.contains(&node) {
+                return Err(Error::NotSubscribed);
+            }
+            self.ensure_subscriber(&app_did, &node)?;
+
+            let mut acks = self
+                .restriction_acks
+                .get((&user_did, &app_did))
+                .unwrap_or_default();
+            if !acks.contains(&node) {
+                acks.push(node.clone());
+                self.restriction_acks.insert((&user_did, &app_did), &acks);
+
+                // emit event
+                self.env().emit_event(RestrictionAcknowledged {
+                    application_did: app_did,
+                    user_did,
+                    node,
+                });
+            }
+            Ok(())
+        }
+
+        /// Retrieves how many of an application's subscribed nodes have applied the latest
+        /// restriction change for a user
+        #[ink(message, payable)]
+        pub fn restriction_ack_status(&self, app_did: DID, user_did: DID) -> AckStatus {
+            let subscribers = self.subscribers.get(&app_did).unwrap_or_default();
+            let acks = self
+                .restriction_acks
+                .get((&user_did, &app_did))
+                .unwrap_or_default();
+
+            AckStatus {
+                key_version: self.key_version(user_did, app_did),
+                // nodes that left since acknowledging no longer count
+                acknowledged: acks
+                    .iter()
+                    .filter(|node| subscribers.contains(node))
+                    .count() as u32,
+                replicas: subscribers.len() as u32,
+            }
+        }
+
+        /// Retrieves the live bootnodes of this deployment's network, the parameters, fees and
+        /// epoch information a joining node needs, and the oldest client version it supports
+        #[ink(message, payable)]
+        pub fn bootstrap_bundle(&self) -> BootstrapBundle {
+            BootstrapBundle {
+                network_id: self.network_id,
+                bootnodes: self
+                    .live_bootnodes(self.network_id)
+                    .map(|record| record.multiaddr)
+                    .collect(),
+                max_bootnodes: self.config.max_bootnodes,
+                epoch: self.epoch_info(),
+                registration_fee: self.config.registration_fee,
+                node_stake: self.config.node_stake,
+                min_client_version: self.version,
+            }
+        }
+
+        /// Appends a change to an application's subscriber journal, dropping the oldest
+        /// entry once the journal is full
+        fn record_subscriber_change(&mut self, did: &DID, node: &Multiaddr, joined: bool) {
+            let mut journal = self.subscriber_journals.get(did).unwrap_or_default();
+            if journal.changes.len() >= MAX_JOURNAL_ENTRIES {
+                let dropped = journal.changes.remove(0);
+                journal.truncated_at = Some(dropped.block);
+            }
+            journal.changes.push(SubscriberChange {
+                block: self.env().block_number(),
+                node: node.clone(),
+                joined,
+            });
+            self.subscriber_journals.insert(did, &journal);
+        }
+
+        /// Retrieves the changes to an application's subscribers made after `block`.
+        /// The flag is `false` when older changes were dropped from the journal, in which
+        /// case the caller must resync the full list with `get_subscribers`
+        #[ink(message, payable)]
+        pub fn subscriber_changes_since(
+            &self,
+            did: DID,
+            block: BlockNumber,
+        ) -> (Vec<SubscriberChange>, bool) {
+            let journal = self.subscriber_journals.get(&did).unwrap_or_default();
+            let complete = journal
+                .truncated_at
+                .is_none_or(|truncated_at| truncated_at <= block);
+            let changes = journal
+                .changes
+                .into_iter()
+                .filter(|change| change.block > block)
+                .collect();
+            (changes, complete)
+        }
+
+        /// Appends a change to the restriction journal, evicting the oldest retained entry
+        fn record_restriction_change(&mut self, user_did: &DID, app_did: &DID, restricted: bool) {
+            self.restriction_seq += 1;
+            self.restriction_log.insert(
+                self.restriction_seq,
+                &RestrictionChange {
+                    seq: self.restriction_seq,
+                    block: self.env().block_number(),
+                    app_did: app_did.clone(),
+                    user_did: user_did.clone(),
+                    restricted,
+                },
+            );
+            if self.restriction_seq > MAX_RESTRICTION_LOG {
+                self.restriction_log
+                    .remove(self.restriction_seq - MAX_RESTRICTION_LOG);
+            }
+        }
+
+        /// Retrieves up to `limit` restriction changes with a sequence number above `seq`.
+        /// The flag is `false` when changes after `seq` were already evicted from the journal,
+        /// in which case the caller must resync full restriction lists
+        #[ink(message, payable)]
+        pub fn restriction_changes_since(
+            &self,
+            seq: u64,
+            limit: u32,
+        ) -> (Vec<RestrictionChange>, bool) {
+            let oldest = self
+                .restriction_seq
+                .saturating_sub(MAX_RESTRICTION_LOG - 1)
+                .max(1);
+            let complete = seq.saturating_add(1) >= oldest;
+            let start = seq.saturating_add(1).max(oldest);
+            let end = self
+                .restriction_seq
+                .min(start.saturating_add(limit as u64).saturating_sub(1));
+            let changes = (start..=end)
+                .filter_map(|seq| self.restriction_log.get(seq))
+                .collect();
+            (changes, complete)
+        }
+
+        /// Registers a public key of the given type to control an account and returns its index.
+        /// Once the account holds the maximum, the key takes the lowest slot holding a revoked
+        /// credential. Only the account's controller can add one
+        #[ink(message, payable)]
+        pub fn add_credential(
+            &mut self,
+            did: DID,
+            credential_type: CredentialType,
+            public_key: Vec<u8>,
+        ) -> Result<u32> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            self.authorize(&did)?;
+
+            let mut credentials = self.credentials.get(&did).unwrap_or_default();
+            if public_key.len() != credential_type.key_len() {
+                return Err(Error::InvalidCredential);
+            }
+
+            let credential = Credential {
+                credential_type,
+                public_key: public_key.clone(),
+                added_at: self.env().block_number(),
+                revoked: false,
+            };
+            let index = if credentials.len() < MAX_CREDENTIALS {
+                credentials.push(credential);
+                credentials.len() - 1
+            } else {
+                // only revoked credentials can make room
+                let index = credentials
+                    .iter()
+                    .position(|credential| credential.revoked)
+                    .ok_or(Error::CapacityExceeded)?;
+                credentials[index] = credential;
+                index
+            } as u32;
+            self.credentials.insert(&did, &credentials);
+
+            // emit event
+            self.env().emit_event(CredentialAdded {
+                did,
+                index,
+                credential_type,
+                public_key,
+            });
+
+            Ok(index)
+        }
+
+        /// Revokes one of an account's credentials, leaving the others usable
+        #[ink(message, payable)]
+        pub fn revoke_credential(&mut self, did: DID, index: u32) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            self.authorize(&did)?;
+
+            let mut credentials = self.credentials.get(&did).unwrap_or_default();
+            let credential = credentials
+                .get_mut(index as usize)
+                .ok_or(Error::CredentialNotFound)?;
+            credential.revoked = true;
+            self.credentials.insert(&did, &credentials);
+
+            // emit event
+            self.env().emit_event(CredentialRevoked { did, index });
+            Ok(())
+        }
+
+        /// Retrieves the credentials registered for an account, including revoked ones
+        #[ink(message, payable)]
+        pub fn get_credentials(&self, did: DID) -> Vec<Credential> {
+            self.credentials.get(&did).unwrap_or_default()
+        }
+
+        /// Checks a signature over `message_hash` against one of an account's unrevoked
+        /// credentials, according to its declared type. Only ECDSA can be verified on-chain
+        /// at the moment; other types are reported as invalid
+        #[ink(message, payable)]
+        pub fn verify_credential_signature(
+            &self,
+            did: DID,
+            index: u32,
+            message_hash: [u8; 32],
+            signature: Vec<u8>,
+        ) -> bool {
+            let credential = match self
+                .credentials
+                .get(&did)
+                .and_then(|credentials| credentials.get(index as usize).cloned())
+            {
+                Some(credential) if !credential.revoked => credential,
+                _ => return false,
+            };
+
+            match credential.credential_type {
+                CredentialType::Ecdsa => {
+                    let Ok(signature) = <[u8; 65]>::try_from(signature.as_slice()) else {
+                        return false;
+                    };
+                    self.env()
+                        .ecdsa_recover(&signature, &message_hash)
+                        .is_ok_and(|key| key[..] == credential.public_key[..])
+                }
+                CredentialType::Sr25519 | CredentialType::Ed25519 | CredentialType::WebAuthn => {
+                    false
+                }
+            }
+        }
+
+        /// Makes nodes wait for approval before joining an application. Requests expire after
+        /// `request_ttl` blocks; passing `None` lets nodes join freely again
+        #[ink(message, payable)]
+        pub fn set_subscription_approval(
+            &mut self,
+            app_did: DID,
+            request_ttl: Option<BlockNumber>,
+        ) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Subscriptions)?;
+            self.authorize(&app_did)?;
+            if let Some(ttl) = request_ttl {
+                self.subscription_approval.insert(&app_did, &ttl);
+            } else {
+                self.subscription_approval.remove(&app_did);
+                self.pending_subscriptions.remove(&app_did);
+            }
+
+            // emit event
+            self.env().emit_event(SubscriptionApprovalUpdated {
+                did: app_did,
+                request_ttl,
+            });
+            Ok(())
+        }
+
+        /// Approves a node's subscription request, subscribing it to the application
+        #[ink(message, payable)]
+        pub fn approve_subscriber(&mut self, app_did: DID, addr: Multiaddr) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Subscriptions)?;
+            self.authorize(&app_did)?;
+            let request = self.take_subscription_request(&app_did, &addr)?;
+            self.check_subscription_allowance(&app_did, &request.requester)?;
+            // the node may have moved since it asked to join
+            self.check_placement(&app_did, &addr)?;
+            self.add_subscriber(app_did, addr, request.requester);
+            Ok(())
+        }
+
+        /// Rejects a node's subscription request
+        #[ink(message, payable)]
+        pub fn reject_subscriber(&mut self, app_did: DID, addr: Multiaddr) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Subscriptions)?;
+            self.authorize(&app_did)?;
+            self.take_subscription_request(&app_did, &addr)?;
+
+            // emit event
+            self.env().emit_event(SubscriptionRejected {
+                did: app_did,
+                node: addr,
+            });
+            Ok(())
+        }
+
+        /// Bans a node from supporting an application, evicting it if it is subscribed and
+        /// dropping any request it has pending. Applies even if the network still lists the node
+        #[ink(message, payable)]
+        pub fn ban_subscriber(&mut self, app_did: DID, addr: Multiaddr) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Subscriptions)?;
+            self.authorize(&app_did)?;
+            let mut banned = self.banned_subscribers.get(&app_did).unwrap_or_default();
+            if banned.contains(&addr) {
+                return Err(Error::AlreadyBanned);
+            }
+            if banned.len() >= MAX_BANNED_SUBSCRIBERS {
+                return Err(Error::CapacityExceeded);
+            }
+            banned.push(addr.clone());
+            self.banned_subscribers.insert(&app_did, &banned);
+
+            let mut pending = self.pending_subscriptions.get(&app_did).unwrap_or_default();
+            pending.retain(|request| request.node != addr);
+            self.pending_subscriptions.insert(&app_did, &pending);
+            let nodes = self.subscribers.get(&app_did).unwrap_or_default();
+            if nodes.contains(&addr) {
+                self.remove_subscriber(app_did.clone(), addr.clone(), nodes);
+            }
+
+            // emit event
+            self.env().emit_event(SubscriberBanned {
+                did: app_did,
+                node: addr,
+            });
+            Ok(())
+        }
+
+        /// Lifts an application's ban on a node. The node has to subscribe again
+        #[ink(message, payable)]
+        pub fn unban_subscriber(&mut self, app_did: DID, addr: Multiaddr) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Subscriptions)?;
+            self.authorize(&app_did)?;
+            let mut banned = self.banned_subscribers.get(&app_did).unwrap_or_default();
+            if !banned.contains(&addr) {
+                return Err(Error::NotBanned);
+            }
+            banned.retain(|node| *node != addr);
+            self.banned_subscribers.insert(&app_did, &banned);
+
+            // emit event
+            self.env().emit_event(SubscriberUnbanned {
+                did: app_did,
+                node: addr,
+            });
+            Ok(())
+        }
+
+        /// Retrieves the nodes an application has banned
+        #[ink(message, payable)]
+        pub fn get_banned_subscribers(&self, app_did: DID) -> Vec<Multiaddr> {
+            self.banned_subscribers.get(&app_did).unwrap_or_default()
+        }
+
+        /// Retrieves the unexpired subscription requests awaiting an application's approval
+        #[ink(message, payable)]
+        pub fn get_pending_subscribers(&self, app_did: DID) -> Vec<PendingSubscription> {
+            let now = self.env().block_number();
+            self.pending_subscriptions
+                .get(&app_did)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|request| request.expires_at >= now)
+                .collect()
+        }
+
+        /// Sets where an application's subscribers may run: the regions they must be listed
+        /// in, how many distinct regions they must span and how many of them the application
+        /// accepts. Regions are checked on joins only, but a cap below the current subscriber
+        /// count trims the application at once through `trim_subscribers`. An empty policy
+        /// lifts every restriction
+        #[ink(message, payable)]
+        pub fn set_replication_policy(
+            &mut self,
+            app_did: DID,
+            policy: ReplicationPolicy,
+        ) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Subscriptions)?;
+            self.authorize(&app_did)?;
+            if policy.allowed_regions.len() > MAX_ALLOWED_REGIONS
+                || policy
+                    .allowed_regions
+                    .iter()
+                    .any(|region| region.len() > MAX_REGION_LEN)
+            {
+                return Err(Error::CapacityExceeded);
+            }
+            // the required spread must be reachable within the allowed regions and the cap.
+            // Diversity is enforced by refusing joins near the cap, so it needs one
+            let allowed = policy.allowed_regions.len() as u32;
+            if (allowed > 0 && policy.min_regions > allowed)
+                || policy.min_regions > policy.max_replicas
+            {
+                return Err(Error::InvalidConfig);
+            }
+            if policy == ReplicationPolicy::default() {
+                self.replication_policies.remove(&app_did);
+            } else {
+                self.replication_policies.insert(&app_did, &policy);
+            }
+            if policy.max_replicas > 0 {
+                self.trim_subscribers(&app_did, policy.max_replicas);
+            }
+
+            // emit event
+            self.env().emit_event(ReplicationPolicyUpdated {
+                did: app_did,
+                policy,
+            });
+            Ok(())
+        }
+
+        /// Unsubscribes nodes until an application has at most `cap` subscribers. Unhealthy
+        /// nodes go first, then the most recently joined, so the longest-tenured healthy
+        /// nodes keep serving
+        fn trim_subscribers(&mut self, did: &DID, cap: u32) {
+            let mut nodes = self.subscribers.get(did).unwrap_or_default();
+            let excess = nodes.len().saturating_sub(cap as usize);
+            if excess == 0 {
+                return;
+            }
+            nodes.sort_by_key(|addr| {
+                let joined = self.subscribed_at.get((did, addr)).unwrap_or_default();
+                (self.is_healthy(addr), core::cmp::Reverse(joined))
+            });
+            for addr in nodes.into_iter().take(excess) {
+                let remaining = self.subscribers.get(did).unwrap_or_default();
+                self.remove_subscriber(did.clone(), addr, remaining);
+            }
+        }
+
+        /// Checks that a subscribed node isn't blacklisted and, if it is a listed bootnode,
+        /// has sent a heartbeat within the TTL
+        fn is_healthy(&self, addr: &Multiaddr) -> bool {
+            !self.blacklisted.contains(addr)
+                && self
+                    .node_record(addr)
+                    .is_none_or(|record| self.is_live(&record))
+        }
+
+        /// Retrieves where an application's subscribers may run
+        #[ink(message, payable)]
+        pub fn get_replication_policy(&self, app_did: DID) -> ReplicationPolicy {
+            self.replication_policies.get(&app_did).unwrap_or_default()
+        }
+
+        /// Removes a node's unexpired subscription request, pruning expired ones on the way
+        fn take_subscription_request(
+            &mut self,
+            app_did: &DID,
+            addr: &Multiaddr,
+        ) -> Result<PendingSubscription> {
+            let now = self.env().block_number();
+            let mut pending = self.pending_subscriptions.get(app_did).unwrap_or_default();
+            pending.retain(|request| request.expires_at >= now);
+            let index = pending
+                .iter()
+                .position(|request| request.node == *addr)
+                .ok_or(Error::RequestNotFound)?;
+            let request = pending.remove(index);
+            self.pending_subscriptions.insert(app_did, &pending);
+            Ok(request)
+        }
+
+        /// Stops or resumes new account and bootnode registrations. Existing accounts and
+        /// every other operation keep working while registrations are frozen. Pausers only,
+        /// until a council is set
+        #[ink(message, payable)]
+        pub fn freeze_registrations(&mut self, frozen: bool) -> Result<()> {
+            self.ensure_role(Role::Pauser)?;
+            self.ensure_no_council()?;
+            self.set_registrations_frozen(frozen);
+            Ok(())
+        }
+
+        /// Sets whether new registrations are turned away
+        fn set_registrations_frozen(&mut self, frozen: bool) {
+            self.registrations_frozen = frozen;
+
+            // emit event
+            self.env().emit_event(RegistrationsFrozen { frozen });
+        }
+
+        /// Schedules a replacement of the network parameters and returns the timelock id.
+        /// Lowering the bootnode capacity evicts the oldest bootnodes on the next listing;
+        /// existing subscribers above a lowered cap are kept. Owner only, until a council is set
+        #[ink(message, payable)]
+        pub fn update_config(&mut self, config: Config) -> Result<u32> {
+            self.ensure_owner()?;
+            self.ensure_no_council()?;
+            Self::check_config(&config)?;
+            self.schedule(CouncilAction::UpdateConfig(config))
+        }
+
+        /// Retrieves the network parameters currently in force
+        #[ink(message, payable)]
+        pub fn get_config(&self) -> Config {
+            self.config.clone()
+        }
+
+        /// Replaces the network parameters after checking they are in range
+        fn set_config(&mut self, config: Config) -> Result<()> {
+            Self::check_config(&config)?;
+            self.config = config.clone();
+
+            // emit event
+            self.env().emit_event(ConfigUpdated { config });
+            Ok(())
+        }
+
+        /// Checks that the network parameters are in range
+        fn check_config(config: &Config) -> Result<()> {
+            if config.max_bootnodes == 0
+                || config.max_subscriptions_per_account == 0
+                || config.max_did_len == 0
+                || config.node_votes_required == 0
+                || config.node_voting_period == 0
+                || config.node_ttl == 0
+                || config.fee_burn_percent > 100
+            {
+                return Err(Error::InvalidConfig);
+            }
+            Ok(())
+        }
+
+        /// Checks if new registrations are currently frozen
+        #[ink(message, payable)]
+        pub fn registrations_frozen(&self) -> bool {
+            self.registrations_frozen
+        }
+
+        /// Retrieves registry-wide counters
+        #[ink(message, payable)]
+        pub fn network_stats(&self) -> NetworkStats {
+            NetworkStats {
+                accounts: self.account_count,
+                bootnodes: self.node_count,
+                dids: self.did_count,
+            }
+        }
+
+        /// Retrieves the subscriber and restriction counts of an application
+        #[ink(message, payable)]
+        pub fn did_stats(&self, did: DID) -> DidStats {
+            DidStats {
+                subscribers: self.subscribers.get(&did).unwrap_or_default().len() as u32,
+                restrictions: self.restriction_count(did),
+            }
+        }
+
+        /// Sets the X25519 key nodes use to open encrypted channels with an account.
+        /// The previous key is moved into the account's rotation history
+        #[ink(message, payable)]
+        pub fn set_key_agreement_key(&mut self, did: DID, key: [u8; 32]) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let mut account = self.authorize(&did)?;
+            if let Some(previous) = account.key_agreement {
+                let mut history = self.key_agreement_history.get(&did).unwrap_or_default();
+                if history.len() >= MAX_KEY_HISTORY {
+                    history.remove(0);
+                }
+                history.push(previous);
+                self.key_agreement_history.insert(&did, &history);
+            }
+            account.key_agreement = Some(KeyAgreementKey {
+                key,
+                set_at: self.env().block_number(),
+            });
+            self.accounts.insert(&did, &account);
+
+            // emit event
+            self.env().emit_event(KeyAgreementKeyRotated { did, key });
+            Ok(())
+        }
+
+        /// Retrieves the current key-agreement key of an account
+        #[ink(message, payable)]
+        pub fn get_key_agreement_key(&self, did: DID) -> Option<KeyAgreementKey> {
+            self.accounts
+                .get(&did)
+                .and_then(|account| account.key_agreement)
+        }
+
+        /// Retrieves the key-agreement keys an account has rotated away from, oldest first
+        #[ink(message, payable)]
+        pub fn get_key_agreement_history(&self, did: DID) -> Vec<KeyAgreementKey> {
+            self.key_agreement_history.get(&did).unwrap_or_default()
+        }
+
+        /// Suggests a bootnode for this network. The suggestion is listed once enough distinct
+        /// accounts endorse it or the owner approves it. Each account can suggest once per epoch.
+        /// The value sent must cover the bootnode stake and is locked until the node is unlisted
+        /// or the suggestion withdrawn
+        #[ink(message, payable)]
+        pub fn suggest_node(&mut self, addr: Multiaddr) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Nodes)?;
+            let now = self.env().block_number();
+            // a suggestion whose voting period ended can be made again
+            let previous = self.node_suggestions.get(&addr);
+            let open = previous
+                .as_ref()
+                .is_some_and(|suggestion| suggestion.expires_at >= now);
+            if self.node_slots.contains(&addr) || open {
+                return Err(Error::AlreadyExists);
+            }
+            if self.blacklisted.contains(&addr) {
+                return Err(Error::NodeBlacklisted);
+            }
+            validate_multiaddr(&addr).map_err(Error::InvalidMultiaddr)?;
+            let stake = self.check_stake()?;
+            let caller = self.env().caller();
+            let epoch = self.current_epoch();
+            if self.last_suggestion.get(caller) == Some(epoch) {
+                return Err(Error::RateLimited);
+            }
+            self.last_suggestion.insert(caller, &epoch);
+            if let Some(expired) = previous {
+                self.release_suggestion(&expired);
+            }
+            let expires_at = now.saturating_add(self.config.node_voting_period);
+            self.node_suggestions.insert(
+                &addr,
+                &NodeSuggestion {
+                    operator: caller,
+                    endorsers: Vec::new(),
+                    expires_at,
+                    stake,
+                },
+            );
+            self.staked = self.staked.saturating_add(stake);
+
+            // emit event
+            self.env().emit_event(NodeSuggested {
+                address: addr.clone(),
+                operator: caller,
+                expires_at,
+            });
+
+            // suggesters that already operate a node vote for their suggestion
+            if self.can_vote_on_nodes(caller) {
+                self.endorse_node(addr)
+            } else {
+                Ok(())
+            }
+        }
+
+        /// Votes for a suggested bootnode, listing it once it has enough votes. Only operators
+        /// of listed bootnodes and council members can vote, until the voting period ends
+        #[ink(message, payable)]
+        pub fn endorse_node(&mut self, addr: Multiaddr) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Nodes)?;
+            let mut suggestion = self
+                .node_suggestions
+                .get(&addr)
+                .ok_or(Error::SuggestionNotFound)?;
+            let caller = self.env().caller();
+            if !self.can_vote_on_nodes(caller) {
+                return Err(Error::Unauthorized);
+            }
+            if suggestion.expires_at < self.env().block_number() {
+                return Err(Error::VotingClosed);
+            }
+            if suggestion.endorsers.contains(&caller) {
+                return Err(Error::AlreadyExists);
+            }
+            suggestion.endorsers.push(caller);
+
+            // emit event
+            self.env().emit_event(NodeEndorsed {
+                address: addr.clone(),
+                endorser: caller,
+                endorsements: suggestion.endorsers.len() as u32,
+            });
+
+            if suggestion.endorsers.len() >= self.config.node_votes_required as usize {
+                self.node_suggestions.remove(&addr);
+                self.list_suggestion(addr, suggestion)
+            } else {
+                self.node_suggestions.insert(&addr, &suggestion);
+                Ok(())
+            }
+        }
+
+        /// Checks if an account operates a listed bootnode or sits on the council
+        fn can_vote_on_nodes(&self, account: AccountId) -> bool {
+            self.operated_nodes.get(account).unwrap_or_default() > 0
+                || self.council.members.contains(&account)
+        }
+
+        /// Lists a suggested bootnode without waiting for endorsements. Node registrars only
+        #[ink(message, payable)]
+        pub fn approve_suggestion(&mut self, addr: Multiaddr) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Nodes)?;
+            self.ensure_role(Role::NodeRegistrar)?;
+            let suggestion = self
+                .node_suggestions
+                .take(&addr)
+                .ok_or(Error::SuggestionNotFound)?;
+            self.list_suggestion(addr, suggestion)
+        }
+
+        /// Lists a suggested bootnode, moving the stake locked with the suggestion to the node
+        fn list_suggestion(&mut self, addr: Multiaddr, suggestion: NodeSuggestion) -> Result<()> {
+            self.staked = self.staked.saturating_sub(suggestion.stake);
+            self.list_node(
+                addr,
+                self.network_id,
+                NodeRole::BOOTNODE,
+                suggestion.operator,
+                suggestion.operator,
+                suggestion.stake,
+            )
+        }
+
+        /// Withdraws a suggested bootnode, crediting the stake locked with it back to the
+        /// suggester. Only the suggester can withdraw, during or after the vote
+        #[ink(message, payable)]
+        pub fn withdraw_suggestion(&mut self, addr: Multiaddr) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Nodes)?;
+            let suggestion = self
+                .node_suggestions
+                .get(&addr)
+                .ok_or(Error::SuggestionNotFound)?;
+            if suggestion.operator != self.env().caller() {
+                return Err(Error::Unauthorized);
+            }
+            self.node_suggestions.remove(&addr);
+            self.release_suggestion(&suggestion);
+
+            // emit event
+            self.env()
+                .emit_event(NodeSuggestionWithdrawn { address: addr });
+            Ok(())
+        }
+
+        /// Credits the stake locked with a dropped suggestion back to the suggester
+        fn release_suggestion(&mut self, suggestion: &NodeSuggestion) {
+            if suggestion.stake > 0 {
+                self.staked = self.staked.saturating_sub(suggestion.stake);
+                self.credit(suggestion.operator, suggestion.stake);
+            }
+        }
+
+        /// Retrieves the accounts that have endorsed a suggested bootnode
+        #[ink(message, payable)]
+        pub fn get_node_endorsements(&self, addr: Multiaddr) -> Vec<AccountId> {
+            self.node_suggestions
+                .get(&addr)
+                .map(|suggestion| suggestion.endorsers)
+                .unwrap_or_default()
+        }
+
+        /// Retrieves a suggested bootnode with its operator, votes and voting deadline
+        #[ink(message, payable)]
+        pub fn get_node_suggestion(&self, addr: Multiaddr) -> Option<NodeSuggestion> {
+            self.node_suggestions.get(&addr)
+        }
+
+        /// Retrieves the account operating a listed bootnode
+        #[ink(message, payable)]
+        pub fn get_node_operator(&self, addr: Multiaddr) -> Option<AccountId> {
+            self.node_record(&addr).map(|record| record.operator)
+        }
+
+        /// Sets the ECDSA public key nodes can authenticate with instead of the shared auth
+        /// material. Passing `None` disables signature authentication for the account
+        #[ink(message, payable)]
+        pub fn set_auth_key(&mut self, did: DID, auth_key: Option<Vec<u8>>) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let mut account = self.authorize(&did)?;
+            if auth_key.as_ref().is_some_and(|key| key.len() != 33) {
+                return Err(Error::InvalidCredential);
+            }
+            account.auth_key = auth_key.clone();
+            self.accounts.insert(&did, &account);
+
+            // emit event
+            self.env().emit_event(AuthKeyUpdated { did, auth_key });
+            Ok(())
+        }
+
+        /// Retrieves an account's hashtable CID using a signature over
+        /// `auth_challenge(did, issued_at)` made with its auth key, so nodes don't need to hold
+        /// the shared auth material. `issued_at` must be within the last `AUTH_CHALLENGE_TTL`
+        /// blocks. Refused while the account is auth-locked or declared compromised
+        #[ink(message, payable)]
+        pub fn get_account_ht_cid_signed(
+            &self,
+            did: DID,
+            issued_at: BlockNumber,
+            signature: [u8; 65],
+        ) -> Vec<u8> {
+            let now = self.env().block_number();
+            if issued_at > now
+                || now - issued_at > AUTH_CHALLENGE_TTL
+                || self.is_auth_locked(&did)
+                || self.compromised.contains(&did)
+            {
+                return Vec::new();
+            }
+            match self.accounts.get(&did) {
+                Some(account)
+                    if account.is_active()
+                        && account.verify_auth_signature(&did, issued_at, &signature) =>
+                {
+                    account.hashtable_cid
+                }
+                _ => Vec::new(),
+            }
+        }
+
+        /// Removes duplicate entries left in the restricted lists of the given applications by
+        /// earlier versions of `restrict`, keeping each user's first entry. Returns the number
+        /// of entries removed
+        #[ink(message, payable)]
+        pub fn dedupe_restrictions(&mut self, app_dids: Vec<DID>) -> Result<u32> {
+            self.ensure_not_paused(Subsystem::Restrictions)?;
+            self.ensure_role(Role::Admin)?;
+            let mut removed = 0;
+            for app_did in app_dids {
+                let Some(users) = self.restricted.get(&app_did) else {
+                    continue;
+                };
+                let mut deduped: Vec<DID> = Vec::with_capacity(users.len());
+                for user_did in users {
+                    if deduped.contains(&user_did) {
+                        removed += 1;
+                    } else {
+                        deduped.push(user_did);
+                    }
+                }
+                self.restricted.insert(&app_did, &deduped);
+            }
+            Ok(removed)
+        }
+
+        /// Offers control of a DID to another chain account. The account keeps its data and
+        /// restrictions; control only moves once `new_controller` calls `claim_did`. Proposing
+        /// again replaces the pending offer
+        #[ink(message, payable)]
+        pub fn transfer_did(&mut self, did: DID, new_controller: AccountId) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            self.authorize(&did)?;
+            self.pending_transfers.insert(&did, &new_controller);
+            self.notify_observers(&did, RegistryChange::TransferOffered(new_controller));
+
+            // emit event
+            self.env().emit_event(DidTransferProposed {
+                did,
+                new_controller,
+            });
+            Ok(())
+        }
+
+        /// Accepts control of a DID offered to the caller through `transfer_did`
+        #[ink(message, payable)]
+        pub fn claim_did(&mut self, did: DID) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let caller = self.env().caller();
+            if self.pending_transfers.get(&did) != Some(caller) {
+                return Err(Error::TransferNotFound);
+            }
+            // an offer made with a stolen key can't be claimed while the account is frozen
+            if self.compromised.contains(&did) {
+                return Err(Error::AccountCompromised);
+            }
+            let account = self.accounts.get(&did).ok_or(Error::AccountNotFound)?;
+            self.close_listing(&did, caller);
+            self.hand_over(did, account, caller);
+            Ok(())
+        }
+
+        /// Makes `new_controller` the only controller of a DID, dropping any pending offer
+        fn hand_over(&mut self, did: DID, mut account: AccountInfo, new_controller: AccountId) {
+            self.pending_transfers.remove(&did);
+            let previous_controllers =
+                core::mem::replace(&mut account.controllers, vec![new_controller]);
+            self.accounts.insert(&did, &account);
+            self.notify_observers(
+                &did,
+                RegistryChange::ControllersChanged(account.controllers.clone()),
+            );
+
+            // emit events
+            self.emit_controller_activity(&did, &previous_controllers, AccountAction::Transferred);
+            self.emit_controller_activity(&did, &account.controllers, AccountAction::Transferred);
+            self.env().emit_event(DidOwnershipTransferred {
+                did,
+                previous_controllers,
+                new_controller,
+            });
+        }
+
+        /// Retrieves the account a DID has been offered to, if any
+        #[ink(message, payable)]
+        pub fn get_pending_transfer(&self, did: DID) -> Option<AccountId> {
+            self.pending_transfers.get(&did)
+        }
+
+        /// Lists a DID for sale at `price`, or changes the price while no buyer has escrowed
+        /// the payment. Controllers only
+        #[ink(message, payable)]
+        pub fn list_did(&mut self, did: DID, price: Balance) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            self.authorize(&did)?;
+            if self
+                .did_listings
+                .get(&did)
+                .is_some_and(|listing| listing.buyer.is_some())
+            {
+                return Err(Error::AlreadyExists);
+            }
+            let seller = self.env().caller();
+            self.did_listings.insert(
+                &did,
+                &DidListing {
+                    seller,
+                    price,
+                    buyer: None,
+                },
+            );
+
+            // emit event
+            self.env().emit_event(DidListed { did, seller, price });
+            Ok(())
+        }
+
+        /// Escrows the price of a listed DID. The value sent must cover the price and any
+        /// excess is credited back to the caller. Control moves once the seller settles
+        #[ink(message, payable)]
+        pub fn buy_did(&mut self, did: DID) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let mut listing = self.did_listings.get(&did).ok_or(Error::ListingNotFound)?;
+            if listing.buyer.is_some() {
+                return Err(Error::AlreadyExists);
+            }
+            let value = self.env().transferred_value();
+            if value < listing.price {
+                return Err(Error::InsufficientFunds);
+            }
+            let buyer = self.env().caller();
+            if value > listing.price {
+                self.credit(buyer, value - listing.price);
+            }
+            self.staked = self.staked.saturating_add(listing.price);
+            listing.buyer = Some(buyer);
+            self.did_listings.insert(&did, &listing);
+
+            // emit event
+            self.env().emit_event(DidPaymentEscrowed {
+                did,
+                buyer,
+                price: listing.price,
+            });
+            Ok(())
+        }
+
+        /// Completes the sale of a DID: the buyer becomes its only controller and the escrowed
+        /// payment is credited to the caller. Controllers only
+        #[ink(message, payable)]
+        pub fn settle_did_sale(&mut self, did: DID) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let account = self.authorize(&did)?;
+            let listing = self.did_listings.get(&did).ok_or(Error::ListingNotFound)?;
+            let buyer = listing.buyer.ok_or(Error::ListingNotFound)?;
+            self.did_listings.remove(&did);
+            let seller = self.env().caller();
+            self.staked = self.staked.saturating_sub(listing.price);
+            self.credit(seller, listing.price);
+            self.hand_over(did.clone(), account, buyer);
+
+            // emit event
+            self.env().emit_event(DidSold {
+                did,
+                seller,
+                buyer,
+                price: listing.price,
+            });
+            Ok(())
+        }
+
+        /// Cancels the sale of a DID before settlement, crediting any escrowed payment back to
+        /// the buyer. Controllers take the DID off sale; the buyer only withdraws its payment,
+        /// leaving the DID listed
+        #[ink(message, payable)]
+        pub fn cancel_did_sale(&mut self, did: DID) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let caller = self.env().caller();
+            let mut listing = self.did_listings.get(&did).ok_or(Error::ListingNotFound)?;
+            if listing.buyer == Some(caller) {
+                self.staked = self.staked.saturating_sub(listing.price);
+                self.credit(caller, listing.price);
+                listing.buyer = None;
+                self.did_listings.insert(&did, &listing);
+
+                // emit event
+                self.env().emit_event(DidSaleCancelled { did, by: caller });
+                return Ok(());
+            }
+            self.authorize(&did)?;
+            self.close_listing(&did, caller);
+            Ok(())
+        }
+
+        /// Takes a DID off sale, crediting any escrowed payment back to the buyer
+        fn close_listing(&mut self, did: &DID, by: AccountId) {
+            let Some(listing) = self.did_listings.take(did) else {
+                return;
+            };
+            if let Some(buyer) = listing.buyer {
+                self.staked = self.staked.saturating_sub(listing.price);
+                self.credit(buyer, listing.price);
+            }
+
+            // emit event
+            self.env().emit_event(DidSaleCancelled {
+                did: did.clone(),
+                by,
+            });
+        }
+
+        /// Retrieves the sale listing of a DID, if it is listed
+        #[ink(message, payable)]
+        pub fn get_did_listing(&self, did: DID) -> Option<DidListing> {
+            self.did_listings.get(&did)
+        }
+
+        /// Deletes an account along with its subscribers, its restriction list and its entries
+        /// in other applications' restriction lists, key versions and access tokens. Only the
+        /// controller can delete it. The contract holds no deposit for the account; the storage
+        /// deposit released for the cleared entries goes to the caller
+        #[ink(message, payable)]
+        pub fn delete_account(&mut self, did: DID) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let account = self.authorize(&did)?;
+            // a suspension can't be shed by deleting and registering the DID again
+            if account.status == AccountStatus::Suspended {
+                return Err(Error::AccountSuspended);
+            }
+            self.remove_account(did, account);
+            Ok(())
+        }
+
+        /// Marks an account inactive once no controller has acted on it for 180 epochs,
+        /// warning its controllers through the event. Unless a controller acts on it within
+        /// 30 more epochs, anyone can then reap it. Anyone can call it
+        #[ink(message, payable)]
+        pub fn mark_inactive(&mut self, did: DID) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let account = self.accounts.get(&did).ok_or(Error::AccountNotFound)?;
+            if self.inactive_since.contains(&did) {
+                return Err(Error::AlreadyExists);
+            }
+            let epoch = self.current_epoch();
+            let last_active = self.last_active.get(&did).unwrap_or_else(|| {
+                account.created_at.saturating_sub(self.genesis_block) / self.epoch_length
+            });
+            if epoch.saturating_sub(last_active) < INACTIVITY_EPOCHS {
+                return Err(Error::AccountActive);
+            }
+            self.inactive_since.insert(&did, &epoch);
+
+            // emit event
+            self.env().emit_event(AccountMarkedInactive {
+                did,
+                reapable_from: epoch.saturating_add(REAP_GRACE_EPOCHS),
+            });
+            Ok(())
+        }
+
+        /// Deletes an account marked inactive whose grace period passed without a controller
+        /// acting on it, as `delete_account` would. Anyone can call it
+        #[ink(message, payable)]
+        pub fn reap_account(&mut self, did: DID) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let account = self.accounts.get(&did).ok_or(Error::AccountNotFound)?;
+            let reapable = self.inactive_since.get(&did).is_some_and(|since| {
+                self.current_epoch() >= since.saturating_add(REAP_GRACE_EPOCHS)
+            });
+            if !reapable {
+                return Err(Error::AccountActive);
+            }
+            self.remove_account(did, account);
+            Ok(())
+        }
+
+        /// Retrieves the epoch of the latest controller action on an account, and the epoch
+        /// it was marked inactive in, if it was
+        #[ink(message, payable)]
+        pub fn get_account_activity(&self, did: DID) -> Option<(u32, Option<u32>)> {
+            let account = self.accounts.get(&did)?;
+            let last_active = self.last_active.get(&did).unwrap_or_else(|| {
+                account.created_at.saturating_sub(self.genesis_block) / self.epoch_length
+            });
+            Some((last_active, self.inactive_since.get(&did)))
+        }
+
+        /// Removes an account and everything recorded for it
+        fn remove_account(&mut self, did: DID, account: AccountInfo) {
+            self.close_listing(&did, self.env().caller());
+            self.accounts.remove(&did);
+            self.account_count = self.account_count.saturating_sub(1);
+            self.unindex_prefix(&did);
+
+            // nodes supporting the DID as an application. The journal records their removal
+            // so nodes syncing incrementally drop the DID too
+            for node in self.subscribers.take(&did).unwrap_or_default() {
+                self.record_subscriber_change(&did, &node, false);
+                self.subscribed_at.remove((&did, &node));
+                self.release_subscription(&did, &node);
+                let mut subscriptions = self.node_subscriptions.get(&node).unwrap_or_default();
+                subscriptions.retain(|app_did| *app_did != did);
+                self.node_subscriptions.insert(&node, &subscriptions);
+            }
+            self.subscription_approval.remove(&did);
+            self.pending_subscriptions.remove(&did);
+            self.replication_policies.remove(&did);
+            self.banned_subscribers.remove(&did);
+            self.observers.remove(&did);
+
+            // users that restricted the DID as an application
+            for user_did in self.restricted.take(&did).unwrap_or_default() {
+                let mut apps = self.user_restrictions.get(&user_did).unwrap_or_default();
+                apps.retain(|app_did| *app_did != did);
+                self.user_restrictions.insert(&user_did, &apps);
+                self.restriction_acks.remove((&user_did, &did));
+                self.record_restriction_change(&user_did, &did, false);
+            }
+
+            // applications the DID restricted as a user
+            for app_did in self.user_restrictions.take(&did).unwrap_or_default() {
+                let mut users = self.restricted.get(&app_did).unwrap_or_default();
+                users.retain(|user_did| *user_did != did);
+                self.restricted.insert(&app_did, &users);
+                self.restriction_acks.remove((&did, &app_did));
+                self.record_restriction_change(&did, &app_did, false);
+            }
+
+            // wrapped data-key versions shared with other accounts
+            for peer in self.key_version_peers.take(&did).unwrap_or_default() {
+                self.key_versions.remove((&did, &peer));
+                self.key_versions.remove((&peer, &did));
+                let mut peers = self.key_version_peers.get(&peer).unwrap_or_default();
+                peers.retain(|entry| *entry != did);
+                self.key_version_peers.insert(&peer, &peers);
+            }
+
+            // access tokens minted by or for the DID
+            for token_id in self.account_tokens.take(&did).unwrap_or_default() {
+                let Some(token) = self.access_tokens.take(token_id) else {
+                    continue;
+                };
+                let peer = if token.app_did == did {
+                    token.user_did
+                } else {
+                    token.app_did
+                };
+                let mut tokens = self.account_tokens.get(&peer).unwrap_or_default();
+                tokens.retain(|id| *id != token_id);
+                self.account_tokens.insert(&peer, &tokens);
+            }
+
+            self.pending_cids.remove(&did);
+            self.credentials.remove(&did);
+            self.key_agreement_history.remove(&did);
+            self.pending_transfers.remove(&did);
+            self.guardians.remove(&did);
+            self.recoveries.remove(&did);
+            self.compromise_reports.remove(&did);
+            self.auth_failures.remove(&did);
+            self.auth_failure_reporters.remove(&did);
+            self.auth_cooldowns.remove(&did);
+            self.auth_locked_until.remove(&did);
+            self.last_active.remove(&did);
+            self.inactive_since.remove(&did);
+            if self.suspensions.take(&did).is_some() {
+                self.pending_appeals.retain(|entry| *entry != did);
+            }
+
+            #[cfg(feature = "did-anchor")]
+            self.unanchor_did(&did);
+
+            // emit events
+            self.emit_controller_activity(&did, &account.controllers, AccountAction::Deleted);
+            self.env().emit_event(AccountDeleted { did });
+        }
+
+        /// Pauses an account's presence on the network without deleting its state. Nodes
+        /// can't fetch its hashtable CID or subscribe to it, and it can't take part in new
+        /// restrictions until it is reactivated
+        #[ink(message, payable)]
+        pub fn deactivate(&mut self, did: DID) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            self.set_account_status(did, AccountStatus::Deactivated)
+        }
+
+        /// Restores a deactivated account
+        #[ink(message, payable)]
+        pub fn reactivate(&mut self, did: DID) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            self.set_account_status(did, AccountStatus::Active)
+        }
+
+        /// Sets an account's status after checking that the caller is its controller
+        fn set_account_status(&mut self, did: DID, status: AccountStatus) -> Result<()> {
+            let account = self.authorize(&did)?;
+            if account.status == AccountStatus::Suspended {
+                return Err(Error::AccountSuspended);
+            }
+            self.update_account_status(did, account, status);
+            Ok(())
+        }
+
+        /// Stores an account's new status and reports the change
+        fn update_account_status(
+            &mut self,
+            did: DID,
+            mut account: AccountInfo,
+            status: AccountStatus,
+        ) {
+            account.status = status;
+            self.accounts.insert(&did, &account);
+
+            // emit events
+            self.emit_controller_activity(&did, &account.controllers, AccountAction::StatusChanged);
+            self.env().emit_event(AccountStatusChanged { did, status });
+        }
+
+        /// Suspends an account, e.g. for abuse. Nodes treat it as deactivated, and its
+        /// controllers can't reactivate it; they can only appeal. Admins only
+        #[ink(message, payable)]
+        pub fn suspend_account(&mut self, did: DID, reason: Vec<u8>) -> Result<()> {
+            self.ensure_role(Role::Admin)?;
+            let account = self.accounts.get(&did).ok_or(Error::AccountNotFound)?;
+            if account.status == AccountStatus::Suspended {
+                return Err(Error::AccountSuspended);
+            }
+            self.suspensions.insert(
+                &did,
+                &Suspension {
+                    reason: reason.clone(),
+                    suspended_at: self.env().block_number(),
+                    appeal: None,
+                    ruling: None,
+                },
+            );
+            self.update_account_status(did.clone(), account, AccountStatus::Suspended);
+
+            // emit event
+            self.env().emit_event(AccountSuspended { did, reason });
+            Ok(())
+        }
+
+        /// Appeals an account's suspension with a statement published at `statement_cid`,
+        /// queueing it for an admin's ruling. One appeal can be pending at a time.
+        /// Controllers only
+        #[ink(message, payable)]
+        pub fn appeal_suspension(&mut self, did: DID, statement_cid: CID) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            self.authorize(&did)?;
+            let mut suspension = self.suspensions.get(&did).ok_or(Error::NotSuspended)?;
+            if suspension.appeal.is_some() {
+                return Err(Error::AlreadyExists);
+            }
+            if self.pending_appeals.len() >= MAX_PENDING_APPEALS {
+                return Err(Error::CapacityExceeded);
+            }
+            suspension.appeal = Some(statement_cid.clone());
+            self.suspensions.insert(&did, &suspension);
+            self.pending_appeals.push(did.clone());
+
+            // emit event
+            self.env()
+                .emit_event(SuspensionAppealed { did, statement_cid });
+            Ok(())
+        }
+
+        /// Rules on a pending appeal, recording the reason. Lifting the suspension makes the
+        /// account active again; confirming it keeps the account suspended and records the
+        /// reason with the suspension. Admins only
+        #[ink(message, payable)]
+        pub fn rule_on_appeal(&mut self, did: DID, lift: bool, reason: Vec<u8>) -> Result<()> {
+            self.ensure_role(Role::Admin)?;
+            let mut suspension = self.suspensions.get(&did).ok_or(Error::AppealNotFound)?;
+            if suspension.appeal.is_none() {
+                return Err(Error::AppealNotFound);
+            }
+            self.pending_appeals.retain(|entry| *entry != did);
+            if lift {
+                self.suspensions.remove(&did);
+                if let Some(account) = self.accounts.get(&did) {
+                    self.update_account_status(did.clone(), account, AccountStatus::Active);
+                }
+            } else {
+                suspension.appeal = None;
+                suspension.ruling = Some(reason.clone());
+                self.suspensions.insert(&did, &suspension);
+            }
+
+            // emit event
+            self.env().emit_event(AppealRuled {
+                did,
+                lifted: lift,
+                reason,
+            });
+            Ok(())
+        }
+
+        /// Retrieves the suspension in force on an account, with any pending appeal
+        #[ink(message, payable)]
+        pub fn get_suspension(&self, did: DID) -> Option<Suspension> {
+            self.suspensions.get(&did)
+        }
+
+        /// Retrieves the suspended accounts with an appeal awaiting a ruling, oldest first
+        #[ink(message, payable)]
+        pub fn get_pending_appeals(&self) -> Vec<DID> {
+            self.pending_appeals.clone()
+        }
+
+        /// Reports a failed authentication attempt against a DID. Nodes check auth material
+        /// off-chain when applications connect, so they report failures here; once
+        /// `AUTH_FAILURE_THRESHOLD` failures are reported in an epoch `AuthFailureSpike` is
+        /// emitted and, if the account opted into a cool-down, its auth material is locked.
+        /// Only operators of listed bootnodes can report, each at most once per DID and epoch
+        #[ink(message, payable)]
+        pub fn report_auth_failure(&mut self, did: DID) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            if !self.accounts.contains(&did) {
+                return Err(Error::AccountNotFound);
+            }
+            let reporter = self.env().caller();
+            if self.operated_nodes.get(reporter).unwrap_or_default() == 0 {
+                return Err(Error::Unauthorized);
+            }
+            let epoch = self.current_epoch();
+            let (failures, mut reporters) = match self.auth_failures.get(&did) {
+                Some((counted_in, failures)) if counted_in == epoch => (
+                    failures + 1,
+                    self.auth_failure_reporters.get(&did).unwrap_or_default(),
+                ),
+                _ => (1, Vec::new()),
+            };
+            if reporters.contains(&reporter) {
+                return Err(Error::AlreadyExists);
+            }
+            reporters.push(reporter);
+            self.auth_failure_reporters.insert(&did, &reporters);
+            self.auth_failures.insert(&did, &(epoch, failures));
+
+            if failures == AUTH_FAILURE_THRESHOLD {
+                let locked_until = self.auth_cooldowns.get(&did).map(|cooldown| {
+                    let until = self.env().block_number().saturating_add(cooldown);
+                    self.auth_locked_until.insert(&did, &until);
+                    until
+                });
+
+                // emit event
+                self.env().emit_event(AuthFailureSpike {
+                    did,
+                    epoch,
+                    failures,
+                    locked_until,
+                });
+            }
+            Ok(())
+        }
+
+        /// Opts an account into locking its auth material for `cooldown` blocks after a
+        /// failure spike. Passing `None` opts out; either way any active lock is lifted
+        #[ink(message, payable)]
+        pub fn set_auth_cooldown(&mut self, did: DID, cooldown: Option<BlockNumber>) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            self.authorize(&did)?;
+            match cooldown {
+                Some(cooldown) => self.auth_cooldowns.insert(&did, &cooldown),
+                None => {
+                    self.auth_cooldowns.remove(&did);
+                    None
+                }
+            };
+            self.auth_locked_until.remove(&did);
+            Ok(())
+        }
+
+        /// Retrieves the block until which an account's auth material is locked, if it is
+        #[ink(message, payable)]
+        pub fn get_auth_lock(&self, did: DID) -> Option<BlockNumber> {
+            self.auth_locked_until
+                .get(&did)
+                .filter(|until| self.env().block_number() < *until)
+        }
+
+        /// Checks whether reads gated on an account's auth material are currently refused
+        fn is_auth_locked(&self, did: &DID) -> bool {
+            self.auth_locked_until
+                .get(did)
+                .is_some_and(|until| self.env().block_number() < until)
+        }
+
+        /// Appoints the guardians able to recover an account if its controller key and auth
+        /// material are lost, and how many of them must approve. An empty list removes them
+        #[ink(message, payable)]
+        pub fn set_guardians(
+            &mut self,
+            did: DID,
+            guardians: Vec<DID>,
+            threshold: u32,
+        ) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            self.authorize(&did)?;
+            if guardians.len() > MAX_GUARDIANS {
+                return Err(Error::CapacityExceeded);
+            }
+            if guardians.is_empty() {
+                self.guardians.remove(&did);
+            } else if threshold == 0 || threshold as usize > guardians.len() {
+                return Err(Error::InvalidThreshold);
+            } else {
+                self.guardians.insert(
+                    &did,
+                    &GuardianSet {
+                        guardians: guardians.clone(),
+                        threshold,
+                    },
+                );
+            }
+            // a changed guardian set invalidates approvals and reports already collected
+            self.recoveries.remove(&did);
+            self.compromise_reports.remove(&did);
+
+            // emit event
+            self.env().emit_event(GuardiansUpdated {
+                did,
+                guardians,
+                threshold,
+            });
+            Ok(())
+        }
+
+        /// Starts recovering an account to `new_controller`. The caller must control
+        /// `guardian_did`, one of the account's guardians, and counts as its first approval.
+        /// Initiating again replaces the recovery in progress
+        #[ink(message, payable)]
+        pub fn initiate_recovery(
+            &mut self,
+            did: DID,
+            guardian_did: DID,
+            new_controller: AccountId,
+        ) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            self.ensure_guardian(&did, &guardian_did)?;
+            let executable_at = self.env().block_number().saturating_add(RECOVERY_DELAY);
+            self.recoveries.insert(
+                &did,
+                &Recovery {
+                    new_controller,
+                    executable_at,
+                    approvals: vec![guardian_did.clone()],
+                },
+            );
+
+            // emit events
+            self.env().emit_event(RecoveryInitiated {
+                did: did.clone(),
+                new_controller,
+                executable_at,
+            });
+            self.env().emit_event(RecoveryApproved {
+                did,
+                guardian: guardian_did,
+                approvals: 1,
+            });
+            Ok(())
+        }
+
+        /// Approves the recovery in progress for an account. The caller must control
+        /// `guardian_did`, one of the account's guardians
+        #[ink(message, payable)]
+        pub fn approve_recovery(&mut self, did: DID, guardian_did: DID) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            self.ensure_guardian(&did, &guardian_did)?;
+            let mut recovery = self.recoveries.get(&did).ok_or(Error::RecoveryNotFound)?;
+            if recovery.approvals.contains(&guardian_did) {
+                return Err(Error::AlreadyExists);
+            }
+            recovery.approvals.push(guardian_did.clone());
+            self.recoveries.insert(&did, &recovery);
+
+            // emit event
+            self.env().emit_event(RecoveryApproved {
+                did,
+                guardian: guardian_did,
+                approvals: recovery.approvals.len() as u32,
+            });
+            Ok(())
+        }
+
+        /// Completes a recovery once enough guardians approved and its timelock has passed.
+        /// Only the new controller can call it, supplying the hash of fresh auth material; the
+        /// account's compromise flag is cleared, and the signature auth key, credentials and
+        /// any announced CID set with the lost keys are dropped
+        #[ink(message, payable)]
+        pub fn finalize_recovery(&mut self, did: DID, auth_hash: Hash) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let recovery = self.recoveries.get(&did).ok_or(Error::RecoveryNotFound)?;
+            let caller = self.env().caller();
+            if recovery.new_controller != caller {
+                return Err(Error::Unauthorized);
+            }
+            let threshold = self
+                .guardians
+                .get(&did)
+                .map_or(u32::MAX, |set| set.threshold);
+            if (recovery.approvals.len() as u32) < threshold
+                || self.env().block_number() < recovery.executable_at
+            {
+                return Err(Error::RecoveryNotReady);
+            }
+
+            let mut account = self.accounts.get(&did).ok_or(Error::AccountNotFound)?;
+            let previous_controllers = core::mem::replace(&mut account.controllers, vec![caller]);
+            account.auth_hash = auth_hash;
+            account.auth_key = None;
+            self.accounts.insert(&did, &account);
+            self.recoveries.remove(&did);
+            self.compromised.remove(&did);
+            self.compromise_reports.remove(&did);
+            self.pending_transfers.remove(&did);
+            self.pending_cids.remove(&did);
+            self.credentials.remove(&did);
+
+            // emit events
+            self.emit_controller_activity(&did, &previous_controllers, AccountAction::Recovered);
+            self.emit_controller_activity(&did, &account.controllers, AccountAction::Recovered);
+            self.env().emit_event(RecoveryFinalized {
+                did,
+                new_controller: caller,
+            });
+            Ok(())
+        }
+
+        /// Cancels the recovery in progress for an account. Only its current controller can
+        /// call it, which protects the account from colluding guardians during the timelock.
+        /// Allowed while the account is declared compromised, since colluding guardians can
+        /// declare a compromise themselves to take the veto away
+        #[ink(message, payable)]
+        pub fn cancel_recovery(&mut self, did: DID) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            self.ensure_controller(&did)?;
+            if self.recoveries.take(&did).is_none() {
+                return Err(Error::RecoveryNotFound);
+            }
+
+            // emit event
+            self.env().emit_event(RecoveryCancelled { did });
+            Ok(())
+        }
+
+        /// Retrieves an account's guardians and the recovery in progress, if any
+        #[ink(message, payable)]
+        pub fn get_recovery(&self, did: DID) -> (Option<GuardianSet>, Option<Recovery>) {
+            (self.guardians.get(&did), self.recoveries.get(&did))
+        }
+
+        /// Checks that `guardian_did` guards `did` and that the caller controls it
+        fn ensure_guardian(&mut self, did: &DID, guardian_did: &DID) -> Result<()> {
+            let set = self.guardians.get(did).ok_or(Error::Unauthorized)?;
+            if !set.guardians.contains(guardian_did) {
+                return Err(Error::Unauthorized);
+            }
+            self.authorize(guardian_did)?;
+            Ok(())
+        }
+
+        /// Lets another chain account control a DID alongside the existing controllers.
+        /// Any controller can add one, up to `MAX_CONTROLLERS`
+        #[ink(message, payable)]
+        pub fn add_controller(&mut self, did: DID, controller: AccountId) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let mut account = self.authorize(&did)?;
+            if account.is_controller(&controller) {
+                return Err(Error::AlreadyExists);
+            }
+            if account.controllers.len() >= MAX_CONTROLLERS {
+                return Err(Error::CapacityExceeded);
+            }
+            account.controllers.push(controller);
+            self.accounts.insert(&did, &account);
+            self.notify_observers(
+                &did,
+                RegistryChange::ControllersChanged(account.controllers.clone()),
+            );
+
+            // emit events
+            self.emit_controller_activity(
+                &did,
+                &account.controllers,
+                AccountAction::ControllersUpdated,
+            );
+            self.env().emit_event(ControllersUpdated {
+                did,
+                controllers: account.controllers,
+            });
+            Ok(())
+        }
+
+        /// Revokes a chain account's control of a DID. Any controller can remove one,
+        /// including itself, as long as at least one remains
+        #[ink(message, payable)]
+        pub fn remove_controller(&mut self, did: DID, controller: AccountId) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            let mut account = self.authorize(&did)?;
+            if !account.is_controller(&controller) {
+                return Err(Error::ControllerNotFound);
+            }
+            if account.controllers.len() == 1 {
+                return Err(Error::LastController);
+            }
+            account.controllers.retain(|entry| *entry != controller);
+            self.accounts.insert(&did, &account);
+            self.notify_observers(
+                &did,
+                RegistryChange::ControllersChanged(account.controllers.clone()),
+            );
+
+            // emit events, including to the removed controller
+            self.emit_controller_activity(
+                &did,
+                &[account.controllers.as_slice(), &[controller]].concat(),
+                AccountAction::ControllersUpdated,
+            );
+            self.env().emit_event(ControllersUpdated {
+                did,
+                controllers: account.controllers,
+            });
+            Ok(())
+        }
+
+        /// Registers an account as an indexer able to publish checkpoints (admins only)
+        #[ink(message, payable)]
+        pub fn register_indexer(&mut self, indexer: AccountId) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Nodes)?;
+            self.ensure_role(Role::Admin)?;
+            if self.indexers.contains(&indexer) {
+                return Err(Error::AlreadyExists);
+            }
+            if self.indexers.len() >= MAX_INDEXERS {
+                return Err(Error::CapacityExceeded);
+            }
+            self.indexers.push(indexer);
+
+            // emit event
+            self.env().emit_event(IndexerUpdated {
+                indexer,
+                registered: true,
+            });
+            Ok(())
+        }
+
+        /// Removes an indexer along with its checkpoints (admins only)
+        #[ink(message, payable)]
+        pub fn unregister_indexer(&mut self, indexer: AccountId) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Nodes)?;
+            self.ensure_role(Role::Admin)?;
+            if !self.indexers.contains(&indexer) {
+                return Err(Error::Unauthorized);
+            }
+            self.indexers.retain(|entry| *entry != indexer);
+            self.checkpoints.remove(indexer);
+
+            // emit event
+            self.env().emit_event(IndexerUpdated {
+                indexer,
+                registered: false,
+            });
+            Ok(())
+        }
+
+        /// Publishes that the calling indexer has indexed the contract through `block`,
+        /// reaching `state_hash`. Checkpoints must move forward and can't lie in the future
+        #[ink(message, payable)]
+        pub fn publish_checkpoint(&mut self, block: BlockNumber, state_hash: Hash) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Nodes)?;
+            let indexer = self.env().caller();
+            if !self.indexers.contains(&indexer) {
+                return Err(Error::Unauthorized);
+            }
+            let now = self.env().block_number();
+            let mut checkpoints = self.checkpoints.get(indexer).unwrap_or_default();
+            if block > now
+                || checkpoints
+                    .last()
+                    .is_some_and(|latest| block <= latest.block)
+            {
+                return Err(Error::StaleCheckpoint);
+            }
+            if checkpoints.len() >= MAX_CHECKPOINTS {
+                checkpoints.remove(0);
+            }
+            checkpoints.push(Checkpoint {
+                block,
+                state_hash,
+                published_at: now,
+            });
+            self.checkpoints.insert(indexer, &checkpoints);
+
+            // emit event
+            self.env().emit_event(CheckpointPublished {
+                indexer,
+                block,
+                state_hash,
+            });
+            Ok(())
+        }
+
+        /// Retrieves the recent checkpoints of an indexer, oldest first
+        #[ink(message, payable)]
+        pub fn get_checkpoints(&self, indexer: AccountId) -> Vec<Checkpoint> {
+            self.checkpoints.get(indexer).unwrap_or_default()
+        }
+
+        /// Retrieves the latest checkpoint of every registered indexer that published one,
+        /// so clients can compare them for consistency
+        #[ink(message, payable)]
+        pub fn get_latest_checkpoints(&self) -> Vec<(AccountId, Checkpoint)> {
+            self.indexers
+                .iter()
+                .filter_map(|indexer| {
+                    self.checkpoints
+                        .get(indexer)
+                        .and_then(|mut checkpoints| checkpoints.pop())
+                        .map(|checkpoint| (*indexer, checkpoint))
+                })
+                .collect()
+        }
+
+        /// Runs the checks of `new_account` for the caller without registering anything,
+        /// so clients can surface the exact error before submitting a transaction
+        #[ink(message, payable)]
+        pub fn validate_new_account(&self, did: DID) -> Result<()> {
+            self.check_new_account(&did)
+        }
+
+        /// Runs the checks of `add_address` without listing the address
+        #[ink(message, payable)]
+        pub fn validate_address(&self, addr: Multiaddr, network_id: NetworkId) -> Result<()> {
+            self.ensure_can_list()?;
+            self.check_new_address(&addr, network_id)?;
+            self.check_stake().map(|_| ())
+        }
+
+        /// Runs the checks of `subscribe_node` without subscribing the node
+        #[ink(message, payable)]
+        pub fn validate_subscription(
+            &self,
+            did: DID,
+            addr: Multiaddr,
+            network_id: NetworkId,
+        ) -> Result<()> {
+            self.check_subscription(&did, &addr, network_id)
+        }
+
+        /// Runs the checks of `restrict` for the caller without recording the restriction
+        #[ink(message, payable)]
+        pub fn validate_restriction(&self, user_did: DID, app_did: DID) -> Result<()> {
+            self.check_restriction(&user_did, &app_did)
+        }
+
+        /// Emits `ControllerActivity` to each of the given controllers of an account
+        fn emit_controller_activity(
+            &self,
+            did: &DID,
+            controllers: &[AccountId],
+            action: AccountAction,
+        ) {
+            for controller in controllers {
+                self.env().emit_event(ControllerActivity {
+                    controller: *controller,
+                    did: did.clone(),
+                    action,
+                });
+            }
+        }
+
+        /// Checks that `did` can be registered
+        fn check_new_account(&self, did: &DID) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            if self.accounts.contains(did) {
+                return Err(Error::AlreadyExists);
+            }
+            if self.registrations_frozen {
+                return Err(Error::RegistrationsFrozen);
+            }
+            if did.len() > self.config.max_did_len as usize {
+                return Err(Error::DidTooLong);
+            }
+            if self.env().transferred_value() < self.config.registration_fee {
+                return Err(Error::InsufficientFee);
+            }
+            if self.is_reserved(did) && self.did_allocations.get(did) != Some(self.env().caller()) {
+                return Err(Error::DidReserved);
+            }
+            Ok(())
+        }
+
+        /// Checks that an address can be added to the bootnode list
+        fn check_new_address(&self, addr: &Multiaddr, network_id: NetworkId) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Nodes)?;
+            // Reject addresses meant for another network
+            if network_id != self.network_id {
+                return Err(Error::NetworkMismatch);
+            }
+            validate_multiaddr(addr).map_err(Error::InvalidMultiaddr)?;
+
+            // Check if the address is already listed
+            if self.node_slots.contains(addr) {
+                return Err(Error::AlreadyExists);
+            }
+            if self.blacklisted.contains(addr) {
+                return Err(Error::NodeBlacklisted);
+            }
+            if self.registrations_frozen {
+                return Err(Error::RegistrationsFrozen);
+            }
+            Ok(())
+        }
+
+        /// Checks that a node can subscribe to, or request to join, an application
+        fn check_subscription(
+            &self,
+            did: &DID,
+            addr: &Multiaddr,
+            network_id: NetworkId,
+        ) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Subscriptions)?;
+            // Reject subscriptions meant for another network
+            if network_id != self.network_id {
+                return Err(Error::NetworkMismatch);
+            }
+            self.check_subscriber(did, addr, &self.env().caller())?;
+            let subscribed = self.subscribers.get(did).unwrap_or_default().contains(addr);
+            if !subscribed {
+                self.check_onboarding_quota(addr)?;
+            }
+            if !subscribed && self.subscription_approval.contains(did) {
+                let now = self.env().block_number();
+                let caller = self.env().caller();
+                let pending = self
+                    .pending_subscriptions
+                    .get(did)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|request| request.expires_at >= now)
+                    .collect::<Vec<_>>();
+                // another account's request for this node stands until the application rules on it
+                if pending
+                    .iter()
+                    .any(|request| request.node == *addr && request.requester != caller)
+                {
+                    return Err(Error::AlreadyExists);
+                }
+                // each account has its own allowance, so no caller can crowd out the others.
+                // The caller's earlier request for this node is replaced
+                let own = pending
+                    .iter()
+                    .filter(|request| request.requester == caller && request.node != *addr)
+                    .count();
+                if own >= MAX_PENDING_PER_REQUESTER {
+                    return Err(Error::CapacityExceeded);
+                }
+            }
+            Ok(())
+        }
+
+        /// Checks that `account` can add a node to an application's subscribers: the
+        /// application is active, the node is neither banned nor blacklisted, its address is
+        /// well formed and, unless already subscribed, the account has allowance left and the
+        /// node runs where the application allows
+        fn check_subscriber(&self, did: &DID, addr: &Multiaddr, account: &AccountId) -> Result<()> {
+            if self
+                .accounts
+                .get(did)
+                .is_some_and(|account| !account.is_active())
+            {
+                return Err(Error::AccountDeactivated);
+            }
+            if self
+                .banned_subscribers
+                .get(did)
+                .unwrap_or_default()
+                .contains(addr)
+            {
+                return Err(Error::NodeBanned);
+            }
+            if self.blacklisted.contains(addr) {
+                return Err(Error::NodeBlacklisted);
+            }
+            validate_multiaddr(addr).map_err(Error::InvalidMultiaddr)?;
+            if !self.subscribers.get(did).unwrap_or_default().contains(addr) {
+                self.check_subscription_allowance(did, account)?;
+                self.check_placement(did, addr)?;
+            }
+            Ok(())
+        }
+
+        /// Checks that a node joining an application fits its replication policy: it is listed
+        /// in an allowed region, the application has room for it and, once few slots are left,
+        /// it adds a region the subscribers don't span yet
+        fn check_placement(&self, did: &DID, addr: &Multiaddr) -> Result<()> {
+            let Some(policy) = self.replication_policies.get(did) else {
+                return Ok(());
+            };
+            if policy.require_attestation && !self.is_attested(addr) {
+                return Err(Error::NodeNotAttested);
+            }
+            let region = self.node_region(addr);
+            if !policy.allowed_regions.is_empty()
+                && !region
+                    .as_ref()
+                    .is_some_and(|region| policy.allowed_regions.contains(region))
+            {
+                return Err(Error::RegionNotAllowed);
+            }
+            if policy.max_replicas == 0 {
+                return Ok(());
+            }
+            let subscribers = self.subscribers.get(did).unwrap_or_default();
+            let count = subscribers.len() as u32;
+            if count >= policy.max_replicas {
+                return Err(Error::CapacityExceeded);
+            }
+            let mut regions = subscribers
+                .iter()
+                .filter_map(|node| self.node_region(node))
+                .collect::<Vec<_>>();
+            regions.sort();
+            regions.dedup();
+            let improves = region.is_some_and(|region| !regions.contains(&region));
+            // every slot left after this join must still be able to bring in a missing region
+            let spanned = regions.len() as u32 + u32::from(improves);
+            let missing = policy.min_regions.saturating_sub(spanned);
+            if missing > policy.max_replicas - count - 1 {
+                return Err(Error::InsufficientDiversity);
+            }
+            Ok(())
+        }
+
+        /// Retrieves the region label of a listed bootnode, if its operator set one
+        fn node_region(&self, addr: &Multiaddr) -> Option<Vec<u8>> {
+            self.node_record(addr)
+                .map(|record| record.region)
+                .filter(|region| !region.is_empty())
+        }
+
+        /// Checks that the caller can restrict an application on behalf of a user
+        fn check_restriction(&self, user_did: &DID, app_did: &DID) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Restrictions)?;
+            // only the user can restrict access to their data
+            let user = self.check_authorized(user_did)?;
+            let app = self.accounts.get(app_did).ok_or(Error::AccountNotFound)?;
+            if !user.is_active() || !app.is_active() {
+                return Err(Error::AccountDeactivated);
+            }
+            if self
+                .restricted
+                .get(app_did)
+                .unwrap_or_default()
+                .contains(user_did)
+            {
+                return Err(Error::AlreadyRestricted);
+            }
+            Ok(())
+        }
+
+        /// Grants a role to an account. Admins only
+        #[ink(message, payable)]
+        pub fn grant_role(&mut self, account: AccountId, role: Role) -> Result<()> {
+            self.ensure_role(Role::Admin)?;
+            if self.has_role(account, role) {
+                return Err(Error::AlreadyExists);
+            }
+            self.roles.insert((account, role), &());
+
+            // emit event
+            self.env().emit_event(RoleGranted {
+                account,
+                role,
+                granted_by: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// Revokes a role from an account. Admins only; the owner's roles can't be revoked
+        #[ink(message, payable)]
+        pub fn revoke_role(&mut self, account: AccountId, role: Role) -> Result<()> {
+            self.ensure_role(Role::Admin)?;
+            if !self.roles.contains((account, role)) {
+                return Err(Error::Unauthorized);
+            }
+            self.roles.remove((account, role));
+
+            // emit event
+            self.env().emit_event(RoleRevoked {
+                account,
+                role,
+                revoked_by: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// Checks whether an account holds a role
+        #[ink(message, payable)]
+        pub fn has_role(&self, account: AccountId, role: Role) -> bool {
+            account == self.owner || self.roles.contains((account, role))
+        }
+
+        /// Stops writes to a subsystem, or with `None` to every subsystem, while a vulnerability
+        /// is investigated. Queries keep working. Pausers only; unlike `unpause` this stays open
+        /// once a council is set, so an incident doesn't wait on a proposal
+        #[ink(message, payable)]
+        pub fn pause(&mut self, subsystem: Option<Subsystem>) -> Result<()> {
+            self.ensure_role(Role::Pauser)?;
+            self.set_paused(subsystem, true);
+            Ok(())
+        }
+
+        /// Resumes writes paused through `pause`. Pausers only, until a council is set
+        #[ink(message, payable)]
+        pub fn unpause(&mut self, subsystem: Option<Subsystem>) -> Result<()> {
+            self.ensure_role(Role::Pauser)?;
+            self.ensure_no_council()?;
+            self.set_paused(subsystem, false);
+            Ok(())
+        }
+
+        /// Checks whether writes to a subsystem are paused, globally or on their own
+        #[ink(message, payable)]
+        pub fn is_paused(&self, subsystem: Subsystem) -> bool {
+            self.paused || self.paused_subsystems.contains(&subsystem)
+        }
+
+        /// Keeps DIDs starting with `prefix`, such as `did:sam:apps:system`, from being
+        /// registered except by accounts they are allocated to. Passing `false` releases the
+        /// prefix. Admins only, until a council is set
+        #[ink(message, payable)]
+        pub fn set_reserved_prefix(&mut self, prefix: Vec<u8>, reserved: bool) -> Result<()> {
+            self.ensure_role(Role::Admin)?;
+            self.ensure_no_council()?;
+            self.set_prefix_reserved(prefix, reserved)
+        }
+
+        /// Adds a prefix to, or removes it from, the reserved list
+        fn set_prefix_reserved(&mut self, prefix: Vec<u8>, reserved: bool) -> Result<()> {
+            self.reserved_prefixes.retain(|entry| *entry != prefix);
+            if reserved {
+                if prefix.is_empty() || prefix.len() > self.config.max_did_len as usize {
+                    return Err(Error::InvalidConfig);
+                }
+                if self.reserved_prefixes.len() >= MAX_RESERVED_PREFIXES {
+                    return Err(Error::CapacityExceeded);
+                }
+                self.reserved_prefixes.push(prefix.clone());
+            }
+
+            // emit event
+            self.env()
+                .emit_event(PrefixReservationUpdated { prefix, reserved });
+            Ok(())
+        }
+
+        /// Retrieves the reserved DID prefixes
+        #[ink(message, payable)]
+        pub fn get_reserved_prefixes(&self) -> Vec<Vec<u8>> {
+            self.reserved_prefixes.clone()
+        }
+
+        /// Checks whether a DID falls under a reserved prefix
+        fn is_reserved(&self, did: &DID) -> bool {
+            self.reserved_prefixes
+                .iter()
+                .any(|prefix| did.starts_with(prefix))
+        }
+
+        /// Lets `account` register a DID under a reserved prefix, e.g. for a well-known
+        /// service. The allocation is used up once the DID is registered. Admins only, until a
+        /// council is set
+        #[ink(message, payable)]
+        pub fn allocate_did(&mut self, did: DID, account: AccountId) -> Result<()> {
+            self.ensure_role(Role::Admin)?;
+            self.ensure_no_council()?;
+            self.allocate(did, account);
+            Ok(())
+        }
+
+        /// Records the account a reserved DID is allocated to
+        fn allocate(&mut self, did: DID, account: AccountId) {
+            self.did_allocations.insert(&did, &account);
+
+            // emit event
+            self.env().emit_event(DidAllocated { did, account });
+        }
+
+        /// Retrieves the account a reserved DID is allocated to, if it hasn't been registered
+        #[ink(message, payable)]
+        pub fn get_allocation(&self, did: DID) -> Option<AccountId> {
+            self.did_allocations.get(&did)
+        }
+
+        /// Sets the global or a subsystem's pause flag
+        fn set_paused(&mut self, subsystem: Option<Subsystem>, paused: bool) {
+            match subsystem {
+                None => self.paused = paused,
+                Some(subsystem) => {
+                    self.paused_subsystems.retain(|entry| *entry != subsystem);
+                    if paused {
+                        self.paused_subsystems.push(subsystem);
+                    }
+                }
+            }
+
+            // emit event
+            self.env().emit_event(PauseUpdated { subsystem, paused });
+        }
+
+        /// Fails with `Paused` while writes to a subsystem are paused
+        fn ensure_not_paused(&self, subsystem: Subsystem) -> Result<()> {
+            if self.is_paused(subsystem) {
+                return Err(Error::Paused);
+            }
+            Ok(())
+        }
+
+        /// Registers `new_did` with the configuration of an existing application, e.g. for a
+        /// staging copy. The DID document, hashtable CID, auth material, auth policy, controllers
+        /// and subscription approval settings are copied unless overridden; subscribers,
+        /// restrictions, credentials and keys are not. Only the source's controller can clone it
+        #[ink(message, payable)]
+        pub fn clone_app(
+            &mut self,
+            source_did: DID,
+            new_did: DID,
+            overrides: CloneOverrides,
+        ) -> Result<()> {
+            let source = self.authorize(&source_did)?;
+            if source.status == AccountStatus::Suspended {
+                return Err(Error::AccountSuspended);
+            }
+            self.check_new_account(&new_did)?;
+
+            let account = AccountInfo {
+                did_document_uri: source.did_document_uri,
+                hashtable_cid: overrides.hashtable_cid.unwrap_or(source.hashtable_cid),
+                auth_hash: overrides.auth_hash.unwrap_or(source.auth_hash),
+                auth_policy: overrides.auth_policy.or(source.auth_policy),
+                created_at: self.env().block_number(),
+                key_agreement: None,
+                auth_key: None,
+                controllers: source.controllers,
+                status: AccountStatus::Active,
+            };
+            if let Some(request_ttl) = self.subscription_approval.get(&source_did) {
+                self.subscription_approval.insert(&new_did, &request_ttl);
+            }
+            if let Some(policy) = self.replication_policies.get(&source_did) {
+                self.replication_policies.insert(&new_did, &policy);
+            }
+            self.register_account(new_did.clone(), account);
+            self.charge_fee(PaymentOperation::CloneApp, self.config.registration_fee);
+
+            // emit event
+            self.env().emit_event(AppCloned {
+                source_did,
+                did: new_did,
+            });
+            Ok(())
+        }
+
+        /// Schedules a replacement of the contract's code that keeps its storage, so accounts
+        /// and subscriptions survive a new release, and returns the timelock id. The version
+        /// must be newer than the live one. Owner only, until a council is set
+        #[ink(message, payable)]
+        pub fn upgrade(&mut self, code_hash: Hash, version: ContractVersion) -> Result<u32> {
+            self.ensure_owner()?;
+            self.ensure_no_council()?;
+            if version <= self.version {
+                return Err(Error::VersionNotNewer);
+            }
+            self.schedule(CouncilAction::Upgrade(code_hash, version))
+        }
+
+        /// Replaces the contract's code if the version is newer than the live one
+        fn set_code(&mut self, code_hash: Hash, version: ContractVersion) -> Result<()> {
+            if version <= self.version {
+                return Err(Error::VersionNotNewer);
+            }
+            self.env()
+                .set_code_hash(&code_hash)
+                .map_err(|_| Error::UpgradeFailed)?;
+            self.version = version;
+
+            // emit event
+            self.env()
+                .emit_event(ContractUpgraded { code_hash, version });
+            Ok(())
+        }
+
+        /// Retrieves the version of the message set currently live
+        #[ink(message, payable)]
+        pub fn contract_version(&self) -> ContractVersion {
+            self.version
+        }
+
+        /// Installs the first council. Once a council is set, pauses, registration freezes,
+        /// upgrades, parameter changes, timelock cancellations and council replacements need a
+        /// council proposal.
+        /// Clearing the members through a proposal hands those operations back to the owner
+        /// and role holders. Owner only, until a council is set
+        #[ink(message, payable)]
+        pub fn set_council(&mut self, members: Vec<AccountId>, threshold: u32) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_no_council()?;
+            self.replace_council(members, threshold)
+        }
+
+        /// Replaces the council members after checking the threshold is reachable
+        fn replace_council(&mut self, members: Vec<AccountId>, threshold: u32) -> Result<()> {
+            let mut members = members;
+            members.sort();
+            members.dedup();
+            if members.len() > MAX_COUNCIL_MEMBERS {
+                return Err(Error::CapacityExceeded);
+            }
+            if !members.is_empty() && (threshold == 0 || threshold as usize > members.len()) {
+                return Err(Error::InvalidThreshold);
+            }
+            self.council = Council {
+                members: members.clone(),
+                threshold,
+            };
+
+            // emit event
+            self.env().emit_event(CouncilUpdated { members, threshold });
+            Ok(())
+        }
+
+        /// Retrieves the council members and the approvals a proposal needs
+        #[ink(message, payable)]
+        pub fn get_council(&self) -> Council {
+            self.council.clone()
+        }
+
+        /// Submits a privileged operation for council approval and returns its id. The proposer's
+        /// approval is counted. Council members only
+        #[ink(message, payable)]
+        pub fn propose(&mut self, action: CouncilAction) -> Result<u32> {
+            let proposer = self.ensure_council_member()?;
+            let id = self.next_proposal;
+            self.next_proposal = id.checked_add(1).ok_or(Error::CapacityExceeded)?;
+            self.proposals.insert(
+                id,
+                &Proposal {
+                    action: action.clone(),
+                    proposer,
+                    approvals: vec![proposer],
+                },
+            );
+
+            // emit event
+            self.env().emit_event(ProposalSubmitted {
+                id,
+                proposer,
+                action,
+            });
+            Ok(id)
+        }
+
+        /// Approves a pending proposal. Council members only
+        #[ink(message, payable)]
+        pub fn approve_proposal(&mut self, id: u32) -> Result<()> {
+            let member = self.ensure_council_member()?;
+            let mut proposal = self.proposals.get(id).ok_or(Error::ProposalNotFound)?;
+            if proposal.approvals.contains(&member) {
+                return Err(Error::AlreadyExists);
+            }
+            proposal.approvals.push(member);
+            self.proposals.insert(id, &proposal);
+
+            // emit event
+            self.env().emit_event(ProposalApproved { id, member });
+            Ok(())
+        }
+
+        /// Executes a proposal once enough current council members approved it. Approvals of
+        /// members who have since left the council don't count. Council members only
+        #[ink(message, payable)]
+        pub fn execute_proposal(&mut self, id: u32) -> Result<()> {
+            self.ensure_council_member()?;
+            let proposal = self.proposals.get(id).ok_or(Error::ProposalNotFound)?;
+            let approvals = proposal
+                .approvals
+                .iter()
+                .filter(|member| self.council.members.contains(member))
+                .count();
+            if approvals < self.council.threshold as usize {
+                return Err(Error::ProposalNotReady);
+            }
+            self.proposals.remove(id);
+
+            // emergency actions apply at once, upgrades and parameter changes are timelocked
+            match proposal.action {
+                action @ (CouncilAction::Upgrade(..) | CouncilAction::UpdateConfig(_)) => {
+                    self.schedule(action)?;
+                }
+                action => self.apply_action(action)?,
+            }
+
+            // emit event
+            self.env().emit_event(ProposalExecuted { id });
+            Ok(())
+        }
+
+        /// Applies a privileged operation
+        fn apply_action(&mut self, action: CouncilAction) -> Result<()> {
+            match action {
+                CouncilAction::Pause(subsystem) => self.set_paused(subsystem, true),
+                CouncilAction::Unpause(subsystem) => self.set_paused(subsystem, false),
+                CouncilAction::Upgrade(code_hash, version) => self.set_code(code_hash, version)?,
+                CouncilAction::FreezeRegistrations(frozen) => self.set_registrations_frozen(frozen),
+                CouncilAction::UpdateConfig(config) => self.set_config(config)?,
+                CouncilAction::Slash(addr, reason) => {
+                    self.slash_node(addr, reason)?;
+                }
+                CouncilAction::Blacklist(addr) => self.set_blacklisted(addr, true),
+                CouncilAction::Unblacklist(addr) => self.set_blacklisted(addr, false),
+                CouncilAction::SetCouncil(members, threshold) => {
+                    self.replace_council(members, threshold)?
+                }
+                CouncilAction::DeclareCompromise(did, evidence) => {
+                    self.mark_compromised(did, evidence)?
+                }
+                CouncilAction::ClearCompromise(did) => self.unmark_compromised(did)?,
+                CouncilAction::CancelScheduled(id) => self.drop_scheduled(id)?,
+                CouncilAction::SetReservedPrefix(prefix, reserved) => {
+                    self.set_prefix_reserved(prefix, reserved)?
+                }
+                CouncilAction::AllocateDid(did, account) => self.allocate(did, account),
+                CouncilAction::AdjudicateReport(id, upheld) => self.resolve_report(id, upheld)?,
+            }
+            Ok(())
+        }
+
+        /// Queues a privileged operation behind the timelock delay and returns its id
+        fn schedule(&mut self, action: CouncilAction) -> Result<u32> {
+            let id = self.next_timelock_id;
+            self.next_timelock_id = id.checked_add(1).ok_or(Error::CapacityExceeded)?;
+            let executable_at = self
+                .env()
+                .block_number()
+                .saturating_add(self.config.timelock_delay);
+            self.timelock.insert(
+                id,
+                &TimelockedAction {
+                    action: action.clone(),
+                    executable_at,
+                },
+            );
+
+            // emit event
+            self.env().emit_event(Scheduled {
+                id,
+                action,
+                executable_at,
+            });
+            Ok(id)
+        }
+
+        /// Applies a scheduled operation once its timelock delay has passed. Anyone can
+        /// trigger it, since the operation was authorized when it was scheduled
+        #[ink(message, payable)]
+        pub fn execute_scheduled(&mut self, id: u32) -> Result<()> {
+            let scheduled = self.timelock.get(id).ok_or(Error::OperationNotFound)?;
+            if self.env().block_number() < scheduled.executable_at {
+                return Err(Error::OperationNotReady);
+            }
+            self.timelock.remove(id);
+            self.apply_action(scheduled.action)?;
+
+            // emit event
+            self.env().emit_event(ScheduledExecuted { id });
+            Ok(())
+        }
+
+        /// Drops a scheduled operation before it is executed. Owner only, until a council is set
+        #[ink(message, payable)]
+        pub fn cancel_scheduled(&mut self, id: u32) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_no_council()?;
+            self.drop_scheduled(id)
+        }
+
+        /// Removes an operation from the timelock queue
+        fn drop_scheduled(&mut self, id: u32) -> Result<()> {
+            if self.timelock.take(id).is_none() {
+                return Err(Error::OperationNotFound);
+            }
+
+            // emit event
+            self.env().emit_event(ScheduledCancelled { id });
+            Ok(())
+        }
+
+        /// Retrieves an operation waiting out the timelock delay
+        #[ink(message, payable)]
+        pub fn get_scheduled(&self, id: u32) -> Option<TimelockedAction> {
+            self.timelock.get(id)
+        }
+
+        /// Retrieves a proposal awaiting approval or execution
+        #[ink(message, payable)]
+        pub fn get_proposal(&self, id: u32) -> Option<Proposal> {
+            self.proposals.get(id)
+        }
+
+        /// Fails with `CouncilRequired` once a council shares control of privileged operations
+        fn ensure_no_council(&self) -> Result<()> {
+            if !self.council.members.is_empty() {
+                return Err(Error::CouncilRequired);
+            }
+            Ok(())
+        }
+
+        /// Returns the caller after checking that they sit on the council
+        fn ensure_council_member(&self) -> Result<AccountId> {
+            let caller = self.env().caller();
+            if !self.council.members.contains(&caller) {
+                return Err(Error::Unauthorized);
+            }
+            Ok(caller)
+        }
+
+        /// Registers a contract to be called with `(DID, RegistryChange)` at `selector` whenever
+        /// the DID's hashtable CID changes, a node stops supporting it or its control changes
+        /// hands. A contract controlling the DID, such as a DAO's governance contract, can
+        /// register itself to be told when it gains or loses control. Controllers only
+        #[ink(message, payable)]
+        pub fn add_observer(
+            &mut self,
+            did: DID,
+            contract: AccountId,
+            selector: [u8; 4],
+        ) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            self.authorize(&did)?;
+            let mut observers = self.observers.get(&did).unwrap_or_default();
+            if observers
+                .iter()
+                .any(|observer| observer.contract == contract)
+            {
+                return Err(Error::AlreadyExists);
+            }
+            if observers.len() >= MAX_OBSERVERS {
+                return Err(Error::CapacityExceeded);
+            }
+            observers.push(Observer { contract, selector });
+            self.observers.insert(&did, &observers);
+
+            // emit event
+            self.env().emit_event(ObserverAdded { did, contract });
+            Ok(())
+        }
+
+        /// Stops notifying a contract of changes to a DID. Controllers only
+        #[ink(message, payable)]
+        pub fn remove_observer(&mut self, did: DID, contract: AccountId) -> Result<()> {
+            self.ensure_not_paused(Subsystem::Accounts)?;
+            self.authorize(&did)?;
+            let mut observers = self.observers.get(&did).unwrap_or_default();
+            if !observers
+                .iter()
+                .any(|observer| observer.contract == contract)
+            {
+                return Err(Error::ObserverNotFound);
+            }
+            observers.retain(|observer| observer.contract != contract);
+            self.observers.insert(&did, &observers);
+
+            // emit event
+            self.env().emit_event(ObserverRemoved { did, contract });
+            Ok(())
+        }
+
+        /// Retrieves the contracts notified of changes to a DID
+        #[ink(message, payable)]
+        pub fn get_observers(&self, did: DID) -> Vec<Observer> {
+            self.observers.get(&did).unwrap_or_default()
+        }
+
+        /// Calls each observer of a DID with a change. Every call is bounded by
+        /// `OBSERVER_GAS_LIMIT`, and a failing observer is reported instead of reverting
+        fn notify_observers(&self, did: &DID, change: RegistryChange) {
+            for observer in self.observers.get(did).unwrap_or_default() {
+                let result = build_call::<Environment>()
+                    .call(observer.contract)
+                    .gas_limit(OBSERVER_GAS_LIMIT)
+                    .exec_input(
+                        ExecutionInput::new(Selector::new(observer.selector))
+                            .push_arg(did)
+                            .push_arg(&change),
+                    )
+                    .returns::<()>()
+                    .try_invoke();
+                if !matches!(result, Ok(Ok(()))) {
+                    self.env().emit_event(ObserverCallFailed {
+                        did: did.clone(),
+                        contract: observer.contract,
+                    });
+                }
+            }
+        }
+
+        /// Imports accounts exported from a previous contract instance, indexing them as if
+        /// they had been created here. Fails on DIDs that already exist. Owner only, until
+        /// `finalize_migration` is called
+        #[ink(message, payable)]
+        pub fn import_accounts(&mut self, accounts: Vec<(DID, AccountInfo)>) -> Result<()> {
+            self.ensure_migrating()?;
+            for (did, account) in accounts {
+                if self.accounts.contains(&did) {
+                    return Err(Error::AlreadyExists);
+                }
+                self.register_account(did, account);
+            }
+            Ok(())
+        }
+
+        /// Imports the nodes supporting each application, each with the account that subscribed
+        /// it and the block it joined at. Entries go through the same checks as `subscribe_node`
+        /// for that account, and the import fails on the first one rejected. Nodes already
+        /// subscribed are skipped. Owner only, until `finalize_migration` is called
+        #[ink(message, payable)]
+        pub fn import_subscribers(
+            &mut self,
+            subscribers: Vec<(DID, Vec<ImportedSubscriber>)>,
+        ) -> Result<()> {
+            self.ensure_migrating()?;
+            let now = self.env().block_number();
+            for (did, nodes) in subscribers {
+                for entry in nodes {
+                    let addr = entry.node;
+                    if self
+                        .subscribers
+                        .get(&did)
+                        .unwrap_or_default()
+                        .contains(&addr)
+                    {
+                        continue;
+                    }
+                    self.check_subscriber(&did, &addr, &entry.owner)?;
+                    self.add_subscriber(did.clone(), addr.clone(), entry.owner);
+                    // keep the tenure earned on the previous instance
+                    self.subscribed_at
+                        .insert((&did, &addr), &entry.subscribed_at.min(now));
+                }
+            }
+            Ok(())
+        }
+
+        /// Imports the users that restricted each application. Existing restrictions are
+        /// skipped. Owner only, until `finalize_migration` is called
+        #[ink(message, payable)]
+        pub fn import_restrictions(&mut self, restrictions: Vec<(DID, Vec<DID>)>) -> Result<()> {
+            self.ensure_migrating()?;
+            for (app_did, users) in restrictions {
+                for user_did in users {
+                    let restricted = self.restricted.get(&app_did).unwrap_or_default();
+                    if !restricted.contains(&user_did) {
+                        self.add_restriction(user_did, app_did.clone());
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        /// Disables the import messages permanently once the previous instance's state has
+        /// been carried over. Owner only
+        #[ink(message, payable)]
+        pub fn finalize_migration(&mut self) -> Result<()> {
+            self.ensure_migrating()?;
+            self.migration_finalized = true;
+
+            // emit event
+            self.env().emit_event(MigrationFinalized {});
+            Ok(())
+        }
+
+        /// Checks if state imports have been disabled
+        #[ink(message, payable)]
+        pub fn migration_finalized(&self) -> bool {
+            self.migration_finalized
+        }
+
+        /// Checks that the caller is the owner and imports are still allowed
+        fn ensure_migrating(&self) -> Result<()> {
+            self.ensure_owner()?;
+            if self.migration_finalized {
+                return Err(Error::MigrationFinalized);
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[ink::test]
+        fn add_address_works() {
+            let mut db = DbContract::new();
+            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            db.add_address(addr.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                .unwrap();
+
+            // test for equality
+            assert_eq!(db.get_node_addresses(DEFAULT_NETWORK_ID), vec![addr]);
+        }
+
+        #[ink::test]
+        fn network_tagging_works() {
+            let mut db = DbContract::with_network_id(7);
+            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            assert_eq!(db.network_id(), 7);
+
+            // registrations for another network are rejected
+            assert_eq!(
+                db.add_address(addr.clone(), 8, NodeRole::BOOTNODE),
+                Err(Error::NetworkMismatch)
+            );
+            assert!(db.get_node_addresses(7).is_empty());
+
+            db.add_address(addr.clone(), 7, NodeRole::BOOTNODE).unwrap();
+            assert_eq!(db.get_node_addresses(7), vec![addr]);
+
+            // discovery for another network comes back empty
+            assert!(db.get_node_addresses(8).is_empty());
+        }
+
+        #[ink::test]
+        fn creation_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            // let ht_cid = "Qmsdujfhsd8sg8s8483nhn10vddfi".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            db.new_account(
+                did.clone(),
+                cid.clone(),
+                /* authentication material */ hash_auth_material(&auth_material),
+            )
+            .unwrap();
+
+            assert_eq!(
+                db.get_account_ht_cid(did.clone(), auth_material.clone()),
+                cid
+            );
+
+            // the whole account comes back in one call
+            let info = db.get_account_info(did.clone(), auth_material).unwrap();
+            assert_eq!(info.hashtable_cid, cid);
+            assert_eq!(info.created_at, 0);
+            assert_eq!(db.get_account_info(did, b"wrong".to_vec()), None);
+        }
+
+        #[ink::test]
+        fn subscribing_flow_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+
+            // subscribe
+            db.subscribe_node(did.clone(), addr.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+
+            // get subscribers
+            assert_eq!(
+                db.get_subscribers(did.clone(), DEFAULT_NETWORK_ID),
+                vec![addr.clone()]
+            );
+            assert_eq!(
+                db.get_node_subscriptions(addr.clone(), DEFAULT_NETWORK_ID),
+                vec![did.clone()]
+            );
+
+            // page through subscribers
+            let second = "/ip4/192.168.44.206/tcp/1509".as_bytes().to_vec();
+            db.subscribe_node(did.clone(), second.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            assert_eq!(
+                db.get_subscribers_paged(did.clone(), DEFAULT_NETWORK_ID, 1, 5),
+                (vec![second.clone()], 2)
+            );
+            assert_eq!(
+                db.get_subscribers_paged(did.clone(), DEFAULT_NETWORK_ID, 2, 5),
+                (vec![], 2)
+            );
+            db.unsubscribe_node(did.clone(), second).unwrap();
+
+            // delete subscribers
+            db.unsubscribe_node(did.clone(), addr.clone()).unwrap();
+            assert!(db
+                .get_subscribers(did.clone(), DEFAULT_NETWORK_ID)
+                .is_empty());
+            assert!(db
+                .get_node_subscriptions(addr.clone(), DEFAULT_NETWORK_ID)
+                .is_empty());
+        }
+
+        #[ink::test]
+        fn subscription_tenure_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let first = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let second = "/ip4/192.168.44.206/tcp/1509".as_bytes().to_vec();
+
+            db.subscribe_node(did.clone(), first.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            for _ in 0..3 {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            db.subscribe_node(did.clone(), second.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+
+            // resubscribing doesn't reset tenure
+            db.subscribe_node(did.clone(), first.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+
+            assert_eq!(
+                db.get_subscribers_with_tenure(did.clone(), DEFAULT_NETWORK_ID),
+                vec![(first.clone(), 4), (second.clone(), 1)]
+            );
+
+            // leaving and rejoining starts over
+            db.unsubscribe_node(did.clone(), first.clone()).unwrap();
+            db.subscribe_node(did.clone(), first.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            assert_eq!(
+                db.get_subscribers_with_tenure(did, DEFAULT_NETWORK_ID),
+                vec![(second, 1), (first, 0)]
+            );
+        }
+
+        #[ink::test]
+        fn access_control_flow_works() {
+            let mut db = DbContract::new();
+
+            // create user
+            let did = "did:sam:user:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+
+            db.new_account(
+                did.clone(),
+                cid.clone(),
+                /* authentication material */ hash_auth_material(&auth_material),
+            )
+            .unwrap();
+
+            // create application
+            let app_did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+
+            let app_cid = "Qmjhggfztfiov7zfbvyzhiuW8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+
+            let app_auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+
+            db.new_account(
+                app_did.clone(),
+                app_cid.clone(),
+                /* authentication material */ hash_auth_material(&app_auth_material),
+            )
+            .unwrap();
+
+            // restrict app access
+            db.restrict(did.clone(), app_did.clone()).unwrap();
+
+            // check for restrictions
+            assert!(db.is_restricted(did.clone(), app_did.clone()));
+            assert_eq!(
+                db.get_restriction_list(app_did.clone(), None),
+                vec![did.clone()]
+            );
+            assert_eq!(
+                db.get_restriction_list(app_did.clone(), Some(did.clone())),
+                vec![did.clone()]
+            );
+            assert!(db
+                .get_restriction_list(app_did.clone(), Some(app_did.clone()))
+                .is_empty());
+
+            // unrestrict and check again
+            db.unrestrict(did.clone(), app_did.clone()).unwrap();
+
+            // check for restrictions
+            assert!(!db.is_restricted(did.clone(), app_did.clone()));
+            assert!(db.get_restriction_list(app_did.clone(), None).is_empty());
+
+            // batch checks answer per user
+            db.restrict(did.clone(), app_did.clone()).unwrap();
+            assert_eq!(
+                db.are_restricted(vec![did.clone(), app_did.clone()], app_did),
+                vec![true, false]
+            );
+        }
+
+        #[ink::test]
+        fn key_version_tracking_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:user:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let app_did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+            db.new_account(
+                app_did.clone(),
+                cid.clone(),
+                hash_auth_material(&auth_material),
+            )
+            .unwrap();
+            assert_eq!(db.key_version(did.clone(), app_did.clone()), 0);
+
+            // every change in restriction state rotates the key
+            db.restrict(did.clone(), app_did.clone()).unwrap();
+            assert_eq!(db.key_version(did.clone(), app_did.clone()), 1);
+
+            db.unrestrict(did.clone(), app_did.clone()).unwrap();
+            assert_eq!(db.key_version(did.clone(), app_did.clone()), 2);
+
+            // other pairs are unaffected
+            assert_eq!(db.key_version(app_did, did), 0);
+        }
+
+        #[ink::test]
+        fn epoch_info_works() {
+            let db = DbContract::new();
+            let genesis = ink::env::block_number::<ink::env::DefaultEnvironment>();
+
+            let info = db.epoch_info();
+            assert_eq!(info.index, 0);
+            assert_eq!(info.start_block, genesis);
+            assert_eq!(info.remaining_blocks, DEFAULT_EPOCH_LENGTH);
+            assert_eq!(info.epoch_length, DEFAULT_EPOCH_LENGTH);
+
+            // move into the next epoch
+            for _ in 0..DEFAULT_EPOCH_LENGTH + 1 {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+
+            let info = db.epoch_info();
+            assert_eq!(db.current_epoch(), 1);
+            assert_eq!(info.start_block, genesis + DEFAULT_EPOCH_LENGTH);
+            assert_eq!(info.remaining_blocks, DEFAULT_EPOCH_LENGTH - 1);
+        }
+
+        #[ink::test]
+        fn withdraw_balance_works() {
+            let mut db = DbContract::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let contract = ink::env::account_id::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_account_balance::<ink::env::DefaultEnvironment>(contract, 1_000);
+            ink::env::test::set_account_balance::<ink::env::DefaultEnvironment>(accounts.bob, 0);
+
+            // credit bob, then withdraw as bob
+            db.credit(accounts.bob, 250);
+            assert_eq!(db.claimable_balance(accounts.bob), 250);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(db.withdraw_balance(), Ok(250));
+            assert_eq!(db.claimable_balance(accounts.bob), 0);
+            assert_eq!(
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.bob),
+                Ok(250)
+            );
+
+            // nothing left to claim
+            assert_eq!(db.withdraw_balance(), Ok(0));
+        }
+
+        #[ink::test]
+        fn transport_discovery_works() {
+            let mut db = DbContract::new();
+            let tcp = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let ws = "/dns4/node.samaritan.io/tcp/443/wss".as_bytes().to_vec();
+            let quic = "/ip4/192.168.44.206/udp/1509/quic-v1".as_bytes().to_vec();
+            let webrtc = "/ip4/192.168.44.207/udp/1510/webrtc-direct/certhash/uEiA"
+                .as_bytes()
+                .to_vec();
+
+            for addr in [&tcp, &ws, &quic, &webrtc] {
+                db.add_address(addr.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                    .unwrap();
+            }
+
+            assert_eq!(
+                db.get_nodes_by_transport(DEFAULT_NETWORK_ID, Transport::WebSocket, 10),
+                vec![ws]
+            );
+            assert_eq!(
+                db.get_nodes_by_transport(DEFAULT_NETWORK_ID, Transport::WebRtc, 10),
+                vec![webrtc]
+            );
+            assert_eq!(
+                db.get_nodes_by_transport(DEFAULT_NETWORK_ID, Transport::Quic, 10),
+                vec![quic]
+            );
+
+            // the limit bounds the result
+            assert!(db
+                .get_nodes_by_transport(DEFAULT_NETWORK_ID, Transport::Tcp, 0)
+                .is_empty());
+            assert_eq!(
+                db.get_nodes_by_transport(DEFAULT_NETWORK_ID, Transport::Tcp, 1),
+                vec![tcp]
+            );
+        }
+
+        #[ink::test]
+        fn region_discovery_works() {
+            let mut db = DbContract::new();
+            let paris = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let ohio = "/ip4/192.168.44.206/tcp/1509".as_bytes().to_vec();
+            let dublin = "/ip4/192.168.44.207/tcp/1509".as_bytes().to_vec();
+            for (addr, region) in [
+                (&paris, "eu-west"),
+                (&ohio, "us-east"),
+                (&dublin, "eu-west"),
+            ] {
+                db.add_address(addr.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                    .unwrap();
+                db.set_node_metadata(addr.clone(), region.as_bytes().to_vec(), 100)
+                    .unwrap();
+            }
+
+            // nodes in the region come first
+            assert_eq!(
+                db.get_nodes_by_region(DEFAULT_NETWORK_ID, b"eu-west".to_vec(), 2),
+                vec![paris.clone(), dublin.clone()]
+            );
+
+            // a thin region is topped up from the global list
+            assert_eq!(
+                db.get_nodes_by_region(DEFAULT_NETWORK_ID, b"us-east".to_vec(), 2),
+                vec![ohio.clone(), paris.clone()]
+            );
+            assert_eq!(
+                db.get_nodes_by_region(DEFAULT_NETWORK_ID, b"ap-south".to_vec(), 10),
+                vec![paris, ohio, dublin]
+            );
+            assert!(db
+                .get_nodes_by_region(DEFAULT_NETWORK_ID, b"eu-west".to_vec(), 0)
+                .is_empty());
+        }
+
+        #[ink::test]
+        fn auth_policy_works() {
+            let mut db = DbContract::new();
+            let app_did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let policy_cid = "QmPo1icyfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let policy_hash = Hash::from([7; 32]);
+
+            // unknown applications can't set a policy
+            assert_eq!(
+                db.set_auth_policy(app_did.clone(), policy_cid.clone(), policy_hash),
+                Err(Error::AccountNotFound)
+            );
+            assert_eq!(db.get_auth_policy(app_did.clone()), None);
+
+            db.new_account(
+                app_did.clone(),
+                "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                    .as_bytes()
+                    .to_vec(),
+                hash_auth_material("bfdh87y*(TD*&^*S&io".as_bytes()),
+            )
+            .unwrap();
+            assert_eq!(db.get_auth_policy(app_did.clone()), None);
+
+            db.set_auth_policy(app_did.clone(), policy_cid.clone(), policy_hash)
+                .unwrap();
+            assert_eq!(
+                db.get_auth_policy(app_did),
+                Some(AuthPolicy {
+                    policy_cid,
+                    policy_hash
+                })
+            );
+        }
+
+        #[ink::test]
+        fn compromise_declaration_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let new_cid = "Qmjhggfztfiov7zfbvyzhiuW8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let evidence = "QmEvidencefexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5q"
+                .as_bytes()
+                .to_vec();
+
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+
+            // only a controller can declare
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            assert_eq!(
+                db.declare_compromise(did.clone(), evidence.clone()),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert!(!db.is_compromised(did.clone()));
+
+            db.transfer_did(did.clone(), accounts.eve).unwrap();
+            db.declare_compromise(did.clone(), evidence).unwrap();
+            assert!(db.is_compromised(did.clone()));
+
+            // the account is frozen, including offers made before the declaration
+            assert_eq!(
+                db.update_account_ht_cid(did.clone(), new_cid),
+                Err(Error::AccountCompromised)
+            );
+            assert_eq!(
+                db.rotate_auth_material(did.clone(), Hash::from([1; 32])),
+                Err(Error::AccountCompromised)
+            );
+            assert_eq!(
+                db.get_account_ht_cid(did.clone(), auth_material.clone()),
+                cid
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            assert_eq!(db.claim_did(did.clone()), Err(Error::AccountCompromised));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            // guardians and the council can declare on the account's behalf
+            let user = "did:sam:user:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let guardian = "did:sam:user:F9S0DF9S0DS0SD9F0S".as_bytes().to_vec();
+            db.new_account(
+                user.clone(),
+                cid.clone(),
+                hash_auth_material(&auth_material),
+            )
+            .unwrap();
+            let second_guardian = "did:sam:user:0SD9F0SF9S0DF9S0DS".as_bytes().to_vec();
+            db.set_guardians(
+                user.clone(),
+                vec![guardian.clone(), second_guardian.clone()],
+                2,
+            )
+            .unwrap();
+            for (did, controller) in [
+                (&guardian, accounts.bob),
+                (&second_guardian, accounts.frank),
+            ] {
+                ink::env::test::set_caller::<ink::env::DefaultEnvironment>(controller);
+                db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                    .unwrap();
+            }
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                db.declare_compromise_as_guardian(guardian.clone(), user.clone(), Vec::new()),
+                Err(Error::Unauthorized)
+            );
+
+            // a single guardian can't freeze the account below the threshold
+            db.declare_compromise_as_guardian(user.clone(), guardian.clone(), Vec::new())
+                .unwrap();
+            assert_eq!(
+                db.declare_compromise_as_guardian(user.clone(), guardian.clone(), Vec::new()),
+                Err(Error::AlreadyExists)
+            );
+            assert!(!db.is_compromised(user.clone()));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.frank);
+            db.declare_compromise_as_guardian(user.clone(), second_guardian, Vec::new())
+                .unwrap();
+            assert!(db.is_compromised(user.clone()));
+
+            // admins lift a declaration, e.g. on an account without guardians to recover it
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(db.clear_compromise(did.clone()), Err(Error::Unauthorized));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.clear_compromise(did.clone()).unwrap();
+            assert!(!db.is_compromised(did.clone()));
+            assert_eq!(db.clear_compromise(did.clone()), Err(Error::NotCompromised));
+            db.update_account_ht_cid(did.clone(), b"QmRestored".to_vec())
+                .unwrap();
+
+            db.set_council(vec![accounts.charlie], 1).unwrap();
+            assert_eq!(
+                db.clear_compromise(user.clone()),
+                Err(Error::CouncilRequired)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let id = db
+                .propose(CouncilAction::DeclareCompromise(
+                    guardian.clone(),
+                    Vec::new(),
+                ))
+                .unwrap();
+            db.execute_proposal(id).unwrap();
+            assert!(db.is_compromised(guardian));
+            let id = db
+                .propose(CouncilAction::ClearCompromise(user.clone()))
+                .unwrap();
+            db.execute_proposal(id).unwrap();
+            assert!(!db.is_compromised(user));
+        }
+
+        #[ink::test]
+        fn access_token_flow_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:user:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let app_did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+            db.new_account(
+                app_did.clone(),
+                cid.clone(),
+                hash_auth_material(&auth_material),
+            )
+            .unwrap();
+
+            // only the application's controller can mint
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            assert_eq!(
+                db.mint_access_token(app_did.clone(), did.clone(), 0b01, 5),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            let (token_id, commitment) = db
+                .mint_access_token(app_did.clone(), did.clone(), 0b01, 5)
+                .unwrap();
+            assert_eq!(
+                db.get_access_token(token_id).unwrap().commitment,
+                commitment
+            );
+            assert!(db.verify_token(token_id, 0b01));
+            assert!(!db.verify_token(token_id, 0b11));
+
+            // restricting the application invalidates its tokens
+            db.restrict(did.clone(), app_did.clone()).unwrap();
+            assert!(!db.verify_token(token_id, 0b01));
+            db.unrestrict(did.clone(), app_did.clone()).unwrap();
+            assert!(db.verify_token(token_id, 0b01));
+
+            // tokens expire after their ttl
+            for _ in 0..6 {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            assert!(!db.verify_token(token_id, 0b01));
+
+            // revoked tokens fail verification
+            let (token_id, _) = db
+                .mint_access_token(app_did.clone(), did.clone(), 0b01, 5)
+                .unwrap();
+            assert!(db.verify_token(token_id, 0b01));
+            db.revoke_token(token_id).unwrap();
+            assert!(!db.verify_token(token_id, 0b01));
+
+            // so do tokens of deactivated or deleted applications
+            let (token_id, _) = db
+                .mint_access_token(app_did.clone(), did.clone(), 0b01, 5)
+                .unwrap();
+            db.deactivate(app_did.clone()).unwrap();
+            assert!(!db.verify_token(token_id, 0b01));
+
+            // and neither side can be deactivated when minting
+            assert_eq!(
+                db.mint_access_token(app_did.clone(), did.clone(), 0b01, 5),
+                Err(Error::AccountDeactivated)
+            );
+            db.reactivate(app_did.clone()).unwrap();
+            db.deactivate(did.clone()).unwrap();
+            assert_eq!(
+                db.mint_access_token(app_did.clone(), did.clone(), 0b01, 5),
+                Err(Error::AccountDeactivated)
+            );
+            db.reactivate(did.clone()).unwrap();
+
+            // the token id counter doesn't wrap
+            db.next_token_id = TokenId::MAX;
+            assert_eq!(
+                db.mint_access_token(app_did.clone(), did, 0b01, 5),
+                Err(Error::CapacityExceeded)
+            );
+            assert!(db.verify_token(token_id, 0b01));
+            db.delete_account(app_did).unwrap();
+            assert!(!db.verify_token(token_id, 0b01));
+        }
+
+        #[ink::test]
+        fn prefix_enumeration_works() {
+            let mut db = DbContract::new();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+
+            for did in [
+                "did:sam:apps:first",
+                "did:sam:user:alice",
+                "did:sam:apps:second",
+                "did:sam:apps:third",
+            ] {
+                db.new_account(
+                    did.as_bytes().to_vec(),
+                    cid.clone(),
+                    hash_auth_material(&auth_material),
+                )
+                .unwrap();
+            }
+
+            // re-registering doesn't duplicate the index entry
+            db.reregister_account(
+                b"did:sam:apps:first".to_vec(),
+                cid,
+                hash_auth_material(&auth_material),
+            )
+            .unwrap();
+
+            assert_eq!(
+                db.get_dids_by_prefix(b"did:sam:apps".to_vec(), 0, 10),
+                vec![
+                    b"did:sam:apps:first".to_vec(),
+                    b"did:sam:apps:second".to_vec(),
+                    b"did:sam:apps:third".to_vec()
+                ]
+            );
+            assert_eq!(
+                db.get_dids_by_prefix(b"did:sam:apps".to_vec(), 1, 1),
+                vec![b"did:sam:apps:second".to_vec()]
+            );
+            assert_eq!(
+                db.get_dids_by_prefix(b"did:sam:user".to_vec(), 0, 10),
+                vec![b"did:sam:user:alice".to_vec()]
+            );
+
+            // deleting a DID moves the last DID of its segment into its place
+            db.delete_account(b"did:sam:apps:first".to_vec()).unwrap();
+            assert_eq!(
+                db.get_dids_by_prefix(b"did:sam:apps".to_vec(), 0, 10),
+                vec![
+                    b"did:sam:apps:third".to_vec(),
+                    b"did:sam:apps:second".to_vec()
+                ]
+            );
+            db.delete_account(b"did:sam:apps:second".to_vec()).unwrap();
+            db.delete_account(b"did:sam:apps:third".to_vec()).unwrap();
+            assert!(db
+                .get_dids_by_prefix(b"did:sam:apps".to_vec(), 0, 10)
+                .is_empty());
+        }
+
+        #[ink::test]
+        fn snapshot_export_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:user:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let app_did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+            db.new_account(
+                app_did.clone(),
+                cid.clone(),
+                hash_auth_material(&auth_material),
+            )
+            .unwrap();
+            db.subscribe_node(app_did.clone(), addr.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            db.restrict(did.clone(), app_did.clone()).unwrap();
+            assert_eq!(db.did_count(), 2);
+
+            // accounts are exported page by page
+            let page: Vec<(DID, AccountInfo)> =
+                scale::Decode::decode(&mut &db.export_accounts(1, 5)[..]).unwrap();
+            assert_eq!(page.len(), 1);
+            assert_eq!(page[0].0, app_did);
+            assert_eq!(page[0].1.hashtable_cid, cid);
+
+            let subscriptions: Vec<(DID, Vec<ImportedSubscriber>)> =
+                scale::Decode::decode(&mut &db.export_subscriptions(0, 5)[..]).unwrap();
+            assert_eq!(
+                subscriptions,
+                vec![(
+                    app_did.clone(),
+                    vec![ImportedSubscriber {
+                        node: addr,
+                        owner: ink::env::test::default_accounts::<ink::env::DefaultEnvironment>()
+                            .alice,
+                        subscribed_at: 0,
+                    }]
+                )]
+            );
+
+            let restrictions: Vec<(DID, Vec<DID>)> =
+                scale::Decode::decode(&mut &db.export_restrictions(0, 5)[..]).unwrap();
+            assert_eq!(restrictions, vec![(app_did, vec![did.clone()])]);
+
+            // paging past the end yields an empty chunk
+            let page: Vec<(DID, AccountInfo)> =
+                scale::Decode::decode(&mut &db.export_accounts(2, 5)[..]).unwrap();
+            assert!(page.is_empty());
+
+            // a deleted DID registered again keeps its single index entry
+            db.delete_account(did.clone()).unwrap();
+            db.new_account(did.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+            assert_eq!(db.did_count(), 2);
+            let page: Vec<(DID, AccountInfo)> =
+                scale::Decode::decode(&mut &db.export_accounts(0, 5)[..]).unwrap();
+            assert_eq!(page.len(), 2);
+        }
+
+        #[cfg(feature = "did-anchor")]
+        #[ink::test]
+        fn did_anchoring_works() {
+            struct MockDidAnchor {
+                status: u32,
+            }
+
+            impl ink::env::test::ChainExtension for MockDidAnchor {
+                fn func_id(&self) -> u32 {
+                    0x5344_0001
+                }
+
+                fn call(&mut self, _input: &[u8], _output: &mut Vec<u8>) -> u32 {
+                    self.status
+                }
+            }
+
+            // off-chain deployments share storage, so each one registers its own DID
+            let dids = [
+                b"did:sam:apps:first".to_vec(),
+                b"did:sam:apps:second".to_vec(),
+                b"did:sam:apps:third".to_vec(),
+            ];
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+
+            // deployments without anchoring never call the extension
+            let mut db = DbContract::new();
+            db.new_account(
+                dids[0].clone(),
+                cid.clone(),
+                hash_auth_material(&auth_material),
+            )
+            .unwrap();
+            assert_eq!(ink::env::test::recorded_events().count(), 2);
+
+            // a successful anchor emits no failure event
+            ink::env::test::register_chain_extension(MockDidAnchor { status: 0 });
+            let mut db = DbContract::with_did_anchoring(DEFAULT_NETWORK_ID);
+            db.new_account(
+                dids[1].clone(),
+                cid.clone(),
+                hash_auth_material(&auth_material),
+            )
+            .unwrap();
+            assert_eq!(ink::env::test::recorded_events().count(), 4);
+
+            // a runtime that can't anchor doesn't block registration
+            ink::env::test::register_chain_extension(MockDidAnchor { status: 1 });
+            let mut db = DbContract::with_did_anchoring(DEFAULT_NETWORK_ID);
+            db.new_account(
+                dids[2].clone(),
+                cid.clone(),
+                hash_auth_material(&auth_material),
+            )
+            .unwrap();
+            assert_eq!(ink::env::test::recorded_events().count(), 7);
+            assert_eq!(db.get_account_ht_cid(dids[2].clone(), auth_material), cid);
+        }
+
+        #[ink::test]
+        fn restriction_receipts_work() {
+            let mut db = DbContract::new();
+            let did = "did:sam:user:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let app_did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let first = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let second = "/ip4/192.168.44.206/tcp/1509".as_bytes().to_vec();
+            let outsider = "/ip4/192.168.44.207/tcp/1509".as_bytes().to_vec();
+
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+            db.new_account(app_did.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+            db.subscribe_node(app_did.clone(), first.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            db.subscribe_node(app_did.clone(), second.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            db.restrict(did.clone(), app_did.clone()).unwrap();
+
+            // only the accounts acting for subscribed nodes can acknowledge, and only once
+            assert_eq!(
+                db.ack_restriction(app_did.clone(), did.clone(), outsider),
+                Err(Error::NotSubscribed)
+            );
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            assert_eq!(
+                db.ack_restriction(app_did.clone(), did.clone(), first.clone()),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.ack_restriction(app_did.clone(), did.clone(), first.clone())
+                .unwrap();
+            db.ack_restriction(app_did.clone(), did.clone(), first.clone())
+                .unwrap();
+            assert_eq!(
+                db.restriction_ack_status(app_did.clone(), did.clone()),
+                AckStatus {
+                    key_version: 1,
+                    acknowledged: 1,
+                    replicas: 2
+                }
+            );
+
+            db.ack_restriction(app_did.clone(), did.clone(), second)
+                .unwrap();
+            let status = db.restriction_ack_status(app_did.clone(), did.clone());
+            assert_eq!(status.acknowledged, status.replicas);
+
+            // a new change needs fresh acknowledgements
+            db.unrestrict(did.clone(), app_did.clone()).unwrap();
+            assert_eq!(
+                db.restriction_ack_status(app_did, did),
+                AckStatus {
+                    key_version: 2,
+                    acknowledged: 0,
+                    replicas: 2
+                }
+            );
+        }
+
+        #[ink::test]
+        fn bootstrap_bundle_works() {
+            let mut db = DbContract::with_network_id(3);
+            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            db.add_address(addr.clone(), 3, NodeRole::BOOTNODE).unwrap();
+
+            let bundle = db.bootstrap_bundle();
+            assert_eq!(bundle.network_id, 3);
+            assert_eq!(bundle.bootnodes, vec![addr]);
+            assert_eq!(bundle.max_bootnodes, DEFAULT_MAX_BOOTNODES);
+            assert_eq!(bundle.epoch, db.epoch_info());
+            assert_eq!(bundle.registration_fee, DEFAULT_REGISTRATION_FEE);
+            assert_eq!(bundle.node_stake, DEFAULT_NODE_STAKE);
+            assert_eq!(bundle.min_client_version, db.contract_version());
+        }
+
+        #[ink::test]
+        fn pending_update_flow_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let new_cid = "Qmjhggfztfiov7zfbvyzhiuW8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+
+            // announcing keeps the confirmed CID authoritative
+            db.announce_pending_update(did.clone(), new_cid.clone())
+                .unwrap();
+            assert_eq!(
+                db.get_account_ht_cid(did.clone(), auth_material.clone()),
+                cid
+            );
+            assert_eq!(
+                db.get_pending_ht_cid(did.clone(), auth_material.clone()),
+                new_cid
+            );
+
+            // confirming switches over and clears the pending pointer
+            db.confirm_update(did.clone()).unwrap();
+            assert_eq!(
+                db.get_account_ht_cid(did.clone(), auth_material.clone()),
+                new_cid
+            );
+            assert_eq!(
+                db.get_pending_ht_cid(did.clone(), auth_material.clone()),
+                Vec::<u8>::new()
+            );
+
+            // a deactivated account's pending CID is withheld
+            db.announce_pending_update(did.clone(), cid).unwrap();
+            db.deactivate(did.clone()).unwrap();
+            assert_eq!(db.get_pending_ht_cid(did, auth_material), Vec::<u8>::new());
+        }
+
+        #[ink::test]
+        fn subscriber_journal_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let first = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let second = "/ip4/192.168.44.206/tcp/1509".as_bytes().to_vec();
+
+            db.subscribe_node(did.clone(), first.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            let synced_at = ink::env::block_number::<ink::env::DefaultEnvironment>();
+            db.subscribe_node(did.clone(), second.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            db.unsubscribe_node(did.clone(), first.clone()).unwrap();
+            // removing a node that isn't subscribed isn't a change
+            assert_eq!(
+                db.unsubscribe_node(did.clone(), first.clone()),
+                Err(Error::NotSubscribed)
+            );
+
+            let (changes, complete) = db.subscriber_changes_since(did.clone(), synced_at);
+            assert!(complete);
+            assert_eq!(
+                changes,
+                vec![SubscriberChange {
+                    block: synced_at + 1,
+                    node: first.clone(),
+                    joined: false,
+                }]
+            );
+            assert_eq!(
+                db.subscriber_changes_since(did.clone(), synced_at - 1)
+                    .0
+                    .len(),
+                2
+            );
+
+            // overflowing the journal makes older syncs incomplete
+            for _ in 0..MAX_JOURNAL_ENTRIES {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+                db.subscribe_node(did.clone(), first.clone(), DEFAULT_NETWORK_ID)
+                    .unwrap();
+                db.unsubscribe_node(did.clone(), first.clone()).unwrap();
+            }
+            let (_, complete) = db.subscriber_changes_since(did.clone(), synced_at);
+            assert!(!complete);
+            let now = ink::env::block_number::<ink::env::DefaultEnvironment>();
+            let (changes, complete) = db.subscriber_changes_since(did, now - 1);
+            assert!(complete);
+            assert_eq!(changes.len(), 2);
+        }
+
+        #[ink::test]
+        fn restriction_journal_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:user:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let app_did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+            db.new_account(app_did.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+
+            db.restrict(did.clone(), app_did.clone()).unwrap();
+            db.unrestrict(did.clone(), app_did.clone()).unwrap();
+
+            let (changes, complete) = db.restriction_changes_since(0, 10);
+            assert!(complete);
+            assert_eq!(changes.len(), 2);
+            assert_eq!(changes[0].seq, 1);
+            assert!(changes[0].restricted);
+            assert_eq!(changes[1].seq, 2);
+            assert!(!changes[1].restricted);
+
+            // the cursor and limit page through the journal
+            let (changes, _) = db.restriction_changes_since(1, 10);
+            assert_eq!(changes.len(), 1);
+            assert_eq!(changes[0].seq, 2);
+            assert_eq!(db.restriction_changes_since(0, 1).0.len(), 1);
+            assert!(db.restriction_changes_since(2, 10).0.is_empty());
+            assert_eq!(
+                db.restriction_changes_since(u64::MAX, 10),
+                (Vec::new(), true)
+            );
+
+            // evicted changes make older cursors incomplete
+            for _ in 0..MAX_RESTRICTION_LOG / 2 {
+                db.restrict(did.clone(), app_did.clone()).unwrap();
+                db.unrestrict(did.clone(), app_did.clone()).unwrap();
+            }
+            let (changes, complete) = db.restriction_changes_since(0, 10);
+            assert!(!complete);
+            assert_eq!(changes[0].seq, 3);
+        }
+
+        #[ink::test]
+        fn credential_registry_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:user:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            db.new_account(did.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+
+            let signature: [u8; 65] = [
+                195, 218, 227, 165, 226, 17, 25, 160, 37, 92, 142, 238, 4, 41, 244, 211, 18, 94,
+                131, 116, 231, 116, 255, 164, 252, 248, 85, 233, 173, 225, 26, 185, 119, 235, 137,
+                35, 204, 251, 134, 131, 186, 215, 76, 112, 17, 192, 114, 243, 102, 166, 176, 140,
+                180, 124, 213, 102, 117, 212, 89, 89, 92, 209, 116, 17, 28,
+            ];
+            let message_hash: [u8; 32] = [
+                167, 124, 116, 195, 220, 156, 244, 20, 243, 69, 1, 98, 189, 205, 79, 108, 213, 78,
+                65, 65, 230, 30, 17, 37, 184, 220, 237, 135, 1, 209, 101, 229,
+            ];
+            let public_key: [u8; 33] = [
+                3, 110, 192, 35, 209, 24, 189, 55, 218, 250, 100, 89, 40, 76, 222, 208, 202, 127,
+                31, 13, 58, 51, 242, 179, 13, 63, 19, 22, 252, 164, 226, 248, 98,
+            ];
+
+            // keys must match the declared type
+            assert_eq!(
+                db.add_credential(did.clone(), CredentialType::Sr25519, public_key.to_vec()),
+                Err(Error::InvalidCredential)
+            );
+            assert_eq!(
+                db.add_credential(did.clone(), CredentialType::Ed25519, [1; 32].to_vec()),
+                Ok(0)
+            );
+            assert_eq!(
+                db.add_credential(did.clone(), CredentialType::Ecdsa, public_key.to_vec()),
+                Ok(1)
+            );
+
+            assert!(db.verify_credential_signature(
+                did.clone(),
+                1,
+                message_hash,
+                signature.to_vec()
+            ));
+            assert!(!db.verify_credential_signature(did.clone(), 1, [0; 32], signature.to_vec()));
+
+            // revoking one credential leaves the others in place
+            db.revoke_credential(did.clone(), 1).unwrap();
+            assert!(!db.verify_credential_signature(
+                did.clone(),
+                1,
+                message_hash,
+                signature.to_vec()
+            ));
+            let credentials = db.get_credentials(did.clone());
+            assert!(!credentials[0].revoked);
+            assert!(credentials[1].revoked);
+
+            // at the cap, only a revoked credential's slot can be taken over
+            for index in 2..MAX_CREDENTIALS as u32 {
+                assert_eq!(
+                    db.add_credential(
+                        did.clone(),
+                        CredentialType::Ed25519,
+                        [index as u8; 32].to_vec()
+                    ),
+                    Ok(index)
+                );
+            }
+            assert_eq!(
+                db.add_credential(did.clone(), CredentialType::Ecdsa, public_key.to_vec()),
+                Ok(1)
+            );
+            assert_eq!(
+                db.add_credential(did.clone(), CredentialType::Ed25519, [9; 32].to_vec()),
+                Err(Error::CapacityExceeded)
+            );
+            db.revoke_credential(did.clone(), 0).unwrap();
+            assert_eq!(
+                db.add_credential(did.clone(), CredentialType::Ed25519, [9; 32].to_vec()),
+                Ok(0)
+            );
+            let credentials = db.get_credentials(did);
+            assert_eq!(credentials.len(), MAX_CREDENTIALS);
+            assert!(credentials.iter().all(|credential| !credential.revoked));
+        }
+
+        #[ink::test]
+        fn typed_errors_work() {
+            let mut db = DbContract::new();
+            let did = "did:sam:user:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+
+            assert_eq!(db.remove_address(addr.clone()), Err(Error::NodeNotFound));
+            db.add_address(addr.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                .unwrap();
+            assert_eq!(
+                db.add_address(addr.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE),
+                Err(Error::AlreadyExists)
+            );
+
+            assert_eq!(
+                db.update_account_ht_cid(did.clone(), b"QmCid".to_vec()),
+                Err(Error::AccountNotFound)
+            );
+            db.new_account(
+                did.clone(),
+                b"QmCid".to_vec(),
+                hash_auth_material(&Vec::new()),
+            )
+            .unwrap();
+            assert_eq!(
+                db.confirm_update(did.clone()),
+                Err(Error::PendingUpdateNotFound)
+            );
+            assert_eq!(db.unrestrict(did.clone(), did), Err(Error::NotRestricted));
+            assert_eq!(db.revoke_token(0), Err(Error::TokenNotFound));
+        }
+
+        #[ink::test]
+        fn replication_policy_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let app_did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let paris = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let ohio = "/ip4/192.168.44.206/tcp/1509".as_bytes().to_vec();
+            let unlisted = "/ip4/192.168.44.207/tcp/1509".as_bytes().to_vec();
+            db.new_account(app_did.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+            for (addr, region) in [(&paris, "eu-west"), (&ohio, "us-east")] {
+                db.add_address(addr.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                    .unwrap();
+                db.set_node_metadata(addr.clone(), region.as_bytes().to_vec(), 100)
+                    .unwrap();
+            }
+            db.subscribe_node(app_did.clone(), ohio.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+
+            // only the application's controller sets the policy
+            let policy = ReplicationPolicy {
+                allowed_regions: vec![b"eu-west".to_vec()],
+                ..Default::default()
+            };
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            assert_eq!(
+                db.set_replication_policy(app_did.clone(), policy.clone()),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(
+                db.set_replication_policy(
+                    app_did.clone(),
+                    ReplicationPolicy {
+                        allowed_regions: vec![[b'x'; 33].to_vec()],
+                        ..Default::default()
+                    }
+                ),
+                Err(Error::CapacityExceeded)
+            );
+            db.set_replication_policy(app_did.clone(), policy.clone())
+                .unwrap();
+            assert_eq!(db.get_replication_policy(app_did.clone()), policy);
+
+            // nodes outside the allowed regions, or with no listed region, are turned away
+            db.unsubscribe_node(app_did.clone(), ohio.clone()).unwrap();
+            assert_eq!(
+                db.subscribe_node(app_did.clone(), ohio.clone(), DEFAULT_NETWORK_ID),
+                Err(Error::RegionNotAllowed)
+            );
+            assert_eq!(
+                db.subscribe_node(app_did.clone(), unlisted, DEFAULT_NETWORK_ID),
+                Err(Error::RegionNotAllowed)
+            );
+            db.subscribe_node(app_did.clone(), paris.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+
+            // a node that moved away after asking to join can't be approved
+            db.unsubscribe_node(app_did.clone(), paris.clone()).unwrap();
+            db.set_subscription_approval(app_did.clone(), Some(10))
+                .unwrap();
+            db.subscribe_node(app_did.clone(), paris.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            db.set_node_metadata(paris.clone(), b"us-east".to_vec(), 100)
+                .unwrap();
+            assert_eq!(
+                db.approve_subscriber(app_did.clone(), paris.clone()),
+                Err(Error::RegionNotAllowed)
+            );
+
+            // an empty policy lifts the restriction
+            db.set_subscription_approval(app_did.clone(), None).unwrap();
+            db.set_replication_policy(app_did.clone(), ReplicationPolicy::default())
+                .unwrap();
+            db.subscribe_node(app_did.clone(), ohio.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            assert_eq!(db.get_subscribers(app_did, DEFAULT_NETWORK_ID), vec![ohio]);
+        }
+
+        #[ink::test]
+        fn attestations_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let app_did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let report_hash = Hash::from([0x11; 32]);
+            db.new_account(app_did.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+            db.add_address(addr.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                .unwrap();
+            db.set_replication_policy(
+                app_did.clone(),
+                ReplicationPolicy {
+                    require_attestation: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            // only the node's operator or a registrar submits, and the signature is bounded
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            assert_eq!(
+                db.submit_attestation(addr.clone(), report_hash, vec![7; 64]),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(
+                db.submit_attestation(
+                    addr.clone(),
+                    report_hash,
+                    vec![7; MAX_ATTESTATION_SIGNATURE_LEN + 1]
+                ),
+                Err(Error::CapacityExceeded)
+            );
+            db.submit_attestation(addr.clone(), report_hash, vec![7; 64])
+                .unwrap();
+
+            // an unverified attestation doesn't count
+            assert_eq!(
+                db.subscribe_node(app_did.clone(), addr.clone(), DEFAULT_NETWORK_ID),
+                Err(Error::NodeNotAttested)
+            );
+
+            // only verifiers validate, and a rejected attestation is dropped
+            db.grant_role(accounts.bob, Role::Verifier).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(
+                db.verify_attestation(addr.clone(), true),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.verify_attestation(addr.clone(), false).unwrap();
+            assert_eq!(db.get_attestation(addr.clone()), None);
+            assert_eq!(
+                db.verify_attestation(addr.clone(), true),
+                Err(Error::NodeNotAttested)
+            );
+
+            // a verified attestation lets the node join
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.submit_attestation(addr.clone(), report_hash, vec![7; 64])
+                .unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.verify_attestation(addr.clone(), true).unwrap();
+            assert!(db
+                .get_attestation(addr.clone())
+                .unwrap()
+                .verified_at
+                .is_some());
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.subscribe_node(app_did.clone(), addr.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+
+            // removing the node drops its attestation
+            db.unsubscribe_node(app_did, addr.clone()).unwrap();
+            db.remove_address(addr.clone()).unwrap();
+            assert_eq!(db.get_attestation(addr), None);
+        }
+
+        #[ink::test]
+        fn region_diversity_works() {
+            let mut db = DbContract::new();
+            let app_did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let paris = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let dublin = "/ip4/192.168.44.206/tcp/1509".as_bytes().to_vec();
+            let frankfurt = "/ip4/192.168.44.207/tcp/1509".as_bytes().to_vec();
+            let ohio = "/ip4/192.168.44.208/tcp/1509".as_bytes().to_vec();
+            db.new_account(app_did.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+            for (addr, region) in [
+                (&paris, "eu-west"),
+                (&dublin, "eu-west"),
+                (&frankfurt, "eu-west"),
+                (&ohio, "us-east"),
+            ] {
+                db.add_address(addr.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                    .unwrap();
+                db.set_node_metadata(addr.clone(), region.as_bytes().to_vec(), 100)
+                    .unwrap();
+            }
+
+            // the spread must be reachable within the cap and the allowed regions
+            assert_eq!(
+                db.set_replication_policy(
+                    app_did.clone(),
+                    ReplicationPolicy {
+                        allowed_regions: Vec::new(),
+                        min_regions: 4,
+                        max_replicas: 3,
+                        require_attestation: false,
+                    }
+                ),
+                Err(Error::InvalidConfig)
+            );
+            assert_eq!(
+                db.set_replication_policy(
+                    app_did.clone(),
+                    ReplicationPolicy {
+                        allowed_regions: vec![b"eu-west".to_vec()],
+                        min_regions: 2,
+                        max_replicas: 5,
+                        require_attestation: false,
+                    }
+                ),
+                Err(Error::InvalidConfig)
+            );
+            assert_eq!(
+                db.set_replication_policy(
+                    app_did.clone(),
+                    ReplicationPolicy {
+                        allowed_regions: Vec::new(),
+                        min_regions: 2,
+                        max_replicas: 0,
+                        require_attestation: false,
+                    }
+                ),
+                Err(Error::InvalidConfig)
+            );
+            db.set_replication_policy(
+                app_did.clone(),
+                ReplicationPolicy {
+                    allowed_regions: Vec::new(),
+                    min_regions: 2,
+                    max_replicas: 3,
+                    require_attestation: false,
+                },
+            )
+            .unwrap();
+
+            // replicas in a covered region are welcome while slots remain for the others
+            db.subscribe_node(app_did.clone(), paris.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            db.subscribe_node(app_did.clone(), dublin.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+
+            // the last slot is kept for a region the subscribers don't span yet
+            assert_eq!(
+                db.subscribe_node(app_did.clone(), frankfurt.clone(), DEFAULT_NETWORK_ID),
+                Err(Error::InsufficientDiversity)
+            );
+            db.subscribe_node(app_did.clone(), ohio.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+
+            // the cap holds once every slot is taken
+            db.unsubscribe_node(app_did.clone(), dublin.clone())
+                .unwrap();
+            db.subscribe_node(app_did.clone(), frankfurt.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            assert_eq!(
+                db.subscribe_node(app_did.clone(), dublin, DEFAULT_NETWORK_ID),
+                Err(Error::CapacityExceeded)
+            );
+            assert_eq!(
+                db.get_subscribers(app_did, DEFAULT_NETWORK_ID),
+                vec![paris, ohio, frankfurt]
+            );
+        }
+
+        #[ink::test]
+        fn subscriber_trimming_works() {
+            let mut db = DbContract::new();
+            let app_did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let stale = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let oldest = "/ip4/192.168.44.206/tcp/1509".as_bytes().to_vec();
+            let middle = "/ip4/192.168.44.207/tcp/1509".as_bytes().to_vec();
+            let newest = "/ip4/192.168.44.208/tcp/1509".as_bytes().to_vec();
+            db.new_account(app_did.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+            db.add_address(stale.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                .unwrap();
+            for addr in [&stale, &oldest, &middle, &newest] {
+                db.subscribe_node(app_did.clone(), addr.clone(), DEFAULT_NETWORK_ID)
+                    .unwrap();
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+
+            // the listed node stops sending heartbeats
+            db.config.node_ttl = 1;
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+
+            // lowering the cap drops unhealthy nodes first, then the newest
+            db.set_replication_policy(
+                app_did.clone(),
+                ReplicationPolicy {
+                    max_replicas: 2,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            assert_eq!(
+                db.get_subscribers(app_did.clone(), DEFAULT_NETWORK_ID),
+                vec![oldest.clone(), middle]
+            );
+            db.set_replication_policy(
+                app_did.clone(),
+                ReplicationPolicy {
+                    max_replicas: 1,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            assert_eq!(
+                db.get_subscribers(app_did.clone(), DEFAULT_NETWORK_ID),
+                vec![oldest]
+            );
+            assert_eq!(
+                db.subscribe_node(app_did, newest, DEFAULT_NETWORK_ID),
+                Err(Error::CapacityExceeded)
+            );
+        }
+
+        #[ink::test]
+        fn subscription_approval_works() {
+            let mut db = DbContract::new();
+            let app_did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let first = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let second = "/ip4/192.168.44.206/tcp/1509".as_bytes().to_vec();
+            db.new_account(app_did.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+            db.set_subscription_approval(app_did.clone(), Some(2))
+                .unwrap();
+
+            // subscribing opens a request instead of joining
+            db.subscribe_node(app_did.clone(), first.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            db.subscribe_node(app_did.clone(), second.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            assert!(db
+                .get_subscribers(app_did.clone(), DEFAULT_NETWORK_ID)
+                .is_empty());
+            assert_eq!(db.get_pending_subscribers(app_did.clone()).len(), 2);
+
+            // each account has its own allowance of open requests
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            assert_eq!(
+                db.subscribe_node(app_did.clone(), first.clone(), DEFAULT_NETWORK_ID),
+                Err(Error::AlreadyExists)
+            );
+            for i in 0..MAX_PENDING_PER_REQUESTER {
+                let junk = format!("/ip4/10.0.3.{}/tcp/1509", i).into_bytes();
+                db.subscribe_node(app_did.clone(), junk, DEFAULT_NETWORK_ID)
+                    .unwrap();
+            }
+            assert_eq!(
+                db.subscribe_node(
+                    app_did.clone(),
+                    b"/ip4/10.0.3.99/tcp/1509".to_vec(),
+                    DEFAULT_NETWORK_ID
+                ),
+                Err(Error::CapacityExceeded)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.subscribe_node(
+                app_did.clone(),
+                b"/ip4/10.0.3.99/tcp/1509".to_vec(),
+                DEFAULT_NETWORK_ID,
+            )
+            .unwrap();
+            for i in 0..MAX_PENDING_PER_REQUESTER {
+                let junk = format!("/ip4/10.0.3.{}/tcp/1509", i).into_bytes();
+                db.reject_subscriber(app_did.clone(), junk).unwrap();
+            }
+            db.reject_subscriber(app_did.clone(), b"/ip4/10.0.3.99/tcp/1509".to_vec())
+                .unwrap();
+
+            // only the application can rule on requests
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            assert_eq!(
+                db.approve_subscriber(app_did.clone(), first.clone()),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.approve_subscriber(app_did.clone(), first.clone())
+                .unwrap();
+            assert_eq!(
+                db.get_subscribers(app_did.clone(), DEFAULT_NETWORK_ID),
+                vec![first.clone()]
+            );
+            db.reject_subscriber(app_did.clone(), second.clone())
+                .unwrap();
+            assert!(db.get_pending_subscribers(app_did.clone()).is_empty());
+
+            // unapproved requests expire
+            db.subscribe_node(app_did.clone(), second.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            for _ in 0..3 {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            assert!(db.get_pending_subscribers(app_did.clone()).is_empty());
+            assert_eq!(
+                db.approve_subscriber(app_did.clone(), second.clone()),
+                Err(Error::RequestNotFound)
+            );
+
+            // nodes join freely once approval is turned off
+            db.set_subscription_approval(app_did.clone(), None).unwrap();
+            db.subscribe_node(app_did.clone(), second.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            assert_eq!(
+                db.get_subscribers(app_did, DEFAULT_NETWORK_ID),
+                vec![first, second]
+            );
+        }
+
+        #[ink::test]
+        fn bootnode_seeding_works() {
+            let first = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let second = "/dns4/node.samaritan.io/tcp/443/wss".as_bytes().to_vec();
+            let db =
+                DbContract::with_bootnodes(7, vec![first.clone(), second.clone(), first.clone()])
+                    .unwrap();
+
+            assert_eq!(db.network_id(), 7);
+            assert_eq!(
+                db.get_node_addresses(7),
+                vec![first.clone(), second.clone()]
+            );
+            assert_eq!(
+                db.get_nodes_by_transport(7, Transport::WebSocket, 10),
+                vec![second]
+            );
+
+            // malformed seeds fail the deployment instead of being dropped
+            assert!(matches!(
+                DbContract::with_bootnodes(7, vec![first, b"not-a-multiaddr".to_vec()]),
+                Err(Error::InvalidMultiaddr(_))
+            ));
+        }
+
+        #[ink::test]
+        fn config_constructor_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new_with_config(InitConfig {
+                owner: accounts.bob,
+                network_id: 5,
+                max_bootnodes: 2,
+                epoch_length: 100,
+                registration_fee: 50,
+                node_stake: 7,
+                permissioned: DEFAULT_PERMISSIONED,
+            });
+            assert_eq!(db.owner(), accounts.bob);
+            assert_eq!(db.network_id(), 5);
+            assert_eq!(db.epoch_info().epoch_length, 100);
+            assert_eq!(db.get_config().registration_fee, 50);
+            assert_eq!(db.bootstrap_bundle().node_stake, 7);
+            assert!(db.get_config().permissioned);
+
+            // the configured cap bounds the bootnode list
+            let addrs = [
+                "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec(),
+                "/ip4/192.168.44.206/tcp/1509".as_bytes().to_vec(),
+                "/ip4/192.168.44.207/tcp/1509".as_bytes().to_vec(),
+            ];
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                db.add_address(addrs[0].clone(), 5, NodeRole::BOOTNODE),
+                Err(Error::InsufficientStake)
+            );
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(7);
+            for addr in &addrs {
+                db.add_address(addr.clone(), 5, NodeRole::BOOTNODE).unwrap();
+            }
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(0);
+            assert_eq!(db.get_node_addresses(5), addrs[1..].to_vec());
+            assert_eq!(db.bootstrap_bundle().max_bootnodes, 2);
+
+            // permissioned deployments list bootnodes through registrars only
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(
+                db.add_address(addrs[0].clone(), 5, NodeRole::BOOTNODE),
+                Err(Error::Unauthorized)
+            );
+
+            // open deployments let any operator locking the stake list its node
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let mut open = DbContract::new_with_config(InitConfig {
+                owner: accounts.bob,
+                network_id: 5,
+                max_bootnodes: 2,
+                epoch_length: 100,
+                registration_fee: 0,
+                node_stake: 7,
+                permissioned: false,
+            });
+            assert_eq!(
+                open.add_address(addrs[0].clone(), 5, NodeRole::BOOTNODE),
+                Err(Error::InsufficientStake)
+            );
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(7);
+            open.add_address(addrs[0].clone(), 5, NodeRole::BOOTNODE)
+                .unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(0);
+            assert_eq!(
+                open.get_node_record(addrs[0].clone()).unwrap().operator,
+                accounts.charlie
+            );
+
+            // the default constructor deploys for the caller with default settings
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            let db = DbContract::new();
+            assert_eq!(db.owner(), accounts.alice);
+            assert_eq!(db.bootstrap_bundle().max_bootnodes, DEFAULT_MAX_BOOTNODES);
+            assert!(db.get_config().permissioned);
+        }
+
+        #[ink::test]
+        fn reserved_prefixes_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let system = "did:sam:apps:system".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_hash = hash_auth_material(b"bfdh87y*(TD*&^*S&io");
+
+            // only admins manage the reserved list and allocations
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                db.set_reserved_prefix(system.clone(), true),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(
+                db.set_reserved_prefix(Vec::new(), true),
+                Err(Error::InvalidConfig)
+            );
+            db.set_reserved_prefix(system.clone(), true).unwrap();
+            assert_eq!(db.get_reserved_prefixes(), vec![system.clone()]);
+
+            // reserved DIDs can't be claimed, including under a derived name or by cloning
+            let squatted = b"did:sam:apps:system-status".to_vec();
+            assert_eq!(
+                db.new_account(squatted.clone(), cid.clone(), auth_hash),
+                Err(Error::DidReserved)
+            );
+            assert_eq!(
+                db.validate_new_account(system.clone()),
+                Err(Error::DidReserved)
+            );
+            db.new_account(b"did:sam:apps:other".to_vec(), cid.clone(), auth_hash)
+                .unwrap();
+            assert_eq!(
+                db.clone_app(
+                    b"did:sam:apps:other".to_vec(),
+                    squatted.clone(),
+                    CloneOverrides::default()
+                ),
+                Err(Error::DidReserved)
+            );
+
+            // an allocated DID can be registered by its account only, once
+            db.allocate_did(system.clone(), accounts.bob).unwrap();
+            assert_eq!(
+                db.new_account(system.clone(), cid.clone(), auth_hash),
+                Err(Error::DidReserved)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.new_account(system.clone(), cid.clone(), auth_hash)
+                .unwrap();
+            assert_eq!(db.get_allocation(system), None);
+
+            // the council manages the list through proposals
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.set_council(vec![accounts.charlie], 1).unwrap();
+            assert_eq!(
+                db.allocate_did(squatted.clone(), accounts.django),
+                Err(Error::CouncilRequired)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let id = db
+                .propose(CouncilAction::AllocateDid(
+                    squatted.clone(),
+                    accounts.django,
+                ))
+                .unwrap();
+            db.execute_proposal(id).unwrap();
+            assert_eq!(db.get_allocation(squatted.clone()), Some(accounts.django));
+            let id = db
+                .propose(CouncilAction::SetReservedPrefix(
+                    b"did:sam:apps:system".to_vec(),
+                    false,
+                ))
+                .unwrap();
+            db.execute_proposal(id).unwrap();
+            assert!(db.get_reserved_prefixes().is_empty());
+            db.new_account(squatted, cid, auth_hash).unwrap();
+        }
+
+        #[ink::test]
+        fn registration_freeze_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let did = "did:sam:user:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let new_did = "did:sam:user:8fgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+
+            // only the owner can freeze registrations
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(db.freeze_registrations(true), Err(Error::Unauthorized));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.freeze_registrations(true).unwrap();
+            assert!(db.registrations_frozen());
+
+            // new registrations are turned away, existing accounts keep working
+            assert_eq!(
+                db.new_account(
+                    new_did.clone(),
+                    cid.clone(),
+                    hash_auth_material(&auth_material)
+                ),
+                Err(Error::RegistrationsFrozen)
+            );
+            assert_eq!(
+                db.add_address(addr.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE),
+                Err(Error::RegistrationsFrozen)
+            );
+            db.update_account_ht_cid(did, b"QmNewCid".to_vec()).unwrap();
+
+            db.freeze_registrations(false).unwrap();
+            db.new_account(new_did, cid, hash_auth_material(&auth_material))
+                .unwrap();
+            db.add_address(addr, DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                .unwrap();
+        }
+
+        #[ink::test]
+        fn restriction_list_paging_works() {
+            let mut db = DbContract::new();
+            let app_did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            db.new_account(
+                app_did.clone(),
+                cid.clone(),
+                hash_auth_material(&auth_material),
+            )
+            .unwrap();
+
+            let users = [
+                b"did:sam:user:first".to_vec(),
+                b"did:sam:user:second".to_vec(),
+                b"did:sam:user:third".to_vec(),
+            ];
+            for user in &users {
+                db.new_account(
+                    user.clone(),
+                    cid.clone(),
+                    hash_auth_material(&auth_material),
+                )
+                .unwrap();
+                db.restrict(user.clone(), app_did.clone()).unwrap();
+            }
+            assert_eq!(db.restriction_count(app_did.clone()), 3);
+
+            // walk the list two users at a time
+            let (page, cursor) = db.get_restriction_list_paged(app_did.clone(), 0, 2);
+            assert_eq!(page, users[..2].to_vec());
+            assert_eq!(cursor, Some(2));
+            let (page, cursor) = db.get_restriction_list_paged(app_did.clone(), 2, 2);
+            assert_eq!(page, users[2..].to_vec());
+            assert_eq!(cursor, None);
+        }
+
+        #[ink::test]
+        fn stats_work() {
+            let mut db = DbContract::new();
+            let did = "did:sam:user:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let app_did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+            db.new_account(
+                app_did.clone(),
+                cid.clone(),
+                hash_auth_material(&auth_material),
+            )
+            .unwrap();
+            // re-registering an account doesn't count twice
+            db.reregister_account(did.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+            db.add_address(addr.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                .unwrap();
+            db.subscribe_node(app_did.clone(), addr.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            db.subscribe_node(
+                b"did:sam:apps:unregistered".to_vec(),
+                addr,
+                DEFAULT_NETWORK_ID,
+            )
+            .unwrap();
+            db.restrict(did, app_did.clone()).unwrap();
+
+            assert_eq!(
+                db.network_stats(),
+                NetworkStats {
+                    accounts: 2,
+                    bootnodes: 1,
+                    dids: 3,
+                }
+            );
+            assert_eq!(
+                db.did_stats(app_did),
+                DidStats {
+                    subscribers: 1,
+                    restrictions: 1,
+                }
+            );
+        }
+
+        #[ink::test]
+        fn key_agreement_rotation_works() {
+            let mut db = DbContract::new();
+            let app_did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            db.new_account(app_did.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+            assert_eq!(db.get_key_agreement_key(app_did.clone()), None);
+
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            assert_eq!(
+                db.set_key_agreement_key(app_did.clone(), [1; 32]),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.set_key_agreement_key(app_did.clone(), [1; 32]).unwrap();
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            db.set_key_agreement_key(app_did.clone(), [2; 32]).unwrap();
+
+            // the current key is part of the account, earlier ones move to the history
+            let current = db.get_key_agreement_key(app_did.clone()).unwrap();
+            assert_eq!((current.key, current.set_at), ([2; 32], 1));
+            assert_eq!(
+                db.get_account_info(app_did.clone(), auth_material)
+                    .unwrap()
+                    .key_agreement,
+                Some(current)
+            );
+            let history = db.get_key_agreement_history(app_did);
+            assert_eq!(history.len(), 1);
+            assert_eq!((history[0].key, history[0].set_at), ([1; 32], 0));
+        }
+
+        #[ink::test]
+        fn ownership_checks_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let did = "did:sam:user:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let app_did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+            db.new_account(
+                app_did.clone(),
+                cid.clone(),
+                hash_auth_material(&auth_material),
+            )
+            .unwrap();
+            assert_eq!(
+                db.get_account_info(did.clone(), auth_material.clone())
+                    .unwrap()
+                    .controllers,
+                vec![accounts.alice]
+            );
+
+            // another chain account can't touch the DID, even with its auth material
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                db.new_account(
+                    did.clone(),
+                    b"QmTakeover".to_vec(),
+                    hash_auth_material(&auth_material)
+                ),
+                Err(Error::AlreadyExists)
+            );
+            assert_eq!(
+                db.reregister_account(
+                    did.clone(),
+                    b"QmTakeover".to_vec(),
+                    hash_auth_material(&auth_material)
+                ),
+                Err(Error::Unauthorized)
+            );
+            assert_eq!(
+                db.update_account_ht_cid(did.clone(), b"QmTakeover".to_vec()),
+                Err(Error::Unauthorized)
+            );
+            assert_eq!(
+                db.restrict(did.clone(), app_did.clone()),
+                Err(Error::Unauthorized)
+            );
+            assert_eq!(
+                db.declare_compromise(did.clone(), Vec::new()),
+                Err(Error::Unauthorized)
+            );
+            assert_eq!(db.get_account_ht_cid(did.clone(), auth_material), cid);
+
+            // the controller can
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.restrict(did.clone(), app_did.clone()).unwrap();
+            db.update_account_ht_cid(did, b"QmNewCid".to_vec()).unwrap();
+        }
+
+        #[ink::test]
+        fn node_suggestions_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let other = "/ip4/192.168.44.206/tcp/1509".as_bytes().to_vec();
+            let seed = "/ip4/192.168.44.200/tcp/1509".as_bytes().to_vec();
+            db.add_address(seed.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                .unwrap();
+            db.set_council(vec![accounts.charlie, accounts.django], 1)
+                .unwrap();
+
+            // suggestions are limited to one per account per epoch, and only operators of
+            // listed nodes and council members vote
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.suggest_node(addr.clone()).unwrap();
+            assert_eq!(db.suggest_node(other.clone()), Err(Error::RateLimited));
+            assert_eq!(db.endorse_node(addr.clone()), Err(Error::Unauthorized));
+            assert!(db.get_node_endorsements(addr.clone()).is_empty());
+
+            // the node is listed once enough distinct voters endorse it
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.endorse_node(addr.clone()).unwrap();
+            assert_eq!(db.endorse_node(addr.clone()), Err(Error::AlreadyExists));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            db.endorse_node(addr.clone()).unwrap();
+            assert_eq!(db.get_node_addresses(DEFAULT_NETWORK_ID), vec![seed]);
+            assert_eq!(db.get_node_endorsements(addr.clone()).len(), 2);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            db.endorse_node(addr.clone()).unwrap();
+            assert_eq!(db.get_node_addresses(DEFAULT_NETWORK_ID).len(), 2);
+            assert_eq!(db.get_node_operator(addr), Some(accounts.bob));
+
+            // votes are only accepted during the voting period
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            db.suggest_node(other.clone()).unwrap();
+            for _ in 0..=DEFAULT_NODE_VOTING_PERIOD {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(db.endorse_node(other.clone()), Err(Error::VotingClosed));
+
+            // or when the owner approves it
+            db.suggest_node(other.clone()).unwrap();
+            assert_eq!(
+                db.approve_suggestion(other.clone()),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.approve_suggestion(other.clone()).unwrap();
+            assert_eq!(db.get_node_addresses(DEFAULT_NETWORK_ID).len(), 3);
+            assert_eq!(db.approve_suggestion(other), Err(Error::SuggestionNotFound));
+        }
+
+        #[ink::test]
+        fn auth_material_rotation_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:user:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let old_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let new_material = "9sd8f7*&^S(D*F&".as_bytes().to_vec();
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&old_material))
+                .unwrap();
+
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            assert_eq!(
+                db.rotate_auth_material(did.clone(), hash_auth_material(&new_material)),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.rotate_auth_material(did.clone(), hash_auth_material(&new_material))
+                .unwrap();
+
+            // only the new material unlocks the account
+            assert!(db.get_account_ht_cid(did.clone(), old_material).is_empty());
+            assert_eq!(db.get_account_ht_cid(did, new_material), cid);
+        }
+
+        #[ink::test]
+        fn discovery_rotation_works() {
+            let mut db = DbContract::new();
+            let addrs = [
+                "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec(),
+                "/ip4/192.168.44.206/tcp/1509".as_bytes().to_vec(),
+                "/ip4/192.168.44.207/tcp/1509".as_bytes().to_vec(),
+            ];
+            for addr in &addrs {
+                db.add_address(addr.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                    .unwrap();
+            }
+
+            // each block starts the list one node further along
+            assert_eq!(db.get_node_addresses(DEFAULT_NETWORK_ID), addrs.to_vec());
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            assert_eq!(
+                db.get_node_addresses(DEFAULT_NETWORK_ID),
+                vec![addrs[1].clone(), addrs[2].clone(), addrs[0].clone()]
+            );
+            for _ in 0..2 {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            assert_eq!(db.get_node_addresses(DEFAULT_NETWORK_ID), addrs.to_vec());
+        }
+
+        #[ink::test]
+        fn auth_material_hashing_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let auth_hash = hash_auth_material(&auth_material);
+            db.new_account(did.clone(), cid.clone(), auth_hash).unwrap();
+
+            // the hash itself is not a valid preimage
+            assert!(db
+                .get_account_ht_cid(did.clone(), auth_hash.as_ref().to_vec())
+                .is_empty());
+            assert_eq!(
+                db.get_account_ht_cid(did.clone(), auth_material.clone()),
+                cid
+            );
+            assert_eq!(
+                db.get_account_info(did, auth_material)
+                    .map(|info| info.auth_hash),
+                Some(auth_hash)
+            );
+        }
+
+        #[ink::test]
+        fn signature_auth_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+
+            // signature over auth_challenge(did, 0)
+            let signature: [u8; 65] = [
+                190, 253, 188, 73, 204, 181, 76, 177, 246, 178, 1, 165, 119, 158, 24, 245, 146, 14,
+                226, 246, 231, 50, 245, 41, 40, 40, 203, 139, 61, 157, 102, 42, 25, 85, 169, 132,
+                152, 141, 101, 229, 204, 40, 31, 230, 150, 74, 146, 134, 76, 102, 55, 113, 150,
+                224, 193, 197, 186, 137, 68, 123, 66, 214, 32, 231, 0,
+            ];
+            let auth_key: [u8; 33] = [
+                2, 152, 156, 11, 118, 203, 86, 57, 113, 253, 201, 190, 243, 30, 192, 108, 53, 96,
+                243, 36, 157, 110, 233, 229, 216, 60, 87, 98, 85, 150, 224, 95, 111,
+            ];
+
+            // no key registered yet
+            assert!(db
+                .get_account_ht_cid_signed(did.clone(), 0, signature)
+                .is_empty());
+
+            assert_eq!(
+                db.set_auth_key(did.clone(), Some(vec![3; 32])),
+                Err(Error::InvalidCredential)
+            );
+            db.set_auth_key(did.clone(), Some(auth_key.to_vec()))
+                .unwrap();
+            assert_eq!(db.get_account_ht_cid_signed(did.clone(), 0, signature), cid);
+
+            // the signature only covers the block it names, and challenges from the future
+            // are refused
+            assert!(db
+                .get_account_ht_cid_signed(did.clone(), 1, signature)
+                .is_empty());
+
+            // challenges expire
+            for _ in 0..AUTH_CHALLENGE_TTL {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            assert_eq!(db.get_account_ht_cid_signed(did.clone(), 0, signature), cid);
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            assert!(db
+                .get_account_ht_cid_signed(did.clone(), 0, signature)
+                .is_empty());
+
+            // signature over auth_challenge(did, 11)
+            let fresh: [u8; 65] = [
+                76, 9, 247, 72, 219, 51, 149, 206, 30, 9, 130, 124, 157, 150, 112, 40, 254, 239,
+                156, 119, 224, 55, 247, 60, 33, 193, 139, 134, 37, 134, 91, 210, 77, 34, 152, 95,
+                36, 189, 21, 221, 163, 43, 148, 154, 251, 240, 222, 203, 94, 17, 130, 232, 97, 51,
+                165, 13, 42, 195, 104, 11, 246, 96, 213, 126, 1,
+            ];
+            assert_eq!(db.get_account_ht_cid_signed(did.clone(), 11, fresh), cid);
+
+            // clearing the key turns signature authentication off
+            db.set_auth_key(did.clone(), None).unwrap();
+            assert!(db
+                .get_account_ht_cid_signed(did.clone(), 11, fresh)
+                .is_empty());
+
+            // compromised accounts don't answer signed reads
+            db.set_auth_key(did.clone(), Some(auth_key.to_vec()))
+                .unwrap();
+            db.declare_compromise(did.clone(), Vec::new()).unwrap();
+            assert!(db.get_account_ht_cid_signed(did, 11, fresh).is_empty());
+        }
+
+        #[ink::test]
+        fn idempotent_restriction_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:user:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let app_did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+            db.new_account(app_did.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+
+            db.restrict(did.clone(), app_did.clone()).unwrap();
+            assert_eq!(
+                db.restrict(did.clone(), app_did.clone()),
+                Err(Error::AlreadyRestricted)
+            );
+            assert_eq!(db.restriction_count(app_did.clone()), 1);
+
+            // duplicates stored by older versions are cleaned up by the owner
+            db.restricted
+                .insert(&app_did, &vec![did.clone(), did.clone(), did.clone()]);
+            assert_eq!(db.dedupe_restrictions(vec![app_did.clone()]), Ok(2));
+            assert_eq!(db.restriction_count(app_did.clone()), 1);
+
+            db.unrestrict(did.clone(), app_did.clone()).unwrap();
+            assert_eq!(
+                db.unrestrict(did, app_did.clone()),
+                Err(Error::NotRestricted)
+            );
+            assert_eq!(db.restriction_count(app_did), 0);
+        }
+
+        #[ink::test]
+        fn did_transfer_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+            db.transfer_did(did.clone(), accounts.bob).unwrap();
+            assert_eq!(db.get_pending_transfer(did.clone()), Some(accounts.bob));
+
+            // only the proposed controller can claim, and the old one loses control
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(db.claim_did(did.clone()), Err(Error::TransferNotFound));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.claim_did(did.clone()).unwrap();
+            assert_eq!(db.get_pending_transfer(did.clone()), None);
+            assert_eq!(db.get_account_ht_cid(did.clone(), auth_material), cid);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(
+                db.transfer_did(did, accounts.alice),
+                Err(Error::Unauthorized)
+            );
+        }
+
+        #[ink::test]
+        fn did_sales_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let contract = ink::env::account_id::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_account_balance::<ink::env::DefaultEnvironment>(contract, 1_000);
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            db.new_account(did.clone(), cid, hash_auth_material(b"bfdh87y*(TD*&^*S&io"))
+                .unwrap();
+            assert_eq!(db.buy_did(did.clone()), Err(Error::ListingNotFound));
+            db.list_did(did.clone(), 100).unwrap();
+
+            // buyers escrow the price, and the excess is credited back
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(db.list_did(did.clone(), 10), Err(Error::Unauthorized));
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(50);
+            assert_eq!(db.buy_did(did.clone()), Err(Error::InsufficientFunds));
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(150);
+            db.buy_did(did.clone()).unwrap();
+            assert_eq!(db.claimable_balance(accounts.bob), 50);
+            assert_eq!(db.treasury(), 850);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(db.buy_did(did.clone()), Err(Error::AlreadyExists));
+
+            // the buyer can withdraw before settlement, leaving the DID listed
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.cancel_did_sale(did.clone()).unwrap();
+            assert_eq!(db.claimable_balance(accounts.bob), 150);
+            assert_eq!(db.get_did_listing(did.clone()).unwrap().buyer, None);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(db.settle_did_sale(did.clone()), Err(Error::ListingNotFound));
+
+            // settlement hands the DID over and pays the seller
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            db.buy_did(did.clone()).unwrap();
+            assert_eq!(db.settle_did_sale(did.clone()), Err(Error::Unauthorized));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(db.list_did(did.clone(), 10), Err(Error::AlreadyExists));
+            db.settle_did_sale(did.clone()).unwrap();
+            assert_eq!(db.claimable_balance(accounts.alice), 100);
+            assert_eq!(db.get_did_listing(did.clone()), None);
+            assert_eq!(
+                db.transfer_did(did.clone(), accounts.alice),
+                Err(Error::Unauthorized)
+            );
+
+            // a seller taking the DID off sale refunds the buyer
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            db.list_did(did.clone(), 100).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            db.buy_did(did.clone()).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            db.cancel_did_sale(did.clone()).unwrap();
+            assert_eq!(db.claimable_balance(accounts.django), 100);
+            assert_eq!(db.get_did_listing(did.clone()), None);
+            assert_eq!(db.cancel_did_sale(did), Err(Error::ListingNotFound));
+        }
+
+        #[ink::test]
+        fn contract_controllers_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            // stands in for a DAO's governance contract calling into the registry
+            let dao = AccountId::from([0x42; 32]);
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let new_cid = "QmNewHashtable".as_bytes().to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            db.new_account(did.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+
+            // the contract acknowledges the transfer by claiming the DID itself
+            db.transfer_did(did.clone(), dao).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(dao);
+            db.claim_did(did.clone()).unwrap();
+
+            // ownership checks then follow the contract, not the account that created the DID
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(
+                db.update_account_ht_cid(did.clone(), new_cid.clone()),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(dao);
+            db.update_account_ht_cid(did.clone(), new_cid.clone())
+                .unwrap();
+            assert_eq!(db.get_account_ht_cid(did.clone(), auth_material), new_cid);
+
+            // the contract can share control and hand the DID on
+            db.add_controller(did.clone(), accounts.bob).unwrap();
+            db.remove_controller(did.clone(), accounts.bob).unwrap();
+            db.transfer_did(did.clone(), accounts.charlie).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            db.claim_did(did.clone()).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(dao);
+            assert_eq!(
+                db.update_account_ht_cid(did, new_cid),
+                Err(Error::Unauthorized)
+            );
+        }
+
+        #[ink::test]
+        fn suspension_appeals_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let statement = "QmStatement".as_bytes().to_vec();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.new_account(did.clone(), cid, hash_auth_material(b"bfdh87y*(TD*&^*S&io"))
+                .unwrap();
+            assert_eq!(
+                db.appeal_suspension(did.clone(), statement.clone()),
+                Err(Error::NotSuspended)
+            );
+
+            // admins suspend, and the controller can't lift it on its own
+            assert_eq!(
+                db.suspend_account(did.clone(), b"spam".to_vec()),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.suspend_account(did.clone(), b"spam".to_vec()).unwrap();
+            assert_eq!(
+                db.suspend_account(did.clone(), b"spam".to_vec()),
+                Err(Error::AccountSuspended)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(db.reactivate(did.clone()), Err(Error::AccountSuspended));
+            assert_eq!(db.delete_account(did.clone()), Err(Error::AccountSuspended));
+
+            // appeals queue up for a ruling, one at a time
+            db.appeal_suspension(did.clone(), statement.clone())
+                .unwrap();
+            assert_eq!(
+                db.appeal_suspension(did.clone(), statement.clone()),
+                Err(Error::AlreadyExists)
+            );
+            assert_eq!(db.get_pending_appeals(), vec![did.clone()]);
+            assert_eq!(
+                db.rule_on_appeal(did.clone(), true, Vec::new()),
+                Err(Error::Unauthorized)
+            );
+
+            // a confirmed suspension records the reason and can be appealed again
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.rule_on_appeal(did.clone(), false, b"repeat offence".to_vec())
+                .unwrap();
+            let suspension = db.get_suspension(did.clone()).unwrap();
+            assert_eq!(suspension.ruling, Some(b"repeat offence".to_vec()));
+            assert_eq!(suspension.appeal, None);
+            assert!(db.get_pending_appeals().is_empty());
+            assert_eq!(
+                db.rule_on_appeal(did.clone(), true, Vec::new()),
+                Err(Error::AppealNotFound)
+            );
+
+            // a lifted suspension makes the account active again
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.appeal_suspension(did.clone(), statement).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.rule_on_appeal(did.clone(), true, b"resolved".to_vec())
+                .unwrap();
+            assert_eq!(db.get_suspension(did.clone()), None);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.deactivate(did).unwrap();
+        }
+
+        #[ink::test]
+        fn account_reaping_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new_with_config(InitConfig {
+                owner: accounts.alice,
+                network_id: DEFAULT_NETWORK_ID,
+                max_bootnodes: DEFAULT_MAX_BOOTNODES,
+                epoch_length: 1,
+                registration_fee: DEFAULT_REGISTRATION_FEE,
+                node_stake: DEFAULT_NODE_STAKE,
+                permissioned: DEFAULT_PERMISSIONED,
+            });
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            db.new_account(
+                did.clone(),
+                cid.clone(),
+                hash_auth_material(b"bfdh87y*(TD*&^*S&io"),
+            )
+            .unwrap();
+            let advance = |epochs| {
+                for _ in 0..epochs {
+                    ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+                }
+            };
+
+            // accounts are marked inactive only after enough idle epochs
+            advance(INACTIVITY_EPOCHS - 1);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(db.mark_inactive(did.clone()), Err(Error::AccountActive));
+            advance(1);
+            db.mark_inactive(did.clone()).unwrap();
+            assert_eq!(db.mark_inactive(did.clone()), Err(Error::AlreadyExists));
+            assert_eq!(
+                db.get_account_activity(did.clone()),
+                Some((0, Some(INACTIVITY_EPOCHS)))
+            );
+
+            // a controller action during the grace period clears the mark
+            assert_eq!(db.reap_account(did.clone()), Err(Error::AccountActive));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.update_account_ht_cid(did.clone(), cid).unwrap();
+            assert_eq!(
+                db.get_account_activity(did.clone()),
+                Some((INACTIVITY_EPOCHS, None))
+            );
+
+            // once the grace period passes anyone can reap the account
+            advance(INACTIVITY_EPOCHS);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.mark_inactive(did.clone()).unwrap();
+            advance(REAP_GRACE_EPOCHS - 1);
+            assert_eq!(db.reap_account(did.clone()), Err(Error::AccountActive));
+            advance(1);
+            db.reap_account(did.clone()).unwrap();
+            assert!(!db.check_did_existence(did.clone()));
+            assert_eq!(db.get_account_activity(did.clone()), None);
+            assert_eq!(db.reap_account(did), Err(Error::AccountNotFound));
+        }
+
+        #[ink::test]
+        fn account_deletion_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let did = "did:sam:user:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let app_did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let other_app = "did:sam:apps:F9S0DF9S0DS0SD9F0S".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let node = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            for entry in [&did, &app_did, &other_app] {
+                db.new_account(
+                    entry.clone(),
+                    cid.clone(),
+                    hash_auth_material(&auth_material),
+                )
+                .unwrap();
+            }
+            db.subscribe_node(app_did.clone(), node.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            db.restrict(did.clone(), app_did.clone()).unwrap();
+            db.restrict(did.clone(), other_app.clone()).unwrap();
+            db.add_address(node.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                .unwrap();
+            db.report_auth_failure(app_did.clone()).unwrap();
+            let (token_id, _) = db
+                .mint_access_token(app_did.clone(), did.clone(), 0b01, 5)
+                .unwrap();
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(db.delete_account(app_did.clone()), Err(Error::Unauthorized));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            // deleting an application drops its subscribers and restriction list, and
+            // journals both removals for nodes syncing incrementally
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            db.delete_account(app_did.clone()).unwrap();
+            let (changes, complete) = db.subscriber_changes_since(app_did.clone(), 0);
+            assert!(complete);
+            assert_eq!(
+                changes
+                    .iter()
+                    .map(|change| (change.node.clone(), change.joined))
+                    .collect::<Vec<_>>(),
+                vec![(node.clone(), false)]
+            );
+            let (changes, _) = db.restriction_changes_since(2, 10);
+            assert_eq!(
+                changes
+                    .iter()
+                    .map(|change| (change.user_did.clone(), change.restricted))
+                    .collect::<Vec<_>>(),
+                vec![(did.clone(), false)]
+            );
+            assert!(db
+                .get_account_info(app_did.clone(), auth_material.clone())
+                .is_none());
+            assert!(db
+                .get_subscribers(app_did.clone(), DEFAULT_NETWORK_ID)
+                .is_empty());
+            assert!(db
+                .get_node_subscriptions(node, DEFAULT_NETWORK_ID)
+                .is_empty());
+
+            // its key versions, access tokens and failure reports go with it, so a later
+            // registration of the DID starts clean
+            assert_eq!(db.key_version(did.clone(), app_did.clone()), 0);
+            assert_eq!(
+                db.key_version_peers.get(&did),
+                Some(vec![other_app.clone()])
+            );
+            assert_eq!(db.get_access_token(token_id), None);
+            assert_eq!(db.account_tokens.get(&did), Some(Vec::new()));
+            assert_eq!(db.auth_failure_reporters.get(&app_did), None);
+            assert_eq!(db.restriction_count(app_did), 0);
+            assert_eq!(db.network_stats().accounts, 2);
+
+            // deleting a user purges it from the applications it restricted
+            db.delete_account(did.clone()).unwrap();
+            assert!(!db.is_restricted(did.clone(), other_app.clone()));
+            assert_eq!(db.restriction_count(other_app), 0);
+            assert_eq!(db.delete_account(did), Err(Error::AccountNotFound));
+        }
+
+        #[ink::test]
+        fn unsubscription_events_work() {
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let node = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            db.subscribe_node(did.clone(), node.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            db.unsubscribe_node(did.clone(), node.clone()).unwrap();
+            assert_eq!(db.unsubscribe_node(did, node), Err(Error::NotSubscribed));
+
+            // a join and a single leave were recorded
+            type Event = <DbContract as ink::reflect::ContractEventBase>::Type;
+            let events = ink::env::test::recorded_events()
+                .map(|event| <Event as scale::Decode>::decode(&mut &event.data[..]).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(events.len(), 2);
+            assert!(matches!(events[0], Event::TopicSubscriptionComplete(_)));
+            assert!(matches!(events[1], Event::TopicUnsubscriptionComplete(_)));
+        }
+
+        #[ink::test]
+        fn account_deactivation_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:user:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let app_did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let node = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+            db.new_account(
+                app_did.clone(),
+                cid.clone(),
+                hash_auth_material(&auth_material),
+            )
+            .unwrap();
+
+            db.deactivate(app_did.clone()).unwrap();
+            assert!(db
+                .get_account_ht_cid(app_did.clone(), auth_material.clone())
+                .is_empty());
+            assert!(db
+                .get_account_info(app_did.clone(), auth_material.clone())
+                .is_none());
+            assert_eq!(
+                db.subscribe_node(app_did.clone(), node.clone(), DEFAULT_NETWORK_ID),
+                Err(Error::AccountDeactivated)
+            );
+            assert_eq!(
+                db.restrict(did.clone(), app_did.clone()),
+                Err(Error::AccountDeactivated)
+            );
+
+            // reactivating restores the account with its state intact
+            db.reactivate(app_did.clone()).unwrap();
+            assert_eq!(db.get_account_ht_cid(app_did.clone(), auth_material), cid);
+            db.subscribe_node(app_did.clone(), node, DEFAULT_NETWORK_ID)
+                .unwrap();
+            db.restrict(did, app_did).unwrap();
+        }
+
+        #[ink::test]
+        fn epoch_report_works() {
+            let mut db = DbContract::new_with_config(InitConfig {
+                owner: ink::env::test::default_accounts::<ink::env::DefaultEnvironment>().alice,
+                network_id: DEFAULT_NETWORK_ID,
+                max_bootnodes: DEFAULT_MAX_BOOTNODES,
+                epoch_length: 2,
+                registration_fee: DEFAULT_REGISTRATION_FEE,
+                node_stake: DEFAULT_NODE_STAKE,
+                permissioned: DEFAULT_PERMISSIONED,
+            });
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let node = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+            db.update_account_ht_cid(did.clone(), cid).unwrap();
+            db.subscribe_node(did.clone(), node.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            // subscribing a node again isn't counted
+            db.subscribe_node(did.clone(), node.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            assert_eq!(db.epoch_activity().subscriptions, 1);
+
+            // the first activity of the next epoch reports the closed one
+            for _ in 0..2 {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            assert_eq!(
+                db.epoch_activity(),
+                EpochActivity {
+                    epoch: 1,
+                    ..Default::default()
+                }
+            );
+            let before = ink::env::test::recorded_events().count();
+            db.unsubscribe_node(did, node).unwrap();
+            assert_eq!(ink::env::test::recorded_events().count(), before + 2);
+
+            type Event = <DbContract as ink::reflect::ContractEventBase>::Type;
+            let report = ink::env::test::recorded_events()
+                .nth(before)
+                .map(|event| <Event as scale::Decode>::decode(&mut &event.data[..]).unwrap());
+            let Some(Event::EpochReport(report)) = report else {
+                panic!("expected an epoch report");
+            };
+            assert_eq!(
+                (
+                    report.epoch,
+                    report.accounts_created,
+                    report.cid_updates,
+                    report.subscriptions
+                ),
+                (0, 1, 1, 1)
+            );
+            assert_eq!(db.epoch_activity().unsubscriptions, 1);
+        }
+
+        #[ink::test]
+        fn auth_failure_lockout_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+            db.set_auth_cooldown(did.clone(), Some(10)).unwrap();
+
+            // only node operators report, each once per epoch
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let reporters = [
+                accounts.bob,
+                accounts.charlie,
+                accounts.django,
+                accounts.eve,
+                accounts.frank,
+                accounts.alice,
+            ];
+            assert_eq!(
+                db.report_auth_failure(did.clone()),
+                Err(Error::Unauthorized)
+            );
+            for (i, reporter) in reporters.iter().enumerate() {
+                db.add_address_for(
+                    format!("/ip4/10.0.0.{}/tcp/1509", i).into_bytes(),
+                    DEFAULT_NETWORK_ID,
+                    NodeRole::BOOTNODE,
+                    *reporter,
+                )
+                .unwrap();
+            }
+
+            // reads keep working until the threshold is reached
+            for reporter in &reporters[1..AUTH_FAILURE_THRESHOLD as usize] {
+                ink::env::test::set_caller::<ink::env::DefaultEnvironment>(*reporter);
+                db.report_auth_failure(did.clone()).unwrap();
+            }
+            assert_eq!(
+                db.report_auth_failure(did.clone()),
+                Err(Error::AlreadyExists)
+            );
+            assert_eq!(db.get_auth_lock(did.clone()), None);
+            let before = ink::env::test::recorded_events().count();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(reporters[0]);
+            db.report_auth_failure(did.clone()).unwrap();
+            assert_eq!(ink::env::test::recorded_events().count(), before + 1);
+            assert_eq!(db.get_auth_lock(did.clone()), Some(10));
+            assert!(db
+                .get_account_ht_cid(did.clone(), auth_material.clone())
+                .is_empty());
+
+            // the lock expires after the cool-down and isn't renewed within the same epoch
+            for _ in 0..10 {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            assert_eq!(
+                db.get_account_ht_cid(did.clone(), auth_material.clone()),
+                cid
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(reporters[5]);
+            db.report_auth_failure(did.clone()).unwrap();
+            assert_eq!(db.get_auth_lock(did), None);
+        }
+
+        #[ink::test]
+        fn guardian_recovery_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let did = "did:sam:user:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let first = "did:sam:user:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let second = "did:sam:user:F9S0DF9S0DS0SD9F0S".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let new_material = "9sd8f7*&^S(D*F&".as_bytes().to_vec();
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+            let auth_key = vec![2u8; 33];
+            db.set_auth_key(did.clone(), Some(auth_key)).unwrap();
+            db.add_credential(did.clone(), CredentialType::Ed25519, vec![1u8; 32])
+                .unwrap();
+            db.announce_pending_update(did.clone(), b"QmStagedByTheThief".to_vec())
+                .unwrap();
+            assert_eq!(
+                db.set_guardians(did.clone(), vec![first.clone(), second.clone()], 3),
+                Err(Error::InvalidThreshold)
+            );
+            db.set_guardians(did.clone(), vec![first.clone(), second.clone()], 2)
+                .unwrap();
+            db.declare_compromise(did.clone(), Vec::new()).unwrap();
+
+            // the stolen key can't change the guardians or hand the account over
+            assert_eq!(
+                db.set_guardians(did.clone(), Vec::new(), 0),
+                Err(Error::AccountCompromised)
+            );
+            assert_eq!(
+                db.add_controller(did.clone(), accounts.eve),
+                Err(Error::AccountCompromised)
+            );
+            assert_eq!(
+                db.transfer_did(did.clone(), accounts.eve),
+                Err(Error::AccountCompromised)
+            );
+            assert_eq!(
+                db.delete_account(did.clone()),
+                Err(Error::AccountCompromised)
+            );
+            assert!(db.is_compromised(did.clone()));
+
+            // each guardian DID is controlled by its own chain account
+            for (guardian, controller) in [(&first, accounts.bob), (&second, accounts.charlie)] {
+                ink::env::test::set_caller::<ink::env::DefaultEnvironment>(controller);
+                db.new_account(
+                    guardian.clone(),
+                    cid.clone(),
+                    hash_auth_material(&new_material),
+                )
+                .unwrap();
+            }
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.initiate_recovery(did.clone(), first.clone(), accounts.django)
+                .unwrap();
+            assert_eq!(
+                db.approve_recovery(did.clone(), second.clone()),
+                Err(Error::Unauthorized)
+            );
+
+            // the new controller waits for the threshold and the timelock
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            assert_eq!(
+                db.finalize_recovery(did.clone(), hash_auth_material(&new_material)),
+                Err(Error::RecoveryNotReady)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            db.approve_recovery(did.clone(), second.clone()).unwrap();
+
+            // the controller keeps its veto while the account is frozen
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.cancel_recovery(did.clone()).unwrap();
+            assert_eq!(db.get_recovery(did.clone()).1, None);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.initiate_recovery(did.clone(), first.clone(), accounts.django)
+                .unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            db.approve_recovery(did.clone(), second).unwrap();
+            for _ in 0..RECOVERY_DELAY {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            db.finalize_recovery(did.clone(), hash_auth_material(&new_material))
+                .unwrap();
+            assert!(!db.is_compromised(did.clone()));
+            assert_eq!(
+                db.get_account_ht_cid(did.clone(), new_material.clone()),
+                cid
+            );
+
+            // keys and staged updates set with the lost keys are gone
+            let info = db.get_account_info(did.clone(), new_material).unwrap();
+            assert_eq!(info.auth_key, None);
+            assert!(db.get_credentials(did.clone()).is_empty());
+            assert_eq!(
+                db.confirm_update(did.clone()),
+                Err(Error::PendingUpdateNotFound)
+            );
+
+            // the previous controller no longer controls the account
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(db.cancel_recovery(did), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn multiple_controllers_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            db.new_account(did.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+            db.add_controller(did.clone(), accounts.bob).unwrap();
+            assert_eq!(
+                db.add_controller(did.clone(), accounts.bob),
+                Err(Error::AlreadyExists)
+            );
+
+            // any controller can update the account, including removing the others
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.update_account_ht_cid(did.clone(), b"QmNewCid".to_vec())
+                .unwrap();
+            db.remove_controller(did.clone(), accounts.alice).unwrap();
+            assert_eq!(
+                db.remove_controller(did.clone(), accounts.bob),
+                Err(Error::LastController)
+            );
+            assert_eq!(
+                db.get_account_ht_cid(did.clone(), auth_material),
+                b"QmNewCid".to_vec()
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(
+                db.update_account_ht_cid(did, b"QmOldCid".to_vec()),
+                Err(Error::Unauthorized)
+            );
+        }
+
+        #[ink::test]
+        fn indexer_checkpoints_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            for _ in 0..10 {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                db.publish_checkpoint(5, Hash::from([1; 32])),
+                Err(Error::Unauthorized)
+            );
+            assert_eq!(db.register_indexer(accounts.bob), Err(Error::Unauthorized));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.register_indexer(accounts.bob).unwrap();
+            db.register_indexer(accounts.charlie).unwrap();
+
+            // checkpoints only move forward and can't run ahead of the chain
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.publish_checkpoint(5, Hash::from([1; 32])).unwrap();
+            assert_eq!(
+                db.publish_checkpoint(5, Hash::from([2; 32])),
+                Err(Error::StaleCheckpoint)
+            );
+            assert_eq!(
+                db.publish_checkpoint(11, Hash::from([2; 32])),
+                Err(Error::StaleCheckpoint)
+            );
+            db.publish_checkpoint(8, Hash::from([2; 32])).unwrap();
+            assert_eq!(db.get_checkpoints(accounts.bob).len(), 2);
+
+            let latest = db.get_latest_checkpoints();
+            assert_eq!(latest.len(), 1);
+            assert_eq!((latest[0].0, latest[0].1.block), (accounts.bob, 8));
+        }
+
+        #[ink::test]
+        fn preflight_validation_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let did = "did:sam:user:subfgns89fgg09sgs0j9fusj0fjd"
+                .as_bytes()
+                .to_vec();
+            let app_did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let node = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+
+            assert_eq!(db.validate_new_account(did.clone()), Ok(()));
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+            db.new_account(app_did.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+            assert_eq!(
+                db.validate_restriction(did.clone(), app_did.clone()),
+                Ok(())
+            );
+            db.restrict(did.clone(), app_did.clone()).unwrap();
+            assert_eq!(
+                db.validate_restriction(did.clone(), app_did.clone()),
+                Err(Error::AlreadyRestricted)
+            );
+
+            assert_eq!(
+                db.validate_address(node.clone(), DEFAULT_NETWORK_ID + 1),
+                Err(Error::NetworkMismatch)
+            );
+            db.deactivate(app_did.clone()).unwrap();
+            assert_eq!(
+                db.validate_subscription(app_did, node, DEFAULT_NETWORK_ID),
+                Err(Error::AccountDeactivated)
+            );
+
+            // validation runs as the caller, without changing anything
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                db.validate_restriction(did.clone(), did.clone()),
+                Err(Error::Unauthorized)
+            );
+            assert_eq!(db.validate_new_account(did), Err(Error::AlreadyExists));
+            assert_eq!(db.network_stats().accounts, 2);
+        }
+
+        #[ink::test]
+        fn reregistration_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let new_material = "9sd8f7*&^S(D*F&".as_bytes().to_vec();
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+
+            // registering again never overwrites the account
+            assert_eq!(
+                db.new_account(
+                    did.clone(),
+                    b"QmOther".to_vec(),
+                    hash_auth_material(&new_material)
+                ),
+                Err(Error::AlreadyExists)
+            );
+            assert_eq!(
+                db.get_account_ht_cid(did.clone(), auth_material.clone()),
+                cid
+            );
+
+            db.reregister_account(
+                did.clone(),
+                b"QmOther".to_vec(),
+                hash_auth_material(&new_material),
+            )
+            .unwrap();
+            assert!(db.get_account_ht_cid(did.clone(), auth_material).is_empty());
+            assert_eq!(
+                db.get_account_ht_cid(did, new_material),
+                b"QmOther".to_vec()
+            );
+        }
+
+        #[ink::test]
+        fn controller_activity_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            db.new_account(did.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+            db.add_controller(did.clone(), accounts.bob).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.update_account_ht_cid(did.clone(), b"QmNewCid".to_vec())
+                .unwrap();
+
+            // every controller hears about changes to the account, whoever made them
+            type Event = <DbContract as ink::reflect::ContractEventBase>::Type;
+            let activity = ink::env::test::recorded_events()
+                .filter_map(
+                    |event| match <Event as scale::Decode>::decode(&mut &event.data[..]) {
+                        Ok(Event::ControllerActivity(activity)) => Some(activity),
+                        _ => None,
+                    },
+                )
+                .filter(|activity| activity.controller == accounts.alice && activity.did == did)
+                .map(|activity| activity.action)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                activity,
+                vec![
+                    AccountAction::Created,
+                    AccountAction::ControllersUpdated,
+                    AccountAction::HashtableCidUpdated
+                ]
+            );
+        }
+
+        #[ink::test]
+        fn role_based_access_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+
+            // only holders of the matching role can touch the bootnode list or pause
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                db.add_address(addr.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE),
+                Err(Error::Unauthorized)
+            );
+            assert_eq!(
+                db.grant_role(accounts.bob, Role::NodeRegistrar),
+                Err(Error::Unauthorized)
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.grant_role(accounts.bob, Role::NodeRegistrar).unwrap();
+            db.grant_role(accounts.charlie, Role::Admin).unwrap();
+            assert!(db.has_role(accounts.alice, Role::Pauser));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.add_address(addr.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                .unwrap();
+            assert_eq!(db.freeze_registrations(true), Err(Error::Unauthorized));
+
+            // admins manage roles but can't strip the owner
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            db.revoke_role(accounts.bob, Role::NodeRegistrar).unwrap();
+            assert_eq!(
+                db.revoke_role(accounts.alice, Role::Admin),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                db.add_address(addr, DEFAULT_NETWORK_ID, NodeRole::BOOTNODE),
+                Err(Error::Unauthorized)
+            );
+        }
+
+        #[ink::test]
+        fn emergency_pause_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let node = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(db.pause(None), Err(Error::Unauthorized));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            // pausing a subsystem leaves the others writable
+            db.pause(Some(Subsystem::Subscriptions)).unwrap();
+            assert_eq!(
+                db.subscribe_node(did.clone(), node.clone(), DEFAULT_NETWORK_ID),
+                Err(Error::Paused)
+            );
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+
+            // the global flag stops every write but not queries
+            db.pause(None).unwrap();
+            assert_eq!(
+                db.update_account_ht_cid(did.clone(), b"QmNewCid".to_vec()),
+                Err(Error::Paused)
+            );
+            assert_eq!(
+                db.validate_address(node.clone(), DEFAULT_NETWORK_ID),
+                Err(Error::Paused)
+            );
+            assert_eq!(db.get_account_ht_cid(did.clone(), auth_material), cid);
+
+            db.unpause(None).unwrap();
+            db.unpause(Some(Subsystem::Subscriptions)).unwrap();
+            db.subscribe_node(did, node, DEFAULT_NETWORK_ID).unwrap();
+        }
+
+        #[ink::test]
+        fn app_cloning_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let staging = "did:sam:apps:staging".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let staging_material = "9sd8f7*&^S(D*F&".as_bytes().to_vec();
+            db.new_account(did.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+            db.add_controller(did.clone(), accounts.bob).unwrap();
+            db.set_subscription_approval(did.clone(), Some(10)).unwrap();
+
+            let overrides = CloneOverrides {
+                auth_hash: Some(hash_auth_material(&staging_material)),
+                ..Default::default()
+            };
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            assert_eq!(
+                db.clone_app(did.clone(), staging.clone(), overrides.clone()),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.clone_app(did.clone(), staging.clone(), overrides.clone())
+                .unwrap();
+            assert_eq!(
+                db.clone_app(did, staging.clone(), overrides),
+                Err(Error::AlreadyExists)
+            );
+
+            // the clone keeps the source's configuration under its own auth material
+            let clone = db
+                .get_account_info(staging.clone(), staging_material)
+                .unwrap();
+            assert_eq!(clone.hashtable_cid, cid);
+            assert_eq!(clone.controllers, vec![accounts.alice, accounts.bob]);
+            assert!(db.subscription_approval.contains(&staging));
+        }
+
+        #[ink::test]
+        fn upgrade_checks_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let next = ContractVersion {
+                major: 0,
+                minor: 2,
+                patch: 0,
+            };
+            assert_eq!(db.contract_version(), INITIAL_VERSION);
+
+            // the off-chain environment can't swap code, so only the checks are exercised
+            assert_eq!(
+                db.upgrade(Hash::from([1; 32]), INITIAL_VERSION),
+                Err(Error::VersionNotNewer)
+            );
+            db.grant_role(accounts.bob, Role::Admin).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                db.upgrade(Hash::from([1; 32]), next),
+                Err(Error::Unauthorized)
+            );
+            assert_eq!(db.contract_version(), INITIAL_VERSION);
+        }
+
+        #[ink::test]
+        fn treasury_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let contract = ink::env::account_id::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_account_balance::<ink::env::DefaultEnvironment>(contract, 1_000);
+            ink::env::test::set_account_balance::<ink::env::DefaultEnvironment>(accounts.eve, 0);
+            let mut db = DbContract::new();
+
+            // funds owed to accounts stay out of the treasury
+            db.credit(accounts.bob, 250);
+            assert_eq!(db.treasury(), 750);
+            assert_eq!(
+                db.withdraw(800, accounts.eve),
+                Err(Error::InsufficientFunds)
+            );
+            db.withdraw(500, accounts.eve).unwrap();
+            assert_eq!(db.treasury(), 250);
+            assert_eq!(
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.eve),
+                Ok(500)
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(db.withdraw(1, accounts.bob), Err(Error::Unauthorized));
+            assert_eq!(db.terminate(), Err(Error::Unauthorized));
+            db.withdraw_balance().unwrap();
+
+            // termination needs a global pause and no outstanding claims
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(db.terminate(), Err(Error::NotPaused));
+            db.credit(accounts.bob, 100);
+            db.pause(None).unwrap();
+            assert_eq!(db.terminate(), Err(Error::ClaimsOutstanding));
+            db.claimable.remove(accounts.bob);
+            db.owed = 0;
+
+            let should_terminate = move || {
+                let _ = db.terminate();
+            };
+            ink::env::test::assert_contract_termination::<ink::env::DefaultEnvironment, _>(
+                should_terminate,
+                accounts.alice,
+                250,
+            );
+        }
+
+        #[ink::test]
+        fn subscriber_bans_work() {
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let node = "/ip4/127.0.0.1/tcp/1509".as_bytes().to_vec();
+            db.new_account(did.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+            db.subscribe_node(did.clone(), node.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            assert_eq!(
+                db.ban_subscriber(did.clone(), node.clone()),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.ban_subscriber(did.clone(), node.clone()).unwrap();
+            assert_eq!(
+                db.ban_subscriber(did.clone(), node.clone()),
+                Err(Error::AlreadyBanned)
+            );
+
+            // the node is evicted and can't subscribe again while banned
+            assert!(db
+                .get_subscribers(did.clone(), DEFAULT_NETWORK_ID)
+                .is_empty());
+            assert!(db
+                .get_node_subscriptions(node.clone(), DEFAULT_NETWORK_ID)
+                .is_empty());
+            assert_eq!(db.get_banned_subscribers(did.clone()), vec![node.clone()]);
+            assert_eq!(
+                db.subscribe_node(did.clone(), node.clone(), DEFAULT_NETWORK_ID),
+                Err(Error::NodeBanned)
+            );
+
+            db.unban_subscriber(did.clone(), node.clone()).unwrap();
+            assert_eq!(
+                db.unban_subscriber(did.clone(), node.clone()),
+                Err(Error::NotBanned)
+            );
+            db.subscribe_node(did.clone(), node.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            assert_eq!(db.get_subscribers(did, DEFAULT_NETWORK_ID), vec![node]);
+        }
+
+        #[ink::test]
+        fn council_proposals_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            assert_eq!(
+                db.set_council(vec![accounts.bob, accounts.charlie], 3),
+                Err(Error::InvalidThreshold)
+            );
+            db.set_council(vec![accounts.bob, accounts.charlie, accounts.django], 2)
+                .unwrap();
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            assert_eq!(
+                db.propose(CouncilAction::Pause(None)),
+                Err(Error::Unauthorized)
+            );
+
+            // the owner's direct paths close once the council is set, except for pausing
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.pause(Some(Subsystem::Nodes)).unwrap();
+            assert!(db.is_paused(Subsystem::Nodes));
+            assert_eq!(
+                db.unpause(Some(Subsystem::Nodes)),
+                Err(Error::CouncilRequired)
+            );
+            assert_eq!(db.freeze_registrations(true), Err(Error::CouncilRequired));
+            assert_eq!(
+                db.update_config(db.get_config()),
+                Err(Error::CouncilRequired)
+            );
+            assert_eq!(
+                db.upgrade(
+                    Hash::from([1; 32]),
+                    ContractVersion {
+                        major: 1,
+                        minor: 0,
+                        patch: 0,
+                    }
+                ),
+                Err(Error::CouncilRequired)
+            );
+            assert_eq!(
+                db.set_council(vec![accounts.alice], 1),
+                Err(Error::CouncilRequired)
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let id = db.propose(CouncilAction::Pause(None)).unwrap();
+            assert_eq!(db.approve_proposal(id), Err(Error::AlreadyExists));
+            assert_eq!(db.execute_proposal(id), Err(Error::ProposalNotReady));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            db.approve_proposal(id).unwrap();
+            db.execute_proposal(id).unwrap();
+            assert!(db.is_paused(Subsystem::Accounts));
+            assert_eq!(db.execute_proposal(id), Err(Error::ProposalNotFound));
+
+            // approvals from members who left the council stop counting
+            let config = Config {
+                max_bootnodes: 3,
+                ..db.get_config()
+            };
+            let id = db.propose(CouncilAction::UpdateConfig(config)).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            db.approve_proposal(id).unwrap();
+            let replace = db
+                .propose(CouncilAction::SetCouncil(
+                    vec![accounts.bob, accounts.django],
+                    2,
+                ))
+                .unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.approve_proposal(replace).unwrap();
+            db.execute_proposal(replace).unwrap();
+            assert_eq!(db.get_council().members.len(), 2);
+            assert_eq!(db.execute_proposal(id), Err(Error::ProposalNotReady));
+            db.approve_proposal(id).unwrap();
+            db.execute_proposal(id).unwrap();
+
+            // parameter changes wait out the timelock
+            assert_eq!(db.bootstrap_bundle().max_bootnodes, DEFAULT_MAX_BOOTNODES);
+            for _ in 0..DEFAULT_TIMELOCK_DELAY {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            db.execute_scheduled(0).unwrap();
+            assert_eq!(db.bootstrap_bundle().max_bootnodes, 3);
+
+            // scheduled operations are cancelled through the council too
+            let id = db
+                .propose(CouncilAction::UpdateConfig(db.get_config()))
+                .unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            db.approve_proposal(id).unwrap();
+            db.execute_proposal(id).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(db.cancel_scheduled(1), Err(Error::CouncilRequired));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            let cancel = db.propose(CouncilAction::CancelScheduled(1)).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.approve_proposal(cancel).unwrap();
+            db.execute_proposal(cancel).unwrap();
+            assert_eq!(db.get_scheduled(1), None);
+        }
+
+        #[ink::test]
+        fn config_updates_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_hash = hash_auth_material(b"bfdh87y*(TD*&^*S&io");
+            let config = Config {
+                max_bootnodes: 1,
+                max_subscriptions_per_account: 1,
+                registration_fee: 100,
+                max_did_len: 18,
+                node_votes_required: 1,
+                node_voting_period: 10,
+                timelock_delay: 0,
+                node_ttl: 10,
+                node_stake: 0,
+                permissioned: true,
+                fee_burn_percent: 0,
+            };
+            assert_eq!(db.get_config().max_bootnodes, DEFAULT_MAX_BOOTNODES);
+            assert_eq!(
+                db.update_config(Config {
+                    max_bootnodes: 0,
+                    ..config.clone()
+                }),
+                Err(Error::InvalidConfig)
+            );
+            assert_eq!(
+                db.update_config(Config {
+                    max_subscriptions_per_account: 0,
+                    ..config.clone()
+                }),
+                Err(Error::InvalidConfig)
+            );
+            let id = db.update_config(config.clone()).unwrap();
+            assert_ne!(db.get_config(), config);
+            for _ in 0..DEFAULT_TIMELOCK_DELAY {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            db.execute_scheduled(id).unwrap();
+            assert_eq!(db.get_config(), config);
+
+            // registrations are bounded by length and must pay the fee
+            assert_eq!(
+                db.new_account(did.clone(), cid.clone(), auth_hash),
+                Err(Error::DidTooLong)
+            );
+            let did = b"did:sam:apps:short".to_vec();
+            assert_eq!(
+                db.new_account(did.clone(), cid.clone(), auth_hash),
+                Err(Error::InsufficientFee)
+            );
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            db.new_account(did.clone(), cid, auth_hash).unwrap();
+            assert_eq!(
+                db.get_dids_by_prefix(b"did:sam:apps".to_vec(), 0, 10),
+                vec![b"did:sam:apps:short".to_vec()]
+            );
+
+            // subscription and bootnode caps come from the config. Subscriptions are capped per
+            // account, so one account can't crowd others out of an application
+            db.subscribe_node(
+                did.clone(),
+                b"/ip4/127.0.0.1/tcp/1509".to_vec(),
+                DEFAULT_NETWORK_ID,
+            )
+            .unwrap();
+            assert_eq!(
+                db.subscribe_node(
+                    did.clone(),
+                    b"/ip4/127.0.0.1/tcp/1510".to_vec(),
+                    DEFAULT_NETWORK_ID
+                ),
+                Err(Error::CapacityExceeded)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            db.subscribe_node(
+                did.clone(),
+                b"/ip4/127.0.0.1/tcp/1510".to_vec(),
+                DEFAULT_NETWORK_ID,
+            )
+            .unwrap();
+            assert_eq!(
+                db.unsubscribe_node(did.clone(), b"/ip4/127.0.0.1/tcp/1509".to_vec()),
+                Err(Error::Unauthorized)
+            );
+            db.unsubscribe_node(did.clone(), b"/ip4/127.0.0.1/tcp/1510".to_vec())
+                .unwrap();
+            db.subscribe_node(did, b"/ip4/127.0.0.1/tcp/1511".to_vec(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.add_address(
+                b"/ip4/127.0.0.1/tcp/1509".to_vec(),
+                DEFAULT_NETWORK_ID,
+                NodeRole::BOOTNODE,
+            )
+            .unwrap();
+            db.add_address(
+                b"/ip4/127.0.0.1/tcp/1510".to_vec(),
+                DEFAULT_NETWORK_ID,
+                NodeRole::BOOTNODE,
+            )
+            .unwrap();
+            assert_eq!(db.get_node_addresses(DEFAULT_NETWORK_ID).len(), 1);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(db.update_config(config), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn observer_registration_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let selector = [0xde, 0xad, 0xbe, 0xef];
+            db.new_account(did.clone(), cid, hash_auth_material(b"bfdh87y*(TD*&^*S&io"))
+                .unwrap();
+
+            // the off-chain environment can't dispatch callbacks, so only registration is exercised
+            db.add_observer(did.clone(), accounts.django, selector)
+                .unwrap();
+            assert_eq!(
+                db.add_observer(did.clone(), accounts.django, selector),
+                Err(Error::AlreadyExists)
+            );
+            assert_eq!(
+                db.get_observers(did.clone()),
+                vec![Observer {
+                    contract: accounts.django,
+                    selector
+                }]
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                db.remove_observer(did.clone(), accounts.django),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.remove_observer(did.clone(), accounts.django).unwrap();
+            assert_eq!(
+                db.remove_observer(did.clone(), accounts.django),
+                Err(Error::ObserverNotFound)
+            );
+            assert!(db.get_observers(did).is_empty());
+        }
+
+        #[ink::test]
+        fn migration_import_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let app = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let user = "did:sam:user:imported".as_bytes().to_vec();
+            let node = "/ip4/127.0.0.1/tcp/1509".as_bytes().to_vec();
+            let blacklisted = "/ip4/127.0.0.2/tcp/1509".as_bytes().to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let account = AccountInfo {
+                did_document_uri: Vec::new(),
+                hashtable_cid: "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                    .as_bytes()
+                    .to_vec(),
+                auth_hash: hash_auth_material(&auth_material),
+                auth_policy: None,
+                created_at: 0,
+                key_agreement: None,
+                controllers: vec![accounts.bob],
+                auth_key: None,
+                status: AccountStatus::Active,
+            };
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                db.import_accounts(vec![(app.clone(), account.clone())]),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.import_accounts(vec![(app.clone(), account.clone())])
+                .unwrap();
+            assert_eq!(
+                db.import_accounts(vec![(app.clone(), account.clone())]),
+                Err(Error::AlreadyExists)
+            );
+            for _ in 0..5 {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            let imported = ImportedSubscriber {
+                node: node.clone(),
+                owner: accounts.charlie,
+                subscribed_at: 0,
+            };
+            db.import_subscribers(vec![(app.clone(), vec![imported.clone(), imported])])
+                .unwrap();
+            db.import_restrictions(vec![(app.clone(), vec![user.clone()])])
+                .unwrap();
+
+            // imported state behaves like state created here
+            assert_eq!(
+                db.get_account_info(app.clone(), auth_material),
+                Some(account.clone())
+            );
+            assert_eq!(
+                db.get_subscribers_with_tenure(app.clone(), DEFAULT_NETWORK_ID),
+                vec![(node.clone(), 5)]
+            );
+            assert!(db.is_restricted(user.clone(), app.clone()));
+
+            // imported subscribers are checked like new ones and stay removable by their owner
+            db.blacklist(blacklisted.clone()).unwrap();
+            assert_eq!(
+                db.import_subscribers(vec![(
+                    app.clone(),
+                    vec![ImportedSubscriber {
+                        node: blacklisted,
+                        owner: accounts.charlie,
+                        subscribed_at: 0,
+                    }]
+                )]),
+                Err(Error::NodeBlacklisted)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            db.unsubscribe_node(app.clone(), node).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            db.finalize_migration().unwrap();
+            assert!(db.migration_finalized());
+            assert_eq!(
+                db.import_restrictions(vec![(app, vec![user])]),
+                Err(Error::MigrationFinalized)
+            );
+            assert_eq!(db.finalize_migration(), Err(Error::MigrationFinalized));
+        }
+
+        #[ink::test]
+        fn migration_round_trip_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut old = DbContract::new();
+            let app = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let user = "did:sam:user:F9S0DF9S0DS0SD9F0S".as_bytes().to_vec();
+            let node = "/ip4/127.0.0.1/tcp/1509".as_bytes().to_vec();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+                .as_bytes()
+                .to_vec();
+            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            old.new_account(app.clone(), cid.clone(), hash_auth_material(&auth_material))
+                .unwrap();
+            old.new_account(user.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+            old.restrict(user.clone(), app.clone()).unwrap();
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            old.subscribe_node(app.clone(), node.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            for _ in 0..3 {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            let exported_accounts = old.export_accounts(0, 10);
+            let exported_subscriptions = old.export_subscriptions(0, 10);
+            let exported_restrictions = old.export_restrictions(0, 10);
+
+            // the new instance lives at another address, so its storage starts empty
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            let mut new = DbContract::new();
+            new.import_accounts(scale::Decode::decode(&mut &exported_accounts[..]).unwrap())
+                .unwrap();
+            new.import_subscribers(
+                scale::Decode::decode(&mut &exported_subscriptions[..]).unwrap(),
+            )
+            .unwrap();
+            new.import_restrictions(
+                scale::Decode::decode(&mut &exported_restrictions[..]).unwrap(),
+            )
+            .unwrap();
+
+            // owners and tenure carry over
+            assert_eq!(
+                new.get_subscribers_with_tenure(app.clone(), DEFAULT_NETWORK_ID),
+                vec![(node.clone(), 3)]
+            );
+            assert!(new.is_restricted(user, app.clone()));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            new.unsubscribe_node(app, node).unwrap();
+        }
+
+        #[ink::test]
+        fn operator_node_management_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let other = "/ip4/192.168.44.206/tcp/1509".as_bytes().to_vec();
+            db.grant_role(accounts.charlie, Role::NodeRegistrar)
+                .unwrap();
+
+            // registrars list nodes on behalf of their operators
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                db.add_address_for(
+                    addr.clone(),
+                    DEFAULT_NETWORK_ID,
+                    NodeRole::BOOTNODE,
+                    accounts.bob
+                ),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            db.add_address_for(
+                addr.clone(),
+                DEFAULT_NETWORK_ID,
+                NodeRole::BOOTNODE,
+                accounts.bob,
+            )
+            .unwrap();
+            db.add_address_for(
+                other.clone(),
+                DEFAULT_NETWORK_ID,
+                NodeRole::BOOTNODE,
+                accounts.django,
+            )
+            .unwrap();
+            assert_eq!(db.get_node_operator(addr.clone()), Some(accounts.bob));
+
+            // operators manage only their own addresses, registrars any of them
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(db.remove_address(other.clone()), Err(Error::Unauthorized));
+            db.remove_address(addr.clone()).unwrap();
+            assert_eq!(db.remove_address(addr), Err(Error::Unauthorized));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            db.remove_address(other).unwrap();
+            assert!(db.get_node_addresses(DEFAULT_NETWORK_ID).is_empty());
+        }
+
+        #[ink::test]
+        fn timelock_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let config = Config {
+                max_bootnodes: 2,
+                ..db.get_config()
+            };
+            let id = db.update_config(config.clone()).unwrap();
+            assert_eq!(
+                db.get_scheduled(id)
+                    .map(|scheduled| scheduled.executable_at),
+                Some(DEFAULT_TIMELOCK_DELAY)
+            );
+
+            // nothing changes before the delay has passed
+            assert_eq!(db.execute_scheduled(id), Err(Error::OperationNotReady));
+            for _ in 0..DEFAULT_TIMELOCK_DELAY {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(db.cancel_scheduled(id), Err(Error::Unauthorized));
+            db.execute_scheduled(id).unwrap();
+            assert_eq!(db.get_config(), config);
+            assert_eq!(db.execute_scheduled(id), Err(Error::OperationNotFound));
+
+            // the owner can cancel a scheduled operation
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            let id = db
+                .update_config(Config {
+                    max_bootnodes: 5,
+                    ..config.clone()
+                })
+                .unwrap();
+            db.cancel_scheduled(id).unwrap();
+            assert_eq!(db.get_scheduled(id), None);
+            assert_eq!(db.cancel_scheduled(id), Err(Error::OperationNotFound));
+            assert_eq!(db.get_config(), config);
+        }
+
+        #[ink::test]
+        fn node_records_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let addr = "/ip4/192.168.44.205/tcp/1509/p2p/12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN"
+                .as_bytes()
+                .to_vec();
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            db.add_address_for(
+                addr.clone(),
+                DEFAULT_NETWORK_ID,
+                NodeRole::BOOTNODE,
+                accounts.bob,
+            )
+            .unwrap();
+
+            let record = db.get_node_record(addr.clone()).unwrap();
+            assert_eq!(
+                record.peer_id,
+                b"12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN".to_vec()
+            );
+            assert_eq!(record.registered_at_block, 1);
+            assert_eq!(record.operator, accounts.bob);
+
+            // operators describe their own nodes
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(
+                db.set_node_metadata(addr.clone(), b"eu-west".to_vec(), 200),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                db.set_node_metadata(addr.clone(), [b'x'; 33].to_vec(), 200),
+                Err(Error::CapacityExceeded)
+            );
+            db.set_node_metadata(addr.clone(), b"eu-west".to_vec(), 200)
+                .unwrap();
+            let record = db.get_node_record(addr.clone()).unwrap();
+            assert_eq!(record.region, b"eu-west".to_vec());
+            assert_eq!(record.capacity, 200);
+
+            db.remove_address(addr.clone()).unwrap();
+            assert_eq!(db.get_node_record(addr), None);
+        }
+
+        #[ink::test]
+        fn node_heartbeats_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let other = "/ip4/192.168.44.206/tcp/1509".as_bytes().to_vec();
+            db.add_address_for(
+                addr.clone(),
+                DEFAULT_NETWORK_ID,
+                NodeRole::BOOTNODE,
+                accounts.bob,
+            )
+            .unwrap();
+            db.add_address(other.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                .unwrap();
+
+            // only the operator keeps its node alive
+            for _ in 0..DEFAULT_NODE_TTL {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(db.heartbeat(other.clone()), Err(Error::Unauthorized));
+            db.heartbeat(addr.clone()).unwrap();
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+
+            // stale nodes are skipped until pruned
+            assert_eq!(
+                db.get_node_addresses(DEFAULT_NETWORK_ID),
+                vec![addr.clone()]
+            );
+            assert_eq!(db.bootstrap_bundle().bootnodes, vec![addr.clone()]);
+            assert_eq!(
+                db.get_nodes_by_transport(DEFAULT_NETWORK_ID, Transport::Tcp, 10),
+                vec![addr.clone()]
+            );
+            assert_eq!(db.get_node_record(other.clone()).unwrap().last_seen, 0);
+            assert_eq!(db.prune_stale_nodes(), Ok(1));
+            assert_eq!(db.get_node_record(other), None);
+            assert_eq!(db.prune_stale_nodes(), Ok(0));
+            assert_eq!(
+                db.get_node_record(addr).unwrap().last_seen,
+                DEFAULT_NODE_TTL
+            );
+        }
+
+        #[ink::test]
+        fn uptime_scores_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new_with_config(InitConfig {
+                owner: accounts.alice,
+                network_id: DEFAULT_NETWORK_ID,
+                max_bootnodes: DEFAULT_MAX_BOOTNODES,
+                epoch_length: 10,
+                registration_fee: DEFAULT_REGISTRATION_FEE,
+                node_stake: DEFAULT_NODE_STAKE,
+                permissioned: DEFAULT_PERMISSIONED,
+            });
+            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let other = "/ip4/192.168.44.206/tcp/1509".as_bytes().to_vec();
+            for node in [&other, &addr] {
+                db.add_address(node.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                    .unwrap();
+            }
+            assert_eq!(db.get_node_uptime(addr.clone()), Some(100));
+
+            // epochs without a heartbeat lower the score
+            let advance = |blocks| {
+                for _ in 0..blocks {
+                    ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+                }
+            };
+            advance(10);
+            db.heartbeat(addr.clone()).unwrap();
+            assert_eq!(db.get_node_uptime(addr.clone()), Some(100));
+            assert_eq!(db.get_node_uptime(other.clone()), Some(50));
+
+            // equally reputable nodes are ordered by uptime
+            assert_eq!(
+                db.get_node_addresses_by_reputation(DEFAULT_NETWORK_ID, 0),
+                vec![addr.clone(), other.clone()]
+            );
+            assert_eq!(
+                db.get_node_addresses_by_uptime(DEFAULT_NETWORK_ID, 60),
+                vec![addr.clone()]
+            );
+
+            // the score covers the last 32 epochs at most
+            advance(40);
+            assert_eq!(db.get_node_uptime(addr.clone()), Some(33));
+            advance(UPTIME_WINDOW * 10);
+            db.heartbeat(addr.clone()).unwrap();
+            assert_eq!(db.get_node_uptime(addr.clone()), Some(3));
+            assert_eq!(db.get_node_uptime(other), Some(0));
+            assert_eq!(db.get_node_uptime(b"/ip4/10.0.0.1/tcp/1".to_vec()), None);
+        }
+
+        #[ink::test]
+        fn dial_failure_reports_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let addrs = (0..4)
+                .map(|i| format!("/ip4/10.0.0.{}/tcp/1509", i).into_bytes())
+                .collect::<Vec<_>>();
+            let operators = [
+                accounts.bob,
+                accounts.charlie,
+                accounts.django,
+                accounts.eve,
+            ];
+            for (addr, operator) in addrs.iter().zip(operators) {
+                db.add_address_for(
+                    addr.clone(),
+                    DEFAULT_NETWORK_ID,
+                    NodeRole::BOOTNODE,
+                    operator,
+                )
+                .unwrap();
+            }
+            let target = addrs[0].clone();
+
+            // only operators of other nodes report, once per node every epoch
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.frank);
+            assert_eq!(
+                db.report_dial_failure(target.clone()),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                db.report_dial_failure(target.clone()),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            db.report_dial_failure(target.clone()).unwrap();
+            assert_eq!(
+                db.report_dial_failure(target.clone()),
+                Err(Error::AlreadyExists)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            db.report_dial_failure(target.clone()).unwrap();
+            assert!(db.get_node_addresses(DEFAULT_NETWORK_ID).contains(&target));
+
+            // corroborated failures delist the node without removing it
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            db.report_dial_failure(target.clone()).unwrap();
+            assert!(!db.get_node_addresses(DEFAULT_NETWORK_ID).contains(&target));
+            assert_eq!(db.prune_stale_nodes(), Ok(0));
+
+            // the node returns once its next heartbeat is followed by the probation period
+            for _ in 0..PROBATION_PERIOD {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            assert!(!db.get_node_addresses(DEFAULT_NETWORK_ID).contains(&target));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.heartbeat(target.clone()).unwrap();
+            for _ in 0..PROBATION_PERIOD - 1 {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            assert!(!db.get_node_addresses(DEFAULT_NETWORK_ID).contains(&target));
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            assert!(db.get_node_addresses(DEFAULT_NETWORK_ID).contains(&target));
+        }
+
+        #[ink::test]
+        fn onboarding_quotas_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let dids = (0..6)
+                .map(|i| format!("did:sam:apps:DS0SD9F0SF9S0DF9S{}", i).into_bytes())
+                .collect::<Vec<_>>();
+            db.add_address_for(
+                addr.clone(),
+                DEFAULT_NETWORK_ID,
+                NodeRole::BOOTNODE,
+                accounts.bob,
+            )
+            .unwrap();
+            assert_eq!(db.get_onboarding_quota(accounts.alice), None);
+            assert_eq!(
+                db.get_onboarding_quota(accounts.bob),
+                Some(ONBOARDING_QUOTA)
+            );
+
+            // a new operator's nodes serve a limited number of subscriptions
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            for did in &dids[..4] {
+                db.subscribe_node(did.clone(), addr.clone(), DEFAULT_NETWORK_ID)
+                    .unwrap();
+            }
+            assert_eq!(
+                db.subscribe_node(dids[4].clone(), addr.clone(), DEFAULT_NETWORK_ID),
+                Err(Error::OnboardingQuotaExceeded)
+            );
+
+            // clean epochs grow the quota, once per epoch
+            for _ in 0..DEFAULT_EPOCH_LENGTH {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            db.heartbeat(addr.clone()).unwrap();
+            db.heartbeat(addr.clone()).unwrap();
+            assert_eq!(db.get_operator_trust(accounts.bob).clean_epochs, 1);
+            assert_eq!(
+                db.get_onboarding_quota(accounts.bob),
+                Some(ONBOARDING_QUOTA + ONBOARDING_QUOTA_STEP)
+            );
+            db.subscribe_node(dids[4].clone(), addr.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+
+            // trusted operators serve without a quota
+            db.operator_trust.insert(
+                accounts.bob,
+                &OperatorTrust {
+                    clean_epochs: TRUSTED_OPERATOR_EPOCHS,
+                    last_epoch: Some(1),
+                },
+            );
+            assert_eq!(db.get_onboarding_quota(accounts.bob), None);
+
+            // slashing starts the ramp over
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.slash(addr, b"equivocation".to_vec()).unwrap();
+            assert_eq!(
+                db.get_onboarding_quota(accounts.bob),
+                Some(ONBOARDING_QUOTA)
+            );
+        }
+
+        #[ink::test]
+        fn node_stakes_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let contract = ink::env::account_id::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_account_balance::<ink::env::DefaultEnvironment>(contract, 1_000);
+            let mut db = DbContract::new();
+            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let other = "/ip4/192.168.44.206/tcp/1509".as_bytes().to_vec();
+            let mut config = db.get_config();
+            config.node_stake = 100;
+            config.timelock_delay = 0;
+            let id = db.update_config(config).unwrap();
+            for _ in 0..DEFAULT_TIMELOCK_DELAY {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            db.execute_scheduled(id).unwrap();
+
+            // registrations lock the value sent, which the treasury can't touch
+            assert_eq!(
+                db.add_address_for(
+                    addr.clone(),
+                    DEFAULT_NETWORK_ID,
+                    NodeRole::BOOTNODE,
+                    accounts.bob
+                ),
+                Err(Error::InsufficientStake)
+            );
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            db.add_address_for(
+                addr.clone(),
+                DEFAULT_NETWORK_ID,
+                NodeRole::BOOTNODE,
+                accounts.bob,
+            )
+            .unwrap();
+            db.add_address_for(
+                other.clone(),
+                DEFAULT_NETWORK_ID,
+                NodeRole::BOOTNODE,
+                accounts.charlie,
+            )
+            .unwrap();
+            assert_eq!(db.get_node_record(addr.clone()).unwrap().stake, 100);
+            assert_eq!(db.treasury(), 800);
+
+            // clean removals refund the registrar that paid the stake, slashing moves it to
+            // the treasury
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.remove_address(addr).unwrap();
+            assert_eq!(db.claimable_balance(accounts.bob), 0);
+            assert_eq!(db.claimable_balance(accounts.alice), 100);
+            assert_eq!(
+                db.slash(other.clone(), b"eclipse attempt".to_vec()),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.slash(other.clone(), b"eclipse attempt".to_vec())
+                .unwrap();
+            assert_eq!(db.claimable_balance(accounts.charlie), 0);
+            assert_eq!(db.treasury(), 900);
+            assert_eq!(
+                db.slash(other, b"eclipse attempt".to_vec()),
+                Err(Error::NodeNotFound)
+            );
+
+            // suggestions lock the stake too, and the listed node keeps it
+            let suggested = "/ip4/192.168.44.207/tcp/1509".as_bytes().to_vec();
+            let withdrawn = "/ip4/192.168.44.208/tcp/1509".as_bytes().to_vec();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(0);
+            assert_eq!(
+                db.suggest_node(suggested.clone()),
+                Err(Error::InsufficientStake)
+            );
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            db.suggest_node(suggested.clone()).unwrap();
+            assert_eq!(db.treasury(), 800);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(
+                db.withdraw_suggestion(suggested.clone()),
+                Err(Error::Unauthorized)
+            );
+            db.approve_suggestion(suggested.clone()).unwrap();
+            assert_eq!(db.get_node_record(suggested).unwrap().stake, 100);
+            assert_eq!(db.treasury(), 800);
 
-            // emit event
-            self.env().emit_event(AccountCreated { did });
+            // withdrawn suggestions refund the suggester
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            db.suggest_node(withdrawn.clone()).unwrap();
+            db.withdraw_suggestion(withdrawn.clone()).unwrap();
+            assert_eq!(db.claimable_balance(accounts.django), 100);
+            assert_eq!(db.treasury(), 700);
+            assert_eq!(
+                db.withdraw_suggestion(withdrawn),
+                Err(Error::SuggestionNotFound)
+            );
         }
 
-        /// Adds your network address to the list of nodes using FIFO.
-        /// This helps to eventually remove nodes that may exit without the proper bookkeeping
-        #[ink(message, payable)]
-        pub fn add_address(&mut self, addr: Multiaddr) {
-            // Check if the address already exists in the nodes vector
-            if !self.nodes.contains(&addr) {
-                // If the vector has reached its maximum height, remove the oldest item before adding a new one
-                if self.nodes.len() >= 10 {
-                    self.nodes.remove(0);
-                }
-                // Add the address to the end of the vector
-                self.nodes.push(addr.clone());
+        #[ink::test]
+        fn bonded_reports_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let contract = ink::env::account_id::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_account_balance::<ink::env::DefaultEnvironment>(contract, 1_000);
+            let mut db = DbContract::new();
+            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let evidence = "QmEquivocation".as_bytes().to_vec();
+            db.config.node_stake = 100;
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            db.add_address_for(
+                addr.clone(),
+                DEFAULT_NETWORK_ID,
+                NodeRole::BOOTNODE,
+                accounts.charlie,
+            )
+            .unwrap();
 
-                // emit event
-                self.env().emit_event(BootNodeAdded { address: addr });
-            } else {
-                self.env().emit_event(EntryNotFound { entry_value: addr });
-            }
-        }
+            // reports lock a bond covering the bootnode stake
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(50);
+            assert_eq!(
+                db.report_with_bond(addr.clone(), evidence.clone()),
+                Err(Error::InsufficientStake)
+            );
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            assert_eq!(
+                db.report_with_bond(b"/ip4/10.0.0.1/tcp/1".to_vec(), evidence.clone()),
+                Err(Error::NodeNotFound)
+            );
+            let upheld = db.report_with_bond(addr.clone(), evidence.clone()).unwrap();
+            let frivolous = db.report_with_bond(addr.clone(), evidence.clone()).unwrap();
+            assert_eq!(db.get_node_report(upheld).unwrap().bond, 100);
+            assert_eq!(db.treasury(), 700);
 
-        /// Remove node address from bootnodes
-        #[ink(message, payable)]
-        pub fn remove_address(&mut self, addr: Multiaddr) {
-            // Check if the address already exists in the nodes vector
-            if self.nodes.contains(&addr) {
-                // remove address
-                let filtered_nodes = self
-                    .nodes
-                    .iter()
-                    .cloned()
-                    .filter(|address| *address != addr)
-                    .collect::<Vec<_>>();
+            // only the owner adjudicates
+            assert_eq!(db.adjudicate_report(upheld, true), Err(Error::Unauthorized));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
 
-                self.nodes = filtered_nodes;
+            // an upheld report slashes the node and pays the reporter its bond plus a share
+            db.adjudicate_report(upheld, true).unwrap();
+            assert!(db.get_node_record(addr.clone()).is_none());
+            assert_eq!(db.claimable_balance(accounts.bob), 150);
+            assert_eq!(db.claimable_balance(accounts.charlie), 0);
+            assert_eq!(db.treasury(), 750);
+            assert_eq!(
+                db.adjudicate_report(upheld, true),
+                Err(Error::ReportNotFound)
+            );
 
-                // emit event
-                self.env().emit_event(BootNodeRemoved { address: addr });
-            } else {
-                self.env().emit_event(EntryNotFound { entry_value: addr });
-            }
+            // a rejected report forfeits the bond to the treasury
+            db.adjudicate_report(frivolous, false).unwrap();
+            assert_eq!(db.claimable_balance(accounts.bob), 150);
+            assert_eq!(db.treasury(), 850);
+            assert!(db.get_node_report(frivolous).is_none());
         }
 
-        /// Retrieves the list of bootnodes available
-        #[ink(message, payable)]
-        pub fn get_node_addresses(&self) -> Vec<u8> {
-            self.nodes
-                .iter()
-                .flat_map(|addr| {
-                    let separator: &[u8] = b"$$$";
-                    addr.iter()
-                        .chain(separator.iter())
-                        .copied()
-                        .collect::<Vec<u8>>()
-                })
-                .collect()
-        }
+        #[ink::test]
+        fn node_ring_buffer_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new_with_config(InitConfig {
+                owner: accounts.alice,
+                network_id: DEFAULT_NETWORK_ID,
+                max_bootnodes: 3,
+                epoch_length: DEFAULT_EPOCH_LENGTH,
+                registration_fee: DEFAULT_REGISTRATION_FEE,
+                node_stake: DEFAULT_NODE_STAKE,
+                permissioned: DEFAULT_PERMISSIONED,
+            });
+            let addrs = (0..5)
+                .map(|i| format!("/ip4/10.0.0.{}/tcp/1509", i).into_bytes())
+                .collect::<Vec<_>>();
+            for addr in &addrs[..3] {
+                db.add_address(addr.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                    .unwrap();
+            }
 
-        /// Retrieves the hashtable CID of an account
-        #[ink(message, payable)]
-        pub fn get_account_ht_cid(&self, did: DID, auth_material: Vec<u8>) -> Vec<u8> {
-            if let Some(account_info) = self.accounts.get(&did) {
-                if account_info.auth_material == auth_material {
-                    account_info.hashtable_cid.clone()
-                } else {
-                    Vec::new()
+            // removing from the middle leaves the order intact
+            db.remove_address(addrs[1].clone()).unwrap();
+            assert_eq!(
+                db.bootstrap_bundle().bootnodes,
+                vec![addrs[0].clone(), addrs[2].clone()]
+            );
+            assert_eq!(db.network_stats().bootnodes, 2);
+
+            // the oldest node is still evicted first once the list is full again
+            db.add_address(addrs[3].clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                .unwrap();
+            db.add_address(addrs[4].clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                .unwrap();
+            assert_eq!(
+                db.bootstrap_bundle().bootnodes,
+                vec![addrs[2].clone(), addrs[3].clone(), addrs[4].clone()]
+            );
+            assert_eq!(db.get_node_record(addrs[0].clone()), None);
+
+            // slots stay contiguous while nodes come and go behind a long-lived oldest node
+            for round in 0..20 {
+                let middle = db.bootstrap_bundle().bootnodes[1].clone();
+                db.remove_address(middle).unwrap();
+                let addr = format!("/ip4/10.0.2.{}/tcp/1509", round).into_bytes();
+                db.add_address(addr, DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                    .unwrap();
+                assert!(db.node_count <= 3);
+                assert!(!db.nodes.contains(db.node_count));
+                for addr in db.bootstrap_bundle().bootnodes {
+                    assert!(db.node_slots.get(&addr).unwrap() < db.node_count);
                 }
-            } else {
-                Vec::new()
             }
+            assert_eq!(
+                db.bootstrap_bundle().bootnodes,
+                vec![
+                    addrs[2].clone(),
+                    b"/ip4/10.0.2.18/tcp/1509".to_vec(),
+                    b"/ip4/10.0.2.19/tcp/1509".to_vec()
+                ]
+            );
+
+            // emptying the list and refilling it reuses the freed slots
+            for addr in db.bootstrap_bundle().bootnodes {
+                db.remove_address(addr).unwrap();
+            }
+            assert!(db.bootstrap_bundle().bootnodes.is_empty());
+            assert_eq!((db.nodes_head, db.nodes_tail), (None, None));
+            db.add_address(addrs[0].clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                .unwrap();
+            assert_eq!(db.bootstrap_bundle().bootnodes, vec![addrs[0].clone()]);
+            assert_eq!(db.network_stats().bootnodes, 1);
         }
 
-        /// Updates the hashtable CID of an account
-        #[ink(message, payable)]
-        pub fn update_account_ht_cid(&mut self, did: DID, ht_cid: Vec<u8>) {
-            if let Some(account) = self.accounts.get(&did) {
-                let mut new_account = account.clone();
-                new_account.hashtable_cid = ht_cid.clone();
-                self.accounts.insert(&did, &new_account);
+        #[ink::test]
+        fn node_reputation_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let addrs = (0..3)
+                .map(|i| format!("/ip4/10.0.0.{}/tcp/1509", i).into_bytes())
+                .collect::<Vec<_>>();
+            db.add_address_for(
+                addrs[0].clone(),
+                DEFAULT_NETWORK_ID,
+                NodeRole::BOOTNODE,
+                accounts.bob,
+            )
+            .unwrap();
+            db.add_address_for(
+                addrs[1].clone(),
+                DEFAULT_NETWORK_ID,
+                NodeRole::BOOTNODE,
+                accounts.charlie,
+            )
+            .unwrap();
+            db.add_address_for(
+                addrs[2].clone(),
+                DEFAULT_NETWORK_ID,
+                NodeRole::BOOTNODE,
+                accounts.django,
+            )
+            .unwrap();
 
-                // emit event
-                self.env().emit_event(HashTableAddressUpdated {
-                    did,
-                    ipfs_address: ht_cid,
-                });
-            } else {
-                // emit event indicating the absence of the account
-                self.env().emit_event(EntryNotFound { entry_value: did });
+            // only operators of other listed nodes can rate a node
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            assert_eq!(db.vouch(addrs[0].clone()), Err(Error::Unauthorized));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(db.vouch(addrs[0].clone()), Err(Error::Unauthorized));
+
+            // each operator rates a node once per epoch
+            db.vouch(addrs[1].clone()).unwrap();
+            assert_eq!(db.vouch(addrs[1].clone()), Err(Error::RateLimited));
+            assert_eq!(db.report(addrs[1].clone()), Err(Error::RateLimited));
+            db.report(addrs[2].clone()).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            db.vouch(addrs[1].clone()).unwrap();
+            assert_eq!(db.get_node_record(addrs[1].clone()).unwrap().reputation, 2);
+            assert_eq!(db.get_node_record(addrs[2].clone()).unwrap().reputation, -1);
+
+            assert_eq!(
+                db.get_node_addresses_by_reputation(DEFAULT_NETWORK_ID, 0),
+                vec![addrs[1].clone(), addrs[0].clone()]
+            );
+            assert_eq!(
+                db.get_node_addresses_by_reputation(DEFAULT_NETWORK_ID, 1),
+                vec![addrs[1].clone()]
+            );
+
+            // a new epoch allows rating the same node again
+            for _ in 0..DEFAULT_EPOCH_LENGTH {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
             }
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            db.report(addrs[1].clone()).unwrap();
+            assert_eq!(db.get_node_record(addrs[1].clone()).unwrap().reputation, 1);
         }
 
-        /// Subscribe to join nodes supporting application
-        #[ink(message, payable)]
-        pub fn subscribe_node(&mut self, did: DID, addr: Multiaddr) {
-            if let Some(subs) = self.subscribers.get(&did) {
-                if !subs.contains(&addr) {
-                    // append to the vector of multiaddresses
-                    let mut subscribers = subs.clone();
-                    subscribers.push(addr.clone());
-                    self.subscribers.insert(&did, &subscribers);
-                }
-            } else {
-                // create new, this node is the first of many
-                let mut subscribers: Vec<Multiaddr> = Vec::new();
-                subscribers.push(addr.clone());
-                self.subscribers.insert(&did, &subscribers);
-            }
+        #[ink::test]
+        fn node_blacklist_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut db = DbContract::new();
+            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let did = "did:sam:apps:DS8YWpeHYrXxY4Nk7bXJvt9cPhJyVyQGHgJ35WdKwLDJzAh"
+                .as_bytes()
+                .to_vec();
+            db.add_address_for(
+                addr.clone(),
+                DEFAULT_NETWORK_ID,
+                NodeRole::BOOTNODE,
+                accounts.bob,
+            )
+            .unwrap();
 
-            // emit event
-            self.env()
-                .emit_event(TopicSubscriptionComplete { did, node: addr });
-        }
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(db.blacklist(addr.clone()), Err(Error::Unauthorized));
 
-        /// Stop supporting application
-        #[ink(message, payable)]
-        pub fn unsubscribe_node(&mut self, did: DID, address: Multiaddr) {
-            if let Some(nodes) = self.subscribers.get(&did) {
-                let filtered_nodes = nodes
-                    .iter()
-                    .cloned()
-                    .filter(|addr| *addr != address)
-                    .collect::<Vec<_>>();
-                self.subscribers.insert(&did, &filtered_nodes);
+            // blacklisting evicts the node and keeps it out
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.blacklist(addr.clone()).unwrap();
+            assert!(db.is_blacklisted(addr.clone()));
+            assert_eq!(db.get_node_record(addr.clone()), None);
+            assert_eq!(db.get_node_operator(addr.clone()), None);
+            assert_eq!(
+                db.add_address(addr.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE),
+                Err(Error::NodeBlacklisted)
+            );
+            assert_eq!(db.suggest_node(addr.clone()), Err(Error::NodeBlacklisted));
+            assert_eq!(
+                db.subscribe_node(did.clone(), addr.clone(), DEFAULT_NETWORK_ID),
+                Err(Error::NodeBlacklisted)
+            );
 
-                // emit event
-                self.env()
-                    .emit_event(TopicSubscriptionComplete { did, node: address });
-            }
+            db.unblacklist(addr.clone()).unwrap();
+            assert!(!db.is_blacklisted(addr.clone()));
+            db.subscribe_node(did, addr.clone(), DEFAULT_NETWORK_ID)
+                .unwrap();
+            db.add_address(addr, DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                .unwrap();
         }
 
-        /// Get all nodes supporting an application
-        #[ink(message, payable)]
-        pub fn get_subscribers(&mut self, did: DID) -> Vec<u8> {
-            if let Some(nodes) = self.subscribers.get(&did) {
-                let separator = b"$$$".to_vec();
-                nodes
-                    .iter()
-                    .flat_map(|vector| vector.iter().chain(separator.iter()))
-                    .copied()
-                    .collect()
-            } else {
-                Vec::new()
+        #[ink::test]
+        fn random_bootnodes_work() {
+            let mut db = DbContract::new();
+            let addrs = (0..10)
+                .map(|i| format!("/ip4/10.0.0.{}/tcp/1509", i).into_bytes())
+                .collect::<Vec<_>>();
+            for addr in &addrs {
+                db.add_address(addr.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                    .unwrap();
             }
-        }
 
-        /// Add an application to the restricted list
-        #[ink(message, payable)]
-        pub fn restrict(&mut self, user_did: DID, app_did: DID) {
-            // check for existence of user and application
-            if self.accounts.contains(&user_did) {
-                if self.accounts.contains(&app_did) {
-                    let users_list = if let Some(users) = self.restricted.get(&app_did) {
-                        let mut users = users.clone();
-                        users.push(user_did.clone());
-                        users
-                    } else {
-                        let mut users = Vec::new();
-                        users.push(user_did.clone());
-                        users
-                    };
+            // the same seed in the same block gives the same distinct subset
+            let picked = db.get_random_bootnodes(DEFAULT_NETWORK_ID, 4, 7);
+            assert_eq!(picked.len(), 4);
+            assert_eq!(db.get_random_bootnodes(DEFAULT_NETWORK_ID, 4, 7), picked);
+            assert!(picked.iter().all(|addr| addrs.contains(addr)));
+            let mut distinct = picked.clone();
+            distinct.sort();
+            distinct.dedup();
+            assert_eq!(distinct.len(), 4);
 
-                    self.restricted.insert(app_did.clone(), &users_list);
+            // other seeds spread the picks
+            assert!(
+                (0..8).any(|seed| db.get_random_bootnodes(DEFAULT_NETWORK_ID, 4, seed) != picked)
+            );
 
-                    // emit event
-                    self.env().emit_event(RestrictApplicationAccess {
-                        user_did,
-                        application_did: app_did,
-                    });
-                } else {
-                    self.env().emit_event(EntryNotFound {
-                        entry_value: app_did,
-                    });
-                }
-            } else {
-                self.env().emit_event(EntryNotFound {
-                    entry_value: user_did,
-                });
-            }
+            // the subset is bounded by the list
+            let mut all = db.get_random_bootnodes(DEFAULT_NETWORK_ID, 100, 7);
+            all.sort();
+            let mut expected = addrs;
+            expected.sort();
+            assert_eq!(all, expected);
         }
 
-        /// Unrestrict an application's access to user data
-        #[ink(message, payable)]
-        pub fn unrestrict(&mut self, user_did: DID, app_did: DID) {
-            if let Some(users) = self.restricted.get(&app_did) {
-                let users_list = users
-                    .iter()
-                    .cloned()
-                    .filter(|did| *did != user_did)
-                    .collect::<Vec<_>>();
+        #[ink::test]
+        fn node_roles_work() {
+            let mut db = DbContract::new();
+            let bootnode = "/ip4/10.0.0.1/tcp/1509".as_bytes().to_vec();
+            let storage = "/ip4/10.0.0.2/tcp/1509".as_bytes().to_vec();
+            let relay = "/ip4/10.0.0.3/tcp/1509".as_bytes().to_vec();
+            db.add_address(bootnode.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                .unwrap();
+            db.add_address(
+                storage.clone(),
+                DEFAULT_NETWORK_ID,
+                NodeRole::STORAGE | NodeRole::RELAY,
+            )
+            .unwrap();
+            db.add_address(relay.clone(), DEFAULT_NETWORK_ID, NodeRole::RELAY)
+                .unwrap();
+            assert_eq!(
+                db.add_address(
+                    "/ip4/10.0.0.4/tcp/1509".as_bytes().to_vec(),
+                    DEFAULT_NETWORK_ID,
+                    NodeRole(0)
+                ),
+                Err(Error::InvalidNodeRole)
+            );
 
-                self.restricted.insert(&app_did, &users_list);
+            assert_eq!(
+                db.get_nodes_by_role(DEFAULT_NETWORK_ID, NodeRole::BOOTNODE),
+                vec![bootnode.clone()]
+            );
+            assert_eq!(
+                db.get_nodes_by_role(DEFAULT_NETWORK_ID, NodeRole::RELAY),
+                vec![storage.clone(), relay]
+            );
+            assert_eq!(
+                db.get_nodes_by_role(DEFAULT_NETWORK_ID, NodeRole::STORAGE | NodeRole::RELAY),
+                vec![storage.clone()]
+            );
+            assert_eq!(
+                db.get_node_record(storage.clone()).unwrap().roles,
+                NodeRole::STORAGE | NodeRole::RELAY
+            );
 
-                // emit event
-                self.env().emit_event(UnrestrictApplicationAccess {
-                    user_did,
-                    application_did: app_did,
-                });
-            } else {
-                self.env().emit_event(EntryNotFound {
-                    entry_value: app_did,
-                });
-            }
+            // only bootnodes are handed to clients bootstrapping
+            assert_eq!(
+                db.get_node_addresses(DEFAULT_NETWORK_ID),
+                vec![bootnode.clone()]
+            );
+            assert_eq!(
+                db.get_random_bootnodes(DEFAULT_NETWORK_ID, 16, 7),
+                vec![bootnode.clone()]
+            );
+            assert_eq!(db.bootstrap_bundle().bootnodes, vec![bootnode]);
+            assert!(!db.get_node_addresses(DEFAULT_NETWORK_ID).contains(&storage));
         }
 
-        /// Check if an application is restricted
-        fn is_restricted(&self, did: DID, app_did: DID) -> bool {
-            if let Some(entry) = self.restricted.get(&did) {
-                // check if the application is part of our restriction list
-                entry.contains(&app_did)
-            } else {
-                false
-            }
-        }
+        #[ink::test]
+        fn multiaddr_validation_works() {
+            let mut db = DbContract::new();
+            let did = "did:sam:apps:DS8YWpeHYrXxY4Nk7bXJvt9cPhJyVyQGHgJ35WdKwLDJzAh"
+                .as_bytes()
+                .to_vec();
 
-        /// Fetch users that have restricted applications
-        #[ink(message, payable)]
-        pub fn get_restriction_list(&self, app_did: DID) -> Vec<u8> {
-            if let Some(users) = self.restricted.get(&app_did) {
-                let separator = b"$$$".to_vec();
-                return users
-                    .iter()
-                    .flat_map(|vector| vector.iter().chain(separator.iter()))
-                    .copied()
-                    .collect();
+            for addr in [
+                "/ip4/192.168.44.205/tcp/1509/p2p/12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN",
+                "/ip6/::1/udp/1509/quic-v1",
+                "/dns4/node.samaritan.io/tcp/443/wss",
+                "/ip4/192.168.44.207/udp/1510/webrtc-direct/certhash/uEiA",
+            ] {
+                assert_eq!(validate_multiaddr(addr.as_bytes()), Ok(()));
             }
-            Vec::new()
-        }
-    }
+            for (addr, error) in [
+                ("", ValidationError::Length),
+                ("ip4/127.0.0.1/tcp/1509", ValidationError::Malformed),
+                ("/ip4/127.0.0.1/tcp", ValidationError::Malformed),
+                ("/ip4/127.0.0.1//tcp/1509", ValidationError::Malformed),
+                ("/ip4/127.0.0.1/tcp/1509/", ValidationError::Malformed),
+                ("/tcp/1509", ValidationError::UnknownProtocol),
+                ("/ip4/127.0.0.1/sctp/1509", ValidationError::UnknownProtocol),
+                ("/ip4/127.0.0.256/tcp/1509", ValidationError::InvalidValue),
+                ("/ip4/127.0.0.1/tcp/65536", ValidationError::InvalidValue),
+                (
+                    "/dns4/node samaritan/tcp/443",
+                    ValidationError::InvalidValue,
+                ),
+                (
+                    "/ip4/127.0.0.1/udp/1509",
+                    ValidationError::UnsupportedTransport,
+                ),
+            ] {
+                assert_eq!(validate_multiaddr(addr.as_bytes()), Err(error), "{}", addr);
+            }
+            assert_eq!(
+                validate_multiaddr(&[b'/'; MAX_MULTIADDR_LEN + 1]),
+                Err(ValidationError::Length)
+            );
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
+            // registrations and subscriptions reject garbage bytes
+            let garbage = vec![0xde, 0xad, 0xbe, 0xef];
+            assert_eq!(
+                db.add_address(garbage.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE),
+                Err(Error::InvalidMultiaddr(ValidationError::Malformed))
+            );
+            assert_eq!(
+                db.subscribe_node(did, garbage, DEFAULT_NETWORK_ID),
+                Err(Error::InvalidMultiaddr(ValidationError::Malformed))
+            );
+        }
 
         #[ink::test]
-        fn add_address_works() {
+        fn batch_node_registration_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
             let mut db = DbContract::new();
-            let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
-            db.add_address(addr.clone());
+            let addrs = (0..3)
+                .map(|i| format!("/ip4/10.0.0.{}/tcp/1509", i).into_bytes())
+                .collect::<Vec<_>>();
 
-            // Add the "$$$" separator
-            let mut result = addr.clone();
-            result.push(b'$');
-            result.push(b'$');
-            result.push(b'$');
+            // batches are bounded
+            let oversized = (0..=MAX_NODE_BATCH)
+                .map(|i| format!("/ip4/10.0.1.{}/tcp/1509", i).into_bytes())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                db.add_addresses(oversized.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE),
+                Err(Error::CapacityExceeded)
+            );
+            assert_eq!(db.remove_addresses(oversized), Err(Error::CapacityExceeded));
+            let over_capacity = (0..=DEFAULT_MAX_BOOTNODES)
+                .map(|i| format!("/ip4/10.0.2.{}/tcp/1509", i).into_bytes())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                db.add_addresses(over_capacity, DEFAULT_NETWORK_ID, NodeRole::BOOTNODE),
+                Err(Error::CapacityExceeded)
+            );
+            assert!(db.bootstrap_bundle().bootnodes.is_empty());
 
-            // test for equality
-            assert_eq!(db.get_node_addresses(), result);
+            // the value sent is split between the nodes, with an event per node
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(10);
+            let before = ink::env::test::recorded_events().count();
+            db.add_addresses(addrs.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                .unwrap();
+            assert_eq!(ink::env::test::recorded_events().count(), before + 3);
+            assert_eq!(db.bootstrap_bundle().bootnodes, addrs);
+            assert_eq!(db.get_node_record(addrs[0].clone()).unwrap().stake, 4);
+            assert_eq!(db.get_node_record(addrs[2].clone()).unwrap().stake, 3);
+
+            // each entry is checked as on its own
+            assert_eq!(
+                db.add_addresses(
+                    vec![addrs[0].clone()],
+                    DEFAULT_NETWORK_ID,
+                    NodeRole::BOOTNODE
+                ),
+                Err(Error::AlreadyExists)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                db.remove_addresses(addrs[..2].to_vec()),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            db.remove_addresses(addrs[..2].to_vec()).unwrap();
+            assert_eq!(db.bootstrap_bundle().bootnodes, vec![addrs[2].clone()]);
+            assert_eq!(db.claimable_balance(accounts.alice), 7);
         }
 
         #[ink::test]
-        fn creation_works() {
+        fn address_updates_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
             let mut db = DbContract::new();
-            let did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
-                .as_bytes()
-                .to_vec();
-            // let ht_cid = "Qmsdujfhsd8sg8s8483nhn10vddfi".as_bytes().to_vec();
-            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
+            let first = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
+            let old = "/ip4/192.168.44.206/tcp/1509".as_bytes().to_vec();
+            let new = "/ip4/192.168.44.207/udp/1509/quic-v1/p2p/12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN"
                 .as_bytes()
                 .to_vec();
-            let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
-            db.new_account(
-                did.clone(),
-                cid.clone(),
-                /* authentication material */ auth_material.clone(),
+            db.add_address(first.clone(), DEFAULT_NETWORK_ID, NodeRole::BOOTNODE)
+                .unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(50);
+            db.add_address_for(
+                old.clone(),
+                DEFAULT_NETWORK_ID,
+                NodeRole::BOOTNODE | NodeRole::RELAY,
+                accounts.bob,
+            )
+            .unwrap();
+            let before = db.get_node_record(old.clone()).unwrap();
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(
+                db.update_address(old.clone(), new.clone()),
+                Err(Error::Unauthorized)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                db.update_address(old.clone(), first.clone()),
+                Err(Error::AlreadyExists)
+            );
+            assert_eq!(
+                db.update_address(old.clone(), b"garbage".to_vec()),
+                Err(Error::InvalidMultiaddr(ValidationError::Malformed))
             );
 
-            assert_eq!(db.get_account_ht_cid(did, auth_material), cid);
+            // the record moves to the new address in place
+            db.update_address(old.clone(), new.clone()).unwrap();
+            assert_eq!(db.get_node_record(old.clone()), None);
+            let after = db.get_node_record(new.clone()).unwrap();
+            assert_eq!(after.registered_at_block, before.registered_at_block);
+            assert_eq!(after.stake, 50);
+            assert_eq!(after.roles, NodeRole::BOOTNODE | NodeRole::RELAY);
+            assert_eq!(after.operator, accounts.bob);
+            assert_eq!(
+                after.peer_id,
+                b"12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN"
+            );
+            assert_eq!(db.bootstrap_bundle().bootnodes, vec![first, new.clone()]);
+            assert_eq!(
+                db.get_nodes_by_transport(DEFAULT_NETWORK_ID, Transport::Quic, 10),
+                vec![new]
+            );
+            assert_eq!(
+                db.update_address(old, b"/ip4/10.0.0.1/tcp/1".to_vec()),
+                Err(Error::Unauthorized)
+            );
         }
 
         #[ink::test]
-        fn subscribing_flow_works() {
-            let mut db = DbContract::new();
-            let did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
+        fn network_namespacing_works() {
+            let mut db = DbContract::with_network_id(7);
+            let did = "did:sam:apps:DS8YWpeHYrXxY4Nk7bXJvt9cPhJyVyQGHgJ35WdKwLDJzAh"
                 .as_bytes()
                 .to_vec();
             let addr = "/ip4/192.168.44.205/tcp/1509".as_bytes().to_vec();
 
-            // subscribe
-            db.subscribe_node(did.clone(), addr.clone());
-
-            // get subscribers
+            // registrations for another network are rejected
+            assert_eq!(
+                db.add_address(addr.clone(), 8, NodeRole::BOOTNODE),
+                Err(Error::NetworkMismatch)
+            );
             assert_eq!(
-                db.get_subscribers(did.clone()),
-                "/ip4/192.168.44.205/tcp/1509$$$".as_bytes().to_vec(),
+                db.subscribe_node(did.clone(), addr.clone(), 8),
+                Err(Error::NetworkMismatch)
             );
+            db.add_address(addr.clone(), 7, NodeRole::BOOTNODE).unwrap();
+            db.subscribe_node(did.clone(), addr.clone(), 7).unwrap();
 
-            // delete subscribers
-            db.unsubscribe_node(did.clone(), addr.clone());
-            assert_eq!(db.get_subscribers(did.clone()), Vec::new());
+            // queries only answer for the network they are asked about
+            assert_eq!(
+                db.get_nodes_by_role(7, NodeRole::BOOTNODE),
+                vec![addr.clone()]
+            );
+            assert_eq!(db.get_random_bootnodes(7, 1, 0), vec![addr.clone()]);
+            assert_eq!(db.get_subscribers(did.clone(), 7), vec![addr.clone()]);
+            assert_eq!(
+                db.get_node_subscriptions(addr.clone(), 7),
+                vec![did.clone()]
+            );
+            assert!(db.get_nodes_by_role(8, NodeRole::BOOTNODE).is_empty());
+            assert!(db.get_random_bootnodes(8, 1, 0).is_empty());
+            assert!(db.get_node_addresses_by_reputation(8, 0).is_empty());
+            assert!(db.get_nodes_by_transport(8, Transport::Tcp, 10).is_empty());
+            assert!(db.get_subscribers(did.clone(), 8).is_empty());
+            assert_eq!(
+                db.get_subscribers_paged(did.clone(), 8, 0, 10),
+                (Vec::new(), 0)
+            );
+            assert!(db.get_subscribers_with_tenure(did, 8).is_empty());
+            assert!(db.get_node_subscriptions(addr, 8).is_empty());
         }
 
         #[ink::test]
-        fn access_control_flow_works() {
+        fn payment_receipts_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
             let mut db = DbContract::new();
-
-            // create user
-            let did = "did:sam:user:subfgns89fgg09sgs0j9fusj0fjd"
-                .as_bytes()
-                .to_vec();
-
+            let did = "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
+            let clone_did = "did:sam:apps:F9S0DF9S0DS0SD9F0S".as_bytes().to_vec();
             let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
                 .as_bytes()
                 .to_vec();
-
             let auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
+            let free_did = "did:sam:user:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec();
 
+            // calls sent without value get no receipt
+            let before = ink::env::test::recorded_events().count();
             db.new_account(
-                did.clone(),
+                free_did.clone(),
                 cid.clone(),
-                /* authentication material */ auth_material.clone(),
+                hash_auth_material(&auth_material),
+            )
+            .unwrap();
+            let unpaid_events = ink::env::test::recorded_events().count() - before;
+            assert_eq!(db.paid_total(accounts.alice), 0);
+
+            let config = Config {
+                registration_fee: 100,
+                ..db.get_config()
+            };
+            let id = db.update_config(config).unwrap();
+            for _ in 0..DEFAULT_TIMELOCK_DELAY {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+            db.execute_scheduled(id).unwrap();
+            // roll the epoch over so its report doesn't count below
+            db.update_account_ht_cid(free_did, cid.clone()).unwrap();
+
+            // the fee is kept, the excess is credited back, and a receipt is emitted
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(150);
+            let before = ink::env::test::recorded_events().count();
+            db.new_account(did.clone(), cid, hash_auth_material(&auth_material))
+                .unwrap();
+            assert_eq!(
+                ink::env::test::recorded_events().count(),
+                before + unpaid_events + 1
             );
+            assert_eq!(db.paid_total(accounts.alice), 100);
+            assert_eq!(db.claimable_balance(accounts.alice), 50);
 
-            // create application
-            let app_did = "did:sam:apps:subfgns89fgg09sgs0j9fusj0fjd"
-                .as_bytes()
-                .to_vec();
+            // totals add up across operations
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            db.clone_app(did, clone_did, Default::default()).unwrap();
+            assert_eq!(db.paid_total(accounts.alice), 200);
+            assert_eq!(db.claimable_balance(accounts.alice), 50);
+            assert_eq!(db.paid_total(accounts.bob), 0);
+            assert_eq!(db.epoch_activity().fees_collected, 200);
+        }
 
-            let app_cid = "Qmjhggfztfiov7zfbvyzhiuW8sxrNDobFEQZ6ER5qg2wW"
+        #[ink::test]
+        fn fee_burning_works() {
+            let contract = ink::env::account_id::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_account_balance::<ink::env::DefaultEnvironment>(contract, 1_000);
+            let mut db = DbContract::new();
+            let cid = "QmfSnGmfexFsLDkbgN76Qhx2W8sxrNDobFEQZ6ER5qg2wW"
                 .as_bytes()
                 .to_vec();
+            let auth_hash = hash_auth_material(b"bfdh87y*(TD*&^*S&io");
+            let config = Config {
+                fee_burn_percent: 101,
+                ..db.get_config()
+            };
+            assert_eq!(db.update_config(config), Err(Error::InvalidConfig));
+            db.config.registration_fee = 100;
+            db.config.fee_burn_percent = 30;
 
-            let app_auth_material = "bfdh87y*(TD*&^*S&io".as_bytes().to_vec();
-
+            // the burned share of each fee is counted and kept out of the treasury
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
             db.new_account(
-                app_did.clone(),
-                app_cid.clone(),
-                /* authentication material */ app_auth_material.clone(),
-            );
-
-            // restrict app access
-            db.restrict(did.clone(), app_did.clone());
-
-            // check for restrictions
-            assert!(db.is_restricted(did.clone(), app_did.clone()));
-
-            // unrestrict and check again
-            db.unrestrict(did.clone(), app_did.clone());
-
-            // check for restrictions
-            assert!(!db.is_restricted(did.clone(), app_did.clone()));
+                "did:sam:apps:DS0SD9F0SF9S0DF9S0".as_bytes().to_vec(),
+                cid.clone(),
+                auth_hash,
+            )
+            .unwrap();
+            assert_eq!(db.burned_total(), 30);
+            assert_eq!(db.treasury(), 970);
+            db.new_account(
+                "did:sam:apps:F9S0DF9S0DS0SD9F0S".as_bytes().to_vec(),
+                cid,
+                auth_hash,
+            )
+            .unwrap();
+            assert_eq!(db.burned_total(), 60);
+            assert_eq!(db.treasury(), 940);
         }
     }
 }